@@ -0,0 +1,57 @@
+/* unbase-fsck
+ *
+ * Checks the integrity of one or more slabs stored with the File storage backend.
+ *
+ * Usage: unbase-fsck <path>...
+ *
+ * Each finding is printed on its own line, prefixed by the path of the slab in which it was found.
+ * Exits with status 1 if anything was found amiss, or 2 if a slab could not be opened.
+*/
+
+extern crate unbase;
+use unbase::slab::storage;
+use std::env;
+use std::process;
+
+fn main() {
+    let paths : Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: unbase-fsck <path>...");
+        process::exit(2);
+    }
+
+    let mut status = 0;
+
+    for path in paths.iter() {
+        let store = match storage::File::open(path) {
+            Ok(store) => store,
+            Err(e) => {
+                eprintln!("{}: unable to open storage: {:?}", path, e);
+                status = 2;
+                continue;
+            }
+        };
+
+        let net = unbase::Network::new();
+        let slab = match unbase::Slab::new_with_storage(&net, Box::new(store)) {
+            Ok(slab) => slab,
+            Err(e) => {
+                eprintln!("{}: unable to restore slab: {:?}", path, e);
+                status = 2;
+                continue;
+            }
+        };
+
+        let report = slab.verify();
+        for finding in report.findings.iter() {
+            println!("{}: {}", path, finding);
+        }
+        println!("{}: slab {} - {} memorefs, {} heads, {} findings", path, report.slab_id, report.memorefs, report.heads, report.findings.len());
+
+        if !report.is_clean() && status == 0 {
+            status = 1;
+        }
+    }
+
+    process::exit(status);
+}
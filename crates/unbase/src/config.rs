@@ -0,0 +1,262 @@
+/* Configuration
+ *
+ * Tunables for networks and slabs. Each starts from its defaults, optionally overlaid by a JSON file,
+ * and then by environment variables, such that a deployment may adjust them without forking the crate.
+ *
+ * A configuration file need only mention the settings it wishes to change:
+ *
+ *   { "local_transport": false, "slab": { "request_fanout": 3, "memory_budget": 67108864 } }
+ *
+ * Environment variables are named UNBASE_<SETTING> for network settings, and UNBASE_SLAB_<SETTING> for
+ * slab settings, eg: UNBASE_SLAB_RETRIEVAL_TIMEOUT_MS=500. UNBASE_SLAB_MEMORY_BUDGET=none removes the budget,
+ * as it does either materialization threshold, and likewise UNBASE_METRICS_ADDRESS=none disables the metrics listener.
+*/
+
+use crate::slab::{DurabilityScore,EmissionStrategy,PeerSelection};
+use crate::error::ConfigError;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use serde_json;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlabConfig {
+    /// Maximum number of peers to which a given MemoRequest is sent
+    pub request_fanout: usize,
+    /// How long to wait for a requested memo before asking again
+    pub retrieval_timeout_ms: u64,
+    /// Number of times a memo is requested before retrieval fails
+    pub retrieval_attempts: u32,
+    /// The subject id counter of a newly created slab
+    pub initial_subject_counter: u32,
+    /// Depth of the root index tree
+    pub index_depth: u8,
+    /// Window over which peering updates for a given destination are coalesced. Zero disables coalescing
+    pub peering_window_ms: u64,
+    /// Durability target for memos lacking a more specific target
+    pub default_durability_target: DurabilityScore,
+    /// Approximate number of bytes of resident memos to retain. None for unlimited
+    pub memory_budget: Option<usize>,
+    /// Number of threads which convey received memos to subscribed contexts
+    pub dispatch_workers: usize,
+    /// Most memoref batches which may await dispatch before receipt of further memos waits for room
+    pub dispatch_queue_limit: usize,
+    /// Most peers retained in the peerlist of each memo
+    pub peerlist_limit: usize,
+    /// How memos are disseminated to peers
+    pub emission_strategy: EmissionStrategy,
+    /// Under the Plumtree emission strategy, how long to wait for an announced memo before grafting it
+    pub graft_timeout_ms: u64,
+    /// How peers are chosen to receive replicas, and to be asked for memos
+    pub peer_selection: PeerSelection,
+    /// Number of memos since the last FullyMaterialized memo of a subject, at which it is materialized automatically. None to disable
+    pub materialize_after_edits: Option<usize>,
+    /// Approximate number of bytes of memos since the last FullyMaterialized memo of a subject, at which it is materialized automatically. None to disable
+    pub materialize_after_bytes: Option<usize>,
+    /// How long a slab which is shutting down waits for its peers to acknowledge the memos handed off to them
+    pub handoff_timeout_ms: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConfig {
+    /// Whether the LocalDirect transport is added automatically
+    pub local_transport: bool,
+    /// Address on which to serve metrics over HTTP, eg: "127.0.0.1:9898". None to disable
+    pub metrics_address: Option<String>,
+    /// Configuration for slabs created against this network, unless otherwise specified
+    pub slab: SlabConfig,
+}
+
+/// The settings of a JSON configuration document, or of its "slab" section
+type JsonSettings = serde_json::Map<String, serde_json::Value>;
+
+impl Default for SlabConfig {
+    fn default () -> Self {
+        SlabConfig {
+            request_fanout:            5,
+            retrieval_timeout_ms:      1000,
+            retrieval_attempts:        3,
+            initial_subject_counter:   9000,
+            index_depth:               5,
+            peering_window_ms:         10,
+            default_durability_target: 10,
+            memory_budget:             None,
+            dispatch_workers:          4,
+            dispatch_queue_limit:      1024,
+            peerlist_limit:            10,
+            emission_strategy:         EmissionStrategy::Direct,
+            graft_timeout_ms:          100,
+            peer_selection:            PeerSelection::HighestLifetime,
+            materialize_after_edits:   Some(256),
+            materialize_after_bytes:   None,
+            handoff_timeout_ms:        1000,
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default () -> Self {
+        NetworkConfig {
+            local_transport: true,
+            metrics_address: None,
+            slab:            SlabConfig::default(),
+        }
+    }
+}
+
+impl SlabConfig {
+    /// Defaults, overlaid by any UNBASE_SLAB_* environment variables
+    pub fn from_env () -> Result<Self,ConfigError> {
+        let mut config = Self::default();
+        config.apply_env()?;
+        Ok(config)
+    }
+    pub fn retrieval_timeout (&self) -> Duration {
+        Duration::from_millis(self.retrieval_timeout_ms)
+    }
+    pub fn peering_window (&self) -> Duration {
+        Duration::from_millis(self.peering_window_ms)
+    }
+    pub fn graft_timeout (&self) -> Duration {
+        Duration::from_millis(self.graft_timeout_ms)
+    }
+    pub fn handoff_timeout (&self) -> Duration {
+        Duration::from_millis(self.handoff_timeout_ms)
+    }
+    pub fn apply_env (&mut self) -> Result<(),ConfigError> {
+        env_override("UNBASE_SLAB_REQUEST_FANOUT",            &mut self.request_fanout)?;
+        env_override("UNBASE_SLAB_RETRIEVAL_TIMEOUT_MS",      &mut self.retrieval_timeout_ms)?;
+        env_override("UNBASE_SLAB_RETRIEVAL_ATTEMPTS",        &mut self.retrieval_attempts)?;
+        env_override("UNBASE_SLAB_INITIAL_SUBJECT_COUNTER",   &mut self.initial_subject_counter)?;
+        env_override("UNBASE_SLAB_INDEX_DEPTH",               &mut self.index_depth)?;
+        env_override("UNBASE_SLAB_PEERING_WINDOW_MS",         &mut self.peering_window_ms)?;
+        env_override("UNBASE_SLAB_DEFAULT_DURABILITY_TARGET", &mut self.default_durability_target)?;
+        env_override("UNBASE_SLAB_DISPATCH_WORKERS",          &mut self.dispatch_workers)?;
+        env_override("UNBASE_SLAB_DISPATCH_QUEUE_LIMIT",      &mut self.dispatch_queue_limit)?;
+        env_override("UNBASE_SLAB_PEERLIST_LIMIT",            &mut self.peerlist_limit)?;
+        env_override("UNBASE_SLAB_EMISSION_STRATEGY",         &mut self.emission_strategy)?;
+        env_override("UNBASE_SLAB_GRAFT_TIMEOUT_MS",          &mut self.graft_timeout_ms)?;
+        env_override("UNBASE_SLAB_PEER_SELECTION",            &mut self.peer_selection)?;
+        env_override("UNBASE_SLAB_HANDOFF_TIMEOUT_MS",        &mut self.handoff_timeout_ms)?;
+
+        env_override_option("UNBASE_SLAB_MEMORY_BUDGET",           &mut self.memory_budget)?;
+        env_override_option("UNBASE_SLAB_MATERIALIZE_AFTER_EDITS", &mut self.materialize_after_edits)?;
+        env_override_option("UNBASE_SLAB_MATERIALIZE_AFTER_BYTES", &mut self.materialize_after_bytes)?;
+
+        Ok(())
+    }
+    fn apply_json_settings (&mut self, settings: &JsonSettings) -> Result<(),ConfigError> {
+        json_override(settings, "request_fanout",            &mut self.request_fanout)?;
+        json_override(settings, "retrieval_timeout_ms",      &mut self.retrieval_timeout_ms)?;
+        json_override(settings, "retrieval_attempts",        &mut self.retrieval_attempts)?;
+        json_override(settings, "initial_subject_counter",   &mut self.initial_subject_counter)?;
+        json_override(settings, "index_depth",               &mut self.index_depth)?;
+        json_override(settings, "peering_window_ms",         &mut self.peering_window_ms)?;
+        json_override(settings, "default_durability_target", &mut self.default_durability_target)?;
+        json_override(settings, "dispatch_workers",          &mut self.dispatch_workers)?;
+        json_override(settings, "dispatch_queue_limit",      &mut self.dispatch_queue_limit)?;
+        json_override(settings, "peerlist_limit",            &mut self.peerlist_limit)?;
+        json_override(settings, "emission_strategy",         &mut self.emission_strategy)?;
+        json_override(settings, "graft_timeout_ms",          &mut self.graft_timeout_ms)?;
+        json_override(settings, "peer_selection",            &mut self.peer_selection)?;
+        json_override(settings, "handoff_timeout_ms",        &mut self.handoff_timeout_ms)?;
+
+        json_override_option(settings, "memory_budget",           &mut self.memory_budget)?;
+        json_override_option(settings, "materialize_after_edits", &mut self.materialize_after_edits)?;
+        json_override_option(settings, "materialize_after_bytes", &mut self.materialize_after_bytes)?;
+
+        Ok(())
+    }
+}
+
+impl NetworkConfig {
+    /// Defaults, overlaid by the given JSON configuration file ( if any ) and then by the environment
+    pub fn load<P: AsRef<Path>> (path: Option<P>) -> Result<Self,ConfigError> {
+        let mut config = Self::default();
+        if let Some(path) = path {
+            config.apply_file(path)?;
+        }
+        config.apply_env()?;
+        Ok(config)
+    }
+    pub fn from_env () -> Result<Self,ConfigError> {
+        Self::load(None::<&Path>)
+    }
+    pub fn apply_file<P: AsRef<Path>> (&mut self, path: P) -> Result<(),ConfigError> {
+        let bytes = fs::read(path)?;
+        self.apply_json(&bytes)
+    }
+    /// Apply those settings which are present in the given JSON document
+    pub fn apply_json (&mut self, bytes: &[u8]) -> Result<(),ConfigError> {
+        let document : serde_json::Value = serde_json::from_slice(bytes).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        let settings = json_object(&document, "configuration")?;
+
+        json_override(settings, "local_transport", &mut self.local_transport)?;
+        json_override_option(settings, "metrics_address", &mut self.metrics_address)?;
+
+        match settings.get("slab") {
+            None | Some(&serde_json::Value::Null) => Ok(()),
+            Some(slab)                            => self.slab.apply_json_settings(json_object(slab, "slab")?)
+        }
+    }
+    pub fn apply_env (&mut self) -> Result<(),ConfigError> {
+        env_override("UNBASE_LOCAL_TRANSPORT", &mut self.local_transport)?;
+
+        if let Ok(value) = env::var("UNBASE_METRICS_ADDRESS") {
+            self.metrics_address = match value.as_str() {
+                "" | "none" => None,
+                _           => Some(value)
+            };
+        }
+        self.slab.apply_env()
+    }
+}
+
+fn env_override<T: FromStr> (var: &str, target: &mut T) -> Result<(),ConfigError> {
+    if let Ok(value) = env::var(var) {
+        *target = value.parse().map_err(|_| ConfigError::InvalidValue(var.to_string(), value.clone()))?;
+    }
+    Ok(())
+}
+/// As env_override, for settings which may be disabled with an empty value or "none"
+fn env_override_option<T: FromStr> (var: &str, target: &mut Option<T>) -> Result<(),ConfigError> {
+    if let Ok(value) = env::var(var) {
+        *target = match value.as_str() {
+            "" | "none" => None,
+            _           => Some(value.parse().map_err(|_| ConfigError::InvalidValue(var.to_string(), value.clone()))?)
+        };
+    }
+    Ok(())
+}
+
+fn json_object<'a> (value: &'a serde_json::Value, what: &str) -> Result<&'a JsonSettings,ConfigError> {
+    value.as_object().ok_or_else(|| ConfigError::Parse(format!("{} should be an object", what)))
+}
+/// A setting of a JSON document as text, such that it's parsed just as the environment variable would be.
+/// Those which are absent or null are left as they are
+fn json_setting (settings: &JsonSettings, key: &str) -> Result<Option<String>,ConfigError> {
+    use serde_json::Value;
+
+    match settings.get(key) {
+        None | Some(&Value::Null)                => Ok(None),
+        Some(Value::String(s))              => Ok(Some(s.clone())),
+        Some(&Value::Bool(b))                    => Ok(Some(b.to_string())),
+        Some(Value::Number(n))              => Ok(Some(n.to_string())),
+        Some(&Value::Array(_)) | Some(&Value::Object(_)) => Err(ConfigError::Parse(format!("{} should be a single value", key)))
+    }
+}
+fn json_override<T: FromStr> (settings: &JsonSettings, key: &str, target: &mut T) -> Result<(),ConfigError> {
+    if let Some(value) = json_setting(settings, key)? {
+        *target = value.parse().map_err(|_| ConfigError::Parse(format!("invalid value for {}: {}", key, value)))?;
+    }
+    Ok(())
+}
+fn json_override_option<T: FromStr> (settings: &JsonSettings, key: &str, target: &mut Option<T>) -> Result<(),ConfigError> {
+    if let Some(value) = json_setting(settings, key)? {
+        *target = Some(value.parse().map_err(|_| ConfigError::Parse(format!("invalid value for {}: {}", key, value)))?);
+    }
+    Ok(())
+}
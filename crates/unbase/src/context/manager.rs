@@ -13,9 +13,14 @@ type ItemId = usize;
 #[derive(Clone)]
 struct Item {
     subject_id: SubjectId,
+    /// The number of paths by which this item is referenced, saturating. Paths are not followed around cycles
     indirect_references: isize,
+    /// The length of the longest chain of references leading to this item, not counting those around cycles
+    depth: usize,
     head: Option<MemoRefHead>,
     relations: Vec<Option<ItemId>>,
+    /// The items which relate to this one, once per relation
+    referrers: Vec<ItemId>,
 }
 
 /// Performs topological sorting.
@@ -30,7 +35,9 @@ impl Item {
             subject_id: subject_id,
             head: maybe_head,
             indirect_references: 0,
+            depth: 0,
             relations: Vec::new(),
+            referrers: Vec::new(),
         }
     }
 }
@@ -111,12 +118,17 @@ impl ContextManager {
             item.head = Some(head);
         }
 
+        // The items whose referrers changed
+        let mut affected = Vec::new();
         for link in relation_links {
-            self.set_relation(item_id, link);
+            self.set_relation(item_id, link, &mut affected);
+        }
+
+        if !affected.is_empty() {
+            self.recount_indirect_references(affected);
         }
     }
 
-    #[allow(dead_code)]
     pub fn remove_subject_head(&mut self, subject_id: SubjectId ) {
         if let Some(item_id) = self.items.iter().position(|i| {
             if let &Some(ref it) = i {
@@ -125,45 +137,22 @@ impl ContextManager {
                 false
             }
         }) {
-            let mut full_remove = false;
-            let mut relations = Vec::new();
-            let decrement;
-            let items_len = self.items.len();
-
-            {
-                if let Some(ref mut item) = self.items[item_id] {
-                    decrement = 0 - (item.indirect_references + 1);
-                    for relation in item.relations.iter() {
-                        if let Some(rel_item_id) = *relation {
-                            relations.push(rel_item_id);
-                        }
-                    }
-                
-                    item.relations.clear();
-
-                    if item.indirect_references == 0 {
-                        // If nobody points to me, we can fully bail out
-                        full_remove = true;
-                    }else{
-                        // otherwise just remove the head that we intend to remove
-                        item.head = None;
-                    }
-                }else{
-                    panic!("sanity error");
-                }
-
-                if full_remove {
-                    self.items[item_id] = None;
-                    self.vacancies.push(item_id);
-                }
-            }
+            // no head means we're not pointing to anything anymore, at least not within the context manager
+            let relations : Vec<ItemId> = if let Some(ref mut item) = self.items[item_id] {
+                item.head = None;
+                item.relations.drain(..).flatten().collect()
+            } else {
+                panic!("sanity error");
+            };
 
-            // no head means we're not pointing to these anymore, at least not within the context manager
-            for rel_item_id in relations {
-                let mut removed = vec![false; items_len];
-                self.increment(rel_item_id, decrement, &mut removed);
+            for rel_item_id in relations.iter() {
+                self.remove_referrer(*rel_item_id, item_id);
             }
 
+            // If nobody points to me, the recount fully removes me
+            let mut affected = relations;
+            affected.push(item_id);
+            self.recount_indirect_references(affected);
         }
 
     }
@@ -192,122 +181,120 @@ impl ContextManager {
         }
     }
 
-    fn set_relation(&mut self, item_id: ItemId, link: RelationLink) {
+    /// Points the given slot of an item at the linked subject, noting the items whose referrers changed
+    fn set_relation(&mut self, item_id: ItemId, link: RelationLink, affected: &mut Vec<ItemId>) {
 
-        // let item = &self.items[item_id];
         // retrieve existing relation by SlotId as the vec offset
         // Some(&Some()) due to empty vec slot vs None relation (logically equivalent)
-        let mut remove = None;
-        {
-            let item = {
-                if let Some(ref item) = self.items[item_id] {
-                    item
-                } else {
-                    panic!("sanity error. set relation on item that does not exist")
-                }
-            };
+        let existing = match self.items[item_id] {
+            Some(ref item) => item.relations.get(link.slot_id as usize).cloned().unwrap_or(None),
+            None           => panic!("sanity error. set relation on item that does not exist"),
+        };
 
-            if let Some(&Some(rel_item_id)) = item.relations.get(link.slot_id as usize) {
-                // relation exists
+        let existing_subject_id = existing.map(|rel_item_id| {
+            match self.items[rel_item_id] {
+                Some(ref rel_item) => rel_item.subject_id,
+                None               => panic!("sanity error. relation item_id located, but not found in items"),
+            }
+        });
 
-                let decrement;
-                {
-                    if let &Some(ref rel_item) = &self.items[rel_item_id] {
+        // no change. bail out
+        if existing_subject_id == link.subject_id {
+            return;
+        }
 
-                        // no change. bail out. do not increment or decrement
-                        if Some(rel_item.subject_id) == link.subject_id {
-                            return;
-                        }
+        let new_rel_item_id = link.subject_id.map(|subject_id| self.assert_item(subject_id));
 
-                        decrement = 0 - (1 + item.indirect_references);
-                    } else {
-                        panic!("sanity error. relation item_id located, but not found in items")
-                    }
-                }
+        if let &mut Some(ref mut item) = &mut self.items[item_id] {
+            while item.relations.len() <= link.slot_id as usize {
+                item.relations.push(None);
+            }
 
-                remove = Some((rel_item_id, decrement));
-            };
+            // it's essential to overwrite a Some() if it's there
+            item.relations[link.slot_id as usize] = new_rel_item_id;
+        } else {
+            panic!("sanity error. relation item not found in items")
         }
 
+        if let Some(rel_item_id) = existing {
+            self.remove_referrer(rel_item_id, item_id);
+            affected.push(rel_item_id);
+        }
+        if let Some(rel_item_id) = new_rel_item_id {
+            if let &mut Some(ref mut rel_item) = &mut self.items[rel_item_id] {
+                rel_item.referrers.push(item_id);
+            }
+            affected.push(rel_item_id);
+        }
+    }
+    fn remove_referrer(&mut self, item_id: ItemId, referrer: ItemId) {
+        if let &mut Some(ref mut item) = &mut self.items[item_id] {
+            if let Some(index) = item.referrers.iter().position(|r| *r == referrer) {
+                let _ = item.referrers.swap_remove(index);
+            }
+        }
+    }
 
-        // ruh roh, we're different. Have to back out the old relation
-        // (a little friendly sparring with the borrow checker :-x )
-        if let Some((rel_item_id, decrement)) = remove {
-            let mut removed = vec![false; self.items.len()];
-            {
-                self.increment(rel_item_id, decrement, &mut removed)
-            };
-            // item.relations[link.slot_id] MUST be set below
+    /// Recalculates indirect_references and depth for the items reachable from those whose referrers changed, and
+    /// removes those which have neither a head nor anybody pointing to them.
+    ///
+    /// Adjusting the counts incrementally doesn't survive cycles: a relation added within a cycle feeds back into
+    /// its own count, and removing it later takes away more than was ever added. Instead, the strongly connected
+    /// components of the affected region are found, and each is counted once, in topological order, from the counts
+    /// of its referrers outside of it. This is linear in the size of the region.
+    fn recount_indirect_references(&mut self, affected: Vec<ItemId>) {
+        let mut search = ComponentSearch::new(self.items.len());
+        for item_id in affected {
+            if search.index[item_id] == UNVISITED && self.items[item_id].is_some() {
+                search.visit(&self.items, item_id);
+            }
         }
 
-        if let Some(subject_id) = link.subject_id {
-            let new_rel_item_id = {
-                self.assert_item(subject_id)
-            };
+        // Components are found in reverse topological order. Those which refer to a component precede it
+        let mut component_of = vec![UNVISITED; self.items.len()];
+        for (component_id, component) in search.components.iter().enumerate() {
+            for item_id in component.iter() {
+                component_of[*item_id] = component_id;
+            }
+        }
 
-            let increment;
-            {
-                if let &mut Some(ref mut item) = &mut self.items[item_id] {
-                    while item.relations.len() <= link.slot_id as usize { 
-                        item.relations.push(None);
-                    }
+        for (component_id, component) in search.components.iter().enumerate().rev() {
+            let mut indirect_references : isize = 0;
+            let mut depth = 0;
 
-                    item.relations[link.slot_id as usize] = Some(new_rel_item_id);
-                    increment = 1 + item.indirect_references;
-                } else {
-                    panic!("sanity error. relation just set")
+            for item_id in component.iter() {
+                if let Some(ref item) = self.items[*item_id] {
+                    for referrer in item.referrers.iter() {
+                        if component_of[*referrer] == component_id {
+                            continue;
+                        }
+                        if let Some(ref referring_item) = self.items[*referrer] {
+                            indirect_references = indirect_references.saturating_add(1).saturating_add(referring_item.indirect_references);
+                            depth = depth.max(referring_item.depth + 1);
+                        }
+                    }
                 }
-            };
+            }
 
-            let mut added = vec![false; self.items.len()];
-            self.increment(new_rel_item_id, increment, &mut added);
-        } else {
-            // sometimes this will be unnecessary, but it's essential to overwrite a Some() if it's there
-            if let &mut Some(ref mut item) = &mut self.items[item_id] {
-                while item.relations.len() <= link.slot_id as usize { 
-                    item.relations.push(None);
+            for item_id in component.iter() {
+                if let &mut Some(ref mut item) = &mut self.items[*item_id] {
+                    item.indirect_references = indirect_references;
+                    item.depth = depth;
                 }
-
-                item.relations[link.slot_id as usize] = None;
-
-            } else {
-                panic!("sanity error. relation item not found in items")
             }
         }
-    }
-    fn increment(&mut self, item_id: ItemId, increment: isize, seen: &mut Vec<bool>) {
-        // Avoid traversing cycles
-        if Some(&true) == seen.get(item_id) {
-            return; // dejavu! Bail out
-        }
-        seen[item_id] = true;
-
-        let relations: Vec<ItemId>;
-        let mut remove = false;
-        {
-            if let &mut Some(ref mut item) = &mut self.items[item_id] {
-                item.indirect_references += increment;
-                if item.indirect_references == 0 && item.head.is_none(){
-                    remove = true;
-                }
-                assert!(item.indirect_references >= 0,
-                        "sanity error. indirect_references below zero");
 
-                relations = item.relations.iter().filter_map(|r| *r).collect();
-            } else {
-                panic!("sanity error. increment for item_id");
-            }
-        };
-
-        if remove {
-            self.items[item_id] = None;
-            self.vacancies.push(item_id);
-        }
+        for item_id in search.components.into_iter().flat_map(|c| c.into_iter()) {
+            let remove = match self.items[item_id] {
+                Some(ref item) => item.head.is_none() && item.referrers.is_empty(),
+                None           => false
+            };
 
-        for rel_item_id in relations {
-            self.increment(rel_item_id, increment, seen);
+            if remove {
+                self.items[item_id] = None;
+                self.vacancies.push(item_id);
+            }
         }
-
     }
     pub fn subject_head_iter(&self) -> SubjectHeadIter {
         SubjectHeadIter::new(&self.items)
@@ -341,8 +328,8 @@ impl SubjectHeadIter {
         // Approach B: keep Vec<item> sorted (DESC) by indirect_references, and reset the increment whenever the sort changes
 
         // FOR now, taking the low road
-        // Vec<(usize, MemoRefHead, Vec<SubjectId>)>
-        let mut subject_heads: Vec<SubjectHead> = items.iter()
+
+        let mut subject_heads: Vec<(usize, SubjectHead)> = items.iter()
             .filter_map(|i| {
                 if let &Some(ref item) = i {
                     if let Some(ref head) = item.head {
@@ -362,13 +349,22 @@ impl SubjectHeadIter {
                             })
                             .collect();
 
-                        return Some(SubjectHead {
+                        let mut from_subject_ids: Vec<SubjectId> = Vec::new();
+                        for referrer in item.referrers.iter() {
+                            if let Some(ref referring_item) = items[*referrer] {
+                                if !from_subject_ids.contains(&referring_item.subject_id) {
+                                    from_subject_ids.push(referring_item.subject_id);
+                                }
+                            }
+                        }
+
+                        return Some((item.depth, SubjectHead {
                             subject_id: item.subject_id,
                             indirect_references: item.indirect_references as usize,
                             head: head.clone(),
-                            from_subject_ids: vec![],
+                            from_subject_ids,
                             to_subject_ids: relation_subject_ids,
-                        });
+                        }));
                     }
                 }
                 None
@@ -377,17 +373,76 @@ impl SubjectHeadIter {
 
         // Ascending sort here, because the iterator is using pop
         // TODO: be sure to reverse this later if we switch to incremental calculation
-        subject_heads.sort_by(|a, b| a.indirect_references.cmp(&b.indirect_references));
+        // Depth settles the order among those whose counts are saturated
+        subject_heads.sort_by_key(|a| (a.1.indirect_references, a.0));
+        let subject_heads = subject_heads.into_iter().map(|(_, subject_head)| subject_head).collect();
 
         SubjectHeadIter { sorted: subject_heads }
     }
 }
 
+const UNVISITED : usize = usize::MAX;
+
+/// Tarjan's search for the strongly connected components reachable from a given set of items
+struct ComponentSearch {
+    index:      Vec<usize>,
+    lowlink:    Vec<usize>,
+    on_stack:   Vec<bool>,
+    stack:      Vec<ItemId>,
+    next_index: usize,
+    components: Vec<Vec<ItemId>>,
+}
+
+impl ComponentSearch {
+    fn new(len: usize) -> Self {
+        ComponentSearch {
+            index:      vec![UNVISITED; len],
+            lowlink:    vec![UNVISITED; len],
+            on_stack:   vec![false; len],
+            stack:      Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        }
+    }
+    fn visit(&mut self, items: &Vec<Option<Item>>, item_id: ItemId) {
+        self.index[item_id] = self.next_index;
+        self.lowlink[item_id] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(item_id);
+        self.on_stack[item_id] = true;
+
+        if let Some(ref item) = items[item_id] {
+            for rel_item_id in item.relations.iter().filter_map(|r| *r) {
+                if self.index[rel_item_id] == UNVISITED {
+                    self.visit(items, rel_item_id);
+                    self.lowlink[item_id] = self.lowlink[item_id].min(self.lowlink[rel_item_id]);
+                } else if self.on_stack[rel_item_id] {
+                    self.lowlink[item_id] = self.lowlink[item_id].min(self.index[rel_item_id]);
+                }
+            }
+        }
+
+        if self.lowlink[item_id] == self.index[item_id] {
+            let mut component = Vec::new();
+            loop {
+                let member = self.stack.pop().expect("sanity error. component search stack");
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == item_id {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
     use crate::{Network, Slab};
     use crate::slab::{MemoBody, RelationSlotSubjectHead};
+    use crate::memorefhead::MemoRefHead;
     use super::ContextManager;
 
     #[test]
@@ -493,25 +548,104 @@ mod test {
         let head4 = slab.new_memo_basic_noparent(Some(4), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::single(0, 3, head3.clone()) }).to_head();
         manager.set_subject_head(4, head4.project_all_relation_links(&slab), head4.clone());
 
-        // Repoint Subject 2 slot 0 to subject 4
+        // Repoint Subject 2 slot 0 to subject 4. The new head is that of Subject 2, and its most recent relation wins
         let head2_b = slab.new_memo_basic(Some(2), head2, MemoBody::Relation(RelationSlotSubjectHead::single(0,4,head4) )).to_head();
-        manager.set_subject_head(4, head2_b.project_all_relation_links(&slab), head2_b);
+        let links = head2_b.project_all_relation_links(&slab);
+        assert_eq!(links[0].subject_id, Some(4), "the relation should override the materialized relation");
+        manager.set_subject_head(2, links, head2_b);
 
 
         // 2[0] -> 1
         // 4[0] -> 3
         // Then:
         // 2[0] -> 4
-        
+        //
+        // Subject 3 is referenced by way of 2 and 4, and Subject 1 no longer at all
+
         let mut iter = manager.subject_head_iter();
         // for subject_head in iter {
         //     println!("{} is {}", subject_head.subject_id, subject_head.indirect_references );
         // }
-        assert_eq!(1, iter.next().expect("iter result 1 should be present").subject_id);
-        assert_eq!(4, iter.next().expect("iter result 4 should be present").subject_id);
         assert_eq!(3, iter.next().expect("iter result 3 should be present").subject_id);
+        assert_eq!(4, iter.next().expect("iter result 4 should be present").subject_id);
+        assert_eq!(2, iter.next().expect("iter result 2 should be present").subject_id);
+        assert_eq!(1, iter.next().expect("iter result 1 should be present").subject_id);
+        assert!(iter.next().is_none(), "iter should have ended");
+    }
+    #[test]
+    fn context_manager_clear_relation() {
+        let net = Network::create_new_system();
+        let slab = Slab::new(&net);
+        let mut manager = ContextManager::new();
+
+        // Subject 1 is pointing to nooobody
+        let head1 = slab.new_memo_basic_noparent(Some(1), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::empty() }).to_head();
+        manager.set_subject_head(1, head1.project_all_relation_links(&slab), head1.clone());
+
+        // Subject 2 slot 0 is pointing to Subject 1
+        let head2 = slab.new_memo_basic_noparent(Some(2), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::single(0, 1, head1.clone()) }).to_head();
+        manager.set_subject_head(2, head2.project_all_relation_links(&slab), head2.clone());
+
+        //Subject 3 slot 0 is pointing to Subject 2
+        let head3 = slab.new_memo_basic_noparent(Some(3), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::single(0, 2, head2.clone()) }).to_head();
+        manager.set_subject_head(3, head3.project_all_relation_links(&slab), head3.clone());
+
+        // Clear Subject 2 slot 0
+        let head2_b = slab.new_memo_basic(Some(2), head2, MemoBody::Relation(RelationSlotSubjectHead::cleared(0) )).to_head();
+        let links = head2_b.project_all_relation_links(&slab);
+        assert!(links[0].subject_id.is_none(), "the tombstone should override the materialized relation");
+        manager.set_subject_head(2, links, head2_b);
+
+        // 2[0] -> 1
+        // 3[0] -> 2
+        // Then:
+        // 2[0] -> nobody
+
+        let mut iter = manager.subject_head_iter();
         assert_eq!(2, iter.next().expect("iter result 2 should be present").subject_id);
+        assert_eq!(3, iter.next().expect("iter result 3 should be present").subject_id);
+        assert_eq!(1, iter.next().expect("iter result 1 should be present").subject_id);
+        assert!(iter.next().is_none(), "iter should have ended");
+    }
+    #[test]
+    fn context_manager_from_subject_ids() {
+        let net = Network::create_new_system();
+        let slab = Slab::new(&net);
+        let mut manager = ContextManager::new();
+
+        // Subject 1 is pointing to nooobody
+        let head1 = slab.new_memo_basic_noparent(Some(1), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::empty() }).to_head();
+        manager.set_subject_head(1, head1.project_all_relation_links(&slab), head1.clone());
+
+        // Subject 2 slot 0 is pointing to Subject 1
+        let head2 = slab.new_memo_basic_noparent(Some(2), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::single(0, 1, head1.clone()) }).to_head();
+        manager.set_subject_head(2, head2.project_all_relation_links(&slab), head2.clone());
+
+        // Subject 3 slot 0 is pointing to Subject 1, and slot 1 to Subject 2
+        let relations = vec![(0, Some((1, head1.clone()))), (1, Some((2, head2.clone())))].into_iter().collect();
+        let head3 = slab.new_memo_basic_noparent(Some(3), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead(relations) }).to_head();
+        manager.set_subject_head(3, head3.project_all_relation_links(&slab), head3.clone());
+
+        let mut iter = manager.subject_head_iter();
+        let subject_head = iter.next().expect("iter result 1 should be present");
+        assert_eq!(1, subject_head.subject_id);
+        let mut from_subject_ids = subject_head.from_subject_ids.clone();
+        from_subject_ids.sort();
+        assert_eq!(from_subject_ids, vec![2, 3]);
+
+        let subject_head = iter.next().expect("iter result 2 should be present");
+        assert_eq!(2, subject_head.subject_id);
+        assert_eq!(subject_head.from_subject_ids, vec![3]);
+
+        let subject_head = iter.next().expect("iter result 3 should be present");
+        assert_eq!(3, subject_head.subject_id);
+        assert!(subject_head.from_subject_ids.is_empty());
         assert!(iter.next().is_none(), "iter should have ended");
+
+        // Once Subject 3 has no head, it no longer points to anybody
+        manager.remove_subject_head(3);
+        let subject_head = manager.subject_head_iter().find(|s| s.subject_id == 1).expect("subject 1 should be present");
+        assert_eq!(subject_head.from_subject_ids, vec![2]);
     }
     #[test]
     fn context_manager_remove() {
@@ -606,4 +740,82 @@ mod test {
         // }
         assert!(iter.next().is_none(), "iter should have ended");
     }
+    #[test]
+    fn context_manager_remove_from_cycle() {
+        let net = Network::create_new_system();
+        let slab = Slab::new(&net);
+        let mut manager = ContextManager::new();
+
+        // Subject 1 slot 0 is pointing to Subject 2, which doesn't have a head yet
+        let head1 = slab.new_memo_basic_noparent(Some(1), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::single(0, 2, MemoRefHead::new()) }).to_head();
+        manager.set_subject_head(1, head1.project_all_relation_links(&slab), head1.clone());
+
+        // Subject 2 slot 0 is pointing back to Subject 1
+        let head2 = slab.new_memo_basic_noparent(Some(2), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead::single(0, 1, head1.clone()) }).to_head();
+        manager.set_subject_head(2, head2.project_all_relation_links(&slab), head2.clone());
+
+        // 1[0] -> 2
+        // 2[0] -> 1
+        // Removing either head must not take away more references than the cycle added
+
+        manager.remove_subject_head(2);
+        assert_eq!(manager.subject_count(), 2);
+        assert_eq!(manager.subject_head_count(), 1);
+
+        let mut iter = manager.subject_head_iter();
+        let subject_head = iter.next().expect("iter result 1 should be present");
+        assert_eq!(1, subject_head.subject_id);
+        assert_eq!(0, subject_head.indirect_references);
+        assert!(iter.next().is_none(), "iter should have ended");
+
+        manager.remove_subject_head(1);
+        assert_eq!(manager.subject_count(), 0);
+        assert_eq!(manager.vacancies(), 2);
+    }
+    #[test]
+    fn context_manager_wide_deep_graph() {
+        let net = Network::create_new_system();
+        let slab = Slab::new(&net);
+        let mut manager = ContextManager::new();
+
+        // Every subject relates to each of those in the layer below. Each layer multiplies the number of paths
+        // by its width, such that the bottom layer is referenced by way of 8^31 paths, and the counts saturate
+        let width = 8;
+        let layers = 32;
+        let mut below : Vec<(u64, MemoRefHead)> = Vec::new();
+
+        for layer in 0..layers {
+            let mut this_layer = Vec::new();
+            for i in 0..width {
+                let subject_id = (layer * width + i + 1) as u64;
+                let relations = below.iter().enumerate().map(|(slot_id, &(rel_subject_id, ref head))| {
+                    (slot_id as u8, Some((rel_subject_id, head.clone())))
+                }).collect();
+
+                let head = slab.new_memo_basic_noparent(Some(subject_id), MemoBody::FullyMaterialized { v: HashMap::new(), r: RelationSlotSubjectHead(relations) }).to_head();
+                manager.set_subject_head(subject_id, head.project_all_relation_links(&slab), head.clone());
+                this_layer.push((subject_id, head));
+            }
+            below = this_layer;
+        }
+
+        assert_eq!(manager.subject_head_count(), width * layers);
+
+        // Each subject is visited before those which refer to it
+        let mut visited = Vec::new();
+        for subject_head in manager.subject_head_iter() {
+            for to_subject_id in subject_head.to_subject_ids.iter() {
+                assert!(visited.contains(to_subject_id), "{} should have been visited before {}", to_subject_id, subject_head.subject_id);
+            }
+            visited.push(subject_head.subject_id);
+        }
+        assert_eq!(visited.len(), width * layers);
+
+        // Removing the top layer leaves the rest in place
+        for subject_id in below.iter().map(|&(subject_id, _)| subject_id) {
+            manager.remove_subject_head(subject_id);
+        }
+        assert_eq!(manager.subject_count(), width * (layers - 1));
+        assert_eq!(manager.vacancies(), width);
+    }
 }
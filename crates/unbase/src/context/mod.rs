@@ -76,10 +76,15 @@ impl Context {
 
         let seed = slab.get_root_index_seed().expect("Uninitialized slab");
 
-        let index = IndexFixed::new_from_memorefhead(ContextRef::Weak(new_self.weak()), 5, seed);
+        let index = IndexFixed::new_from_memorefhead(ContextRef::Weak(new_self.weak()), slab.config.index_depth, seed);
 
         *new_self.root_index.write().unwrap() = Some(index);
 
+        // A reopened slab conveys the subject heads it found in storage, including that of the root index
+        for (subject_id, head) in slab.restored_subject_heads() {
+            new_self.apply_subject_head(subject_id, &head, true);
+        }
+
         new_self
     }
     pub fn insert_into_root_index(&self, subject_id: SubjectId, subject: &Subject) {
@@ -277,6 +282,34 @@ impl Context {
 
         memoref_count
     }
+    /// Add the subject heads of this context, and of its resident subjects to the pinned set.
+    /// Returns false if the context heads could not be determined without blocking
+    pub fn collect_pinned_memos(&self, pinned: &mut PinnedMemos) -> bool {
+        match self.manager.try_lock() {
+            Ok(manager) => {
+                for subject_head in manager.subject_head_iter() {
+                    pinned.add_head(&subject_head.head);
+                }
+            }
+            Err(_) => return false,
+        }
+
+        let subjects: Vec<Subject> = match self.subjects.try_read() {
+            Ok(subjects) => subjects.values().filter_map(|s| s.upgrade()).collect(),
+            Err(_) => return false,
+        };
+
+        for subject in subjects.iter() {
+            match subject.try_get_head() {
+                Some(head) => pinned.add_head(&head),
+                None => {
+                    pinned.subject_ids.insert(subject.id);
+                }
+            }
+        }
+
+        true
+    }
     pub fn get_subject_head(&self, subject_id: SubjectId) -> Option<MemoRefHead> {
         if let Some(ref head) = self.manager.lock().unwrap().get_head(subject_id) {
             Some((*head).clone())
@@ -342,22 +375,41 @@ impl Context {
 
         // TODO: conditionalize this on the basis of the present context size
 
-        // Iterate the contextualized subject heads in reverse topological order
-        for subject_head in {
-            self.manager.lock().unwrap().subject_head_iter()
-        } {
+        // Don't hold the manager lock while iterating, as materialization and repointing apply subject heads
+        let subject_heads = self.manager.lock().unwrap().subject_head_iter();
+
+        // Iterate the contextualized subject heads in reverse topological order. Each is visited once, and a subject is
+        // only removed once all of its referrers have been repointed. Within a cycle, the first subject visited is removed,
+        // and the rest are left in the context, as their referrers are no longer there to be repointed
+        for subject_head in subject_heads {
+            let subject_id = subject_head.subject_id;
 
-            // TODO: implement MemoRefHead.conditionally_materialize such that the materialization threshold is selected dynamically.
-            //       It shold almost certainly not materialize with a single edit since the last FullyMaterialized memo
-            // head.conditionally_materialize( &self.slab );
+            // Earlier iterations may have repointed this subject, or removed it altogether
+            let head = match self.get_subject_head(subject_id) {
+                Some(head) => head,
+                None       => continue
+            };
+
+            // Materialize any subject which has strayed beyond the thresholds configured for the slab
+            // TODO: consider selecting the threshold dynamically, on the basis of the present context size
+            let head = if head.should_materialize(&self.slab) {
+                let memoref = self.slab.new_memo_basic(
+                    Some(subject_id),
+                    head.clone(),
+                    head.project_materialized_body(self)
+                );
+                self.apply_subject_head(subject_id, &memoref.to_head(), true);
+                memoref.to_head()
+            } else {
+                head
+            };
 
             if subject_head.from_subject_ids.len() > 0 {
                 // OK, somebody is pointing to us, so lets issue an edit for them
                 // to point to the new materialized memo for their relevant relations
-                self.repoint_subject_relations(subject_head.subject_id,
-                                               subject_head.head,
-                                               subject_head.from_subject_ids);
-
+                let repointed = self.repoint_subject_relations(subject_id,
+                                                               head.clone(),
+                                                               subject_head.from_subject_ids);
 
                 // NOTE: In order to remove a subject head from the context, we must ensure that
                 //       ALL referencing subject heads in the context get repointed. It's not enough to just do one
@@ -370,17 +422,73 @@ impl Context {
                 // When trying to materialize/compress fully (not that we'll want to do this often),
                 // this would continue all the way to the root index node, and we should be left
                 // with a very small context head
-
+                if repointed {
+                    let mut manager = self.manager.lock().unwrap();
+
+                    // Unless it moved on in the meantime, in which case the referencing subjects are already stale
+                    let unchanged = match manager.get_head(subject_id) {
+                        Some(current) => current.same_memos(&head),
+                        None          => false
+                    };
+                    if unchanged {
+                        manager.remove_subject_head(subject_id);
+                    }
+                }
             }
         }
 
     }
+    /// Issue relation edits for each of the given subjects, such that every relation they have to the subject in question
+    /// references the given head. Returns true only if each of them was still in the context to be repointed, such that
+    /// the head may be removed from the context without any of them conveying a stale one
     fn repoint_subject_relations(&self,
-                                 _to_subject_id: SubjectId,
-                                 _to_head: MemoRefHead,
-                                 _from_subject_ids: Vec<SubjectId>) {
-        unimplemented!()
+                                 to_subject_id: SubjectId,
+                                 to_head: MemoRefHead,
+                                 from_subject_ids: Vec<SubjectId>) -> bool {
+        let mut all_repointed = true;
+        let mut conveyed      = false;
+
+        for from_subject_id in from_subject_ids {
+            // A subject can't be repointed to its own present head, as that would supersede it. Nor can a subject whose
+            // head was removed earlier in this compression, as is the case for cycles. Either way, we must stay in the context
+            let from_head = match self.get_subject_head(from_subject_id) {
+                Some(ref head) if from_subject_id != to_subject_id => head.clone(),
+                _ => {
+                    all_repointed = false;
+                    continue;
+                }
+            };
+
+            let mut relations = HashMap::new();
+            for link in from_head.project_all_relation_links(&self.slab) {
+                if link.subject_id != Some(to_subject_id) {
+                    continue;
+                }
+                conveyed = true;
+
+                // Skip relations which already reference the head ( or something newer )
+                if let Ok((_, relation_head)) = from_head.project_relation(self, link.slot_id) {
+                    let mut applied = relation_head.clone();
+                    applied.apply(&to_head, &self.slab);
+                    if applied.same_memos(&relation_head) {
+                        continue;
+                    }
+                }
+
+                relations.insert(link.slot_id, Some((to_subject_id, to_head.clone())));
+            }
+
+            if !relations.is_empty() {
+                let memoref = self.slab.new_memo(
+                    Some(from_subject_id),
+                    from_head,
+                    MemoBody::Relation(RelationSlotSubjectHead(relations))
+                );
+                self.apply_subject_head(from_subject_id, &memoref.to_head(), true);
+            }
+        }
 
+        all_repointed && conveyed
     }
 
     pub fn is_fully_materialized(&self) -> bool {
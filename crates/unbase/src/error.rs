@@ -1,3 +1,6 @@
+use crate::slab::{MemoId,SlabId};
+use std::io;
+
 #[derive(PartialEq, Debug)]
 pub enum RetrieveError {
     NotFound,
@@ -7,3 +10,39 @@ pub enum RetrieveError {
     IndexNotInitialized,
     SlabError
 }
+
+#[derive(PartialEq, Debug)]
+pub enum IntegrityError {
+    MemoIdMismatch(MemoId),
+    /// The receiving slab has been shut down, and accepts no further memos
+    SlabClosed(SlabId)
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    Corrupt(String),
+    Encoding(String),
+    /// The stored slab is already open in this process
+    SlabIdInUse(SlabId)
+}
+
+impl From<io::Error> for StorageError {
+    fn from(error: io::Error) -> Self {
+        StorageError::Io(error)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(String),
+    /// The named environment variable held a value which could not be parsed
+    InvalidValue(String, String)
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
@@ -2,6 +2,7 @@ use crate::context::ContextRef;
 use crate::subject::*;
 use crate::memorefhead::{MemoRefHead,RelationSlotId};
 use crate::error::RetrieveError;
+use crate::value::Value;
 use std::collections::HashMap;
 
 
@@ -16,7 +17,7 @@ impl IndexFixed {
 
         Self {
             contextref: contextref.clone(),
-            root: Subject::new_with_contextref( contextref.clone(), HashMap::new(), true ).unwrap(),
+            root: Subject::new_with_contextref( contextref.clone(), HashMap::<String,Value>::new(), true ).unwrap(),
             depth: depth
         }
     }
@@ -65,7 +66,7 @@ impl IndexFixed {
                 }
                 Err( RetrieveError::NotFound ) => {
                     let mut values = HashMap::new();
-                    values.insert("tier".to_string(),tier.to_string());
+                    values.insert("tier".to_string(),Value::I64(tier as i64));
 
                     let new_node = Subject::new_with_contextref(self.contextref.clone(), values, true ).unwrap();
                     node.set_relation(y,&new_node);
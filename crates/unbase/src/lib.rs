@@ -45,12 +45,16 @@ extern crate serde_json;
 pub mod network;
 pub mod slab;
 pub mod subject;
+pub mod value;
 pub mod context;
 pub mod error;
+pub mod config;
+pub mod metrics;
 pub mod index;
 pub mod memorefhead;
 pub mod util;
 
 pub use crate::network::Network;
 pub use crate::subject::Subject;
+pub use crate::value::Value;
 pub use crate::slab::Slab;
\ No newline at end of file
@@ -5,11 +5,12 @@ use crate::slab::*;
 use crate::subject::*;
 use crate::context::*;
 use crate::error::*;
+use crate::value::Value;
 
 use std::mem;
 use std::fmt;
 use std::slice;
-use std::collections::VecDeque;
+use std::collections::{HashMap,VecDeque};
 
 // MemoRefHead is a list of MemoRefs that constitute the "head" of a given causal chain
 //
@@ -28,6 +29,21 @@ pub struct RelationLink{
     pub subject_id: Option<SubjectId>
 }
 
+/// One of possibly several concurrent values of a field, and the memo which wrote it
+#[derive(Clone, Debug)]
+pub struct ValueVersion{
+    pub memo_id: MemoId,
+    pub value:   Value,
+    pub(crate) memoref: MemoRef,
+}
+
+/// The number and approximate size of the memos since the nearest FullyMaterialized memo on each path of a head
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KeyframeDistance{
+    pub edits: usize,
+    pub bytes: usize,
+}
+
 impl MemoRefHead {
     pub fn new () -> Self {
         MemoRefHead( Vec::with_capacity(5) )
@@ -123,7 +139,11 @@ impl MemoRefHead {
         }
     }
     pub fn memo_ids (&self) -> Vec<MemoId> {
-        self.0.iter().map(|m| m.id).collect()
+        self.0.iter().map(|m| m.id()).collect()
+    }
+    /// Returns true if both heads consist of the same memos, in any order. Unlike comparing memo_ids, this doesn't call for ids
+    pub fn same_memos (&self, other: &MemoRefHead) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().all(|m| other.0.contains(m) )
     }
     pub fn first_subject_id (&self) -> Option<SubjectId> {
         if let Some(memoref) = self.iter().next() {
@@ -183,8 +203,13 @@ impl fmt::Debug for MemoRefHead{
 }
 
 pub struct CausalMemoIter {
-    queue: VecDeque<MemoRef>,
-    slab:  Slab
+    /// Memos which may be returned next. None of them descends another
+    frontier: Vec<MemoRef>,
+    /// The parents of the memo most recently returned, which are yet to be released, and whether to traverse them
+    pending:  Option<(MemoRefHead,bool)>,
+    /// Everything beneath the frontier, once the history has been found to branch
+    chart:    Option<HashMap<MemoId,ChartedMemo>>,
+    slab:     Slab
 }
 
 /*
@@ -192,45 +217,220 @@ pub struct CausalMemoIter {
           /- E -> C -\
      G ->              -> B -> A
 head ^    \- F -> D -/
-     Desired iterator sequence: G, E, C, F, D, B, A ( Why? )
-     Consider:                  [G], [E,C], [F,D], [B], [A]
-     Arguably this should not be an iterator at all, but rather a recursive function
-     Going with the iterator for now in the interest of simplicity
+
+  Memos are returned in topological order: no memo is returned before all of its descendants have been.
+  Among concurrent memos, the one with the lowest MemoId goes first. Given the same memos, every slab
+  therefore arrives at the same sequence, such as G, E, F, C, D, B, A or G, F, D, E, C, B, A, but never
+  G, E, C, B, ... as B may not precede D.
+
+  A FullyMaterialized memo conveys the entire state of the subject as of that memo, so its ancestors
+  are not traversed ( though they may still be reached by way of a concurrent memo )
+
+  So long as the history is linear, each memo is retrieved only as it is returned, such that traversal
+  may stop early without examining the rest. Once there is more than one memo to choose from, the
+  history beneath the frontier is charted in full, counting for each memo how many of its children are
+  yet to be returned. A memo joins the frontier once that count reaches zero, provided one of those
+  children was traversed rather than pruned. Each memo is thus retrieved once, and ordering it costs no
+  more than a few map lookups, rather than a walk of its lineage.
 */
+struct ChartedMemo {
+    memo:       Memo,
+    /// Charted children of this memo which are yet to be returned
+    waiting_on: usize,
+    /// Whether any child returned so far has traversed to this memo
+    reached:    bool
+}
+
+/// The memos beneath the frontier, retrieved by the caller as they are called for
+struct Charting {
+    to_visit: Vec<MemoRef>,
+    memos:    HashMap<MemoId,Memo>
+}
+
+impl Charting {
+    fn new (frontier: &[MemoRef]) -> Self {
+        Charting {
+            to_visit: frontier.to_vec(),
+            memos:    HashMap::new()
+        }
+    }
+    /// The next memo which must be retrieved, if any
+    fn next_needed (&mut self) -> Option<MemoRef> {
+        while let Some(memoref) = self.to_visit.pop() {
+            if !self.memos.contains_key(&memoref.id()) {
+                return Some(memoref);
+            }
+        }
+        None
+    }
+    fn add (&mut self, memoref: &MemoRef, memo: Memo) {
+        if CausalMemoIter::traverses(&memo) {
+            self.to_visit.extend(memo.parents.iter().cloned());
+        }
+        self.memos.insert(memoref.id(), memo);
+    }
+    fn finish (self, frontier: &[MemoRef]) -> HashMap<MemoId,ChartedMemo> {
+        let mut chart : HashMap<MemoId,ChartedMemo> = self.memos.into_iter().map(|(memo_id, memo)| {
+            (memo_id, ChartedMemo{ memo, waiting_on: 0, reached: false })
+        }).collect();
+
+        // FullyMaterialized memos still count as children where their parents were charted by way of others,
+        // lest those parents be returned ahead of them
+        let parent_ids : Vec<MemoId> = chart.values().flat_map(|charted| charted.memo.parents.memo_ids()).collect();
+        for parent_id in parent_ids {
+            if let Some(parent) = chart.get_mut(&parent_id) {
+                parent.waiting_on += 1;
+            }
+        }
+        for memoref in frontier {
+            if let Some(charted) = chart.get_mut(&memoref.id()) {
+                charted.reached = true;
+            }
+        }
+
+        chart
+    }
+}
+
 impl CausalMemoIter {
     pub fn from_head ( head: &MemoRefHead, slab: &Slab) -> Self {
         //println!("# -- SubjectMemoIter.from_head({:?})", head.memo_ids() );
 
+        let mut frontier : Vec<MemoRef> = Vec::with_capacity(head.len());
+        for memoref in head.iter() {
+            if !frontier.contains(memoref) {
+                frontier.push(memoref.clone());
+            }
+        }
+
         CausalMemoIter {
-            queue: head.to_vecdeque(),
-            slab:  slab.clone()
+            frontier,
+            pending:  None,
+            chart:    None,
+            slab:     slab.clone()
         }
     }
-}
-impl Iterator for CausalMemoIter {
-    type Item = Memo;
+    /// Don't traverse the parents of the memo most recently returned. They may still be reached by way of other memos
+    pub fn prune (&mut self) {
+        if let Some((_, ref mut traverse)) = self.pending {
+            *traverse = false;
+        }
+    }
+    /// Non-blocking equivalent of Iterator::next, which reports retrieval failures rather than panicking
+    pub async fn next_async (&mut self) -> Result<Option<Memo>,RetrieveError> {
+        if let Some((parents, traverse)) = self.pending.take() {
+            self.release(&parents, traverse);
+        }
 
-    fn next (&mut self) -> Option<Memo> {
-        // iterate over head memos
-        // Unnecessarly complex because we're not always dealing with MemoRefs
-        // Arguably heads should be stored as Vec<MemoRef> instead of Vec<Memo>
-
-        // TODO: Stop traversal when we come across a Keyframe memo
-        if let Some(memoref) = self.queue.pop_front() {
-            // this is wrong - Will result in G, E, F, C, D, B, A
-
-            match memoref.get_memo( &self.slab ){
-                Ok(memo) => {
-                    self.queue.append(&mut memo.get_parent_head().to_vecdeque());
-                    return Some(memo)
-                },
-                Err(err) => {
-                    panic!("Failed to retrieve memo {} ({:?})", memoref.id, err );
+        if self.needs_chart() {
+            let mut charting = Charting::new(&self.frontier);
+            while let Some(memoref) = charting.next_needed() {
+                let memo = memoref.get_memo_async( &self.slab ).await?;
+                charting.add(&memoref, memo);
+            }
+            self.chart(charting);
+        }
+
+        if let Some(memoref) = self.take_next() {
+            let memo = match self.charted_memo(&memoref) {
+                Some(memo) => memo,
+                None       => memoref.get_memo_async( &self.slab ).await?
+            };
+            self.pending = Some((memo.get_parent_head(), Self::traverses(&memo)));
+            return Ok(Some(memo));
+        }
+
+        Ok(None)
+    }
+    pub(crate) fn next_memoref (&mut self) -> Option<(MemoRef,Memo)> {
+        if let Some((parents, traverse)) = self.pending.take() {
+            self.release(&parents, traverse);
+        }
+
+        //TODO: memoref.get_memo needs to be able to fail
+        let retrieve = |memoref: &MemoRef, slab: &Slab| {
+            memoref.get_memo( slab ).unwrap_or_else(|err| panic!("Failed to retrieve memo {:?} ({:?})", memoref.id(), err ))
+        };
+
+        if self.needs_chart() {
+            let mut charting = Charting::new(&self.frontier);
+            while let Some(memoref) = charting.next_needed() {
+                let memo = retrieve(&memoref, &self.slab);
+                charting.add(&memoref, memo);
+            }
+            self.chart(charting);
+        }
+
+        if let Some(memoref) = self.take_next() {
+            let memo = match self.charted_memo(&memoref) {
+                Some(memo) => memo,
+                None       => retrieve(&memoref, &self.slab)
+            };
+            self.pending = Some((memo.get_parent_head(), Self::traverses(&memo)));
+            return Some((memoref, memo));
+        }
+
+        None
+    }
+    /// Nothing beyond a keyframe is of interest
+    fn traverses (memo: &Memo) -> bool {
+        !matches!(memo.body, MemoBody::FullyMaterialized{ .. })
+    }
+    fn needs_chart (&self) -> bool {
+        self.chart.is_none() && self.frontier.len() > 1
+    }
+    fn chart (&mut self, charting: Charting) {
+        let chart = charting.finish(&self.frontier);
+
+        // Anything in the head which descends another has to wait its turn
+        self.frontier.retain(|memoref| chart.get(&memoref.id()).is_none_or(|charted| charted.waiting_on == 0) );
+        self.chart = Some(chart);
+    }
+    fn charted_memo (&self, memoref: &MemoRef) -> Option<Memo> {
+        self.chart.as_ref().and_then(|chart| chart.get(&memoref.id())).map(|charted| charted.memo.clone())
+    }
+    /// Account for the parents of a memo which has been returned, adding those which are now due to the frontier
+    fn release (&mut self, parents: &MemoRefHead, traverse: bool) {
+        let chart = match self.chart {
+            Some(ref mut chart) => chart,
+            None => {
+                // Still linear. The frontier was emptied by returning its only memo
+                if traverse {
+                    self.frontier.extend(parents.iter().cloned());
+                }
+                return;
+            }
+        };
+
+        let mut releasing = vec![(parents.clone(), traverse)];
+        while let Some((parents, traverse)) = releasing.pop() {
+            for parent in parents.iter() {
+                if let Some(charted) = chart.get_mut(&parent.id()) {
+                    charted.waiting_on -= 1;
+                    charted.reached |= traverse;
+
+                    if charted.waiting_on == 0 {
+                        if charted.reached {
+                            self.frontier.push(parent.clone());
+                        }else{
+                            // Nothing traversed to this memo, so nothing may traverse through it either
+                            releasing.push((charted.memo.get_parent_head(), false));
+                        }
+                    }
                 }
             }
-            //TODO: memoref.get_memo needs to be able to fail
         }
+    }
+    /// Remove the frontier memo with the lowest MemoId. Ids are only calculated where there is more than one to choose from
+    fn take_next (&mut self) -> Option<MemoRef> {
+        let position = self.frontier.iter().enumerate().min_by(|a, b| a.1.id().cmp(&b.1.id()) ).map(|(i, _)| i );
+        position.map(|i| self.frontier.swap_remove(i) )
+    }
+}
+impl Iterator for CausalMemoIter {
+    type Item = Memo;
 
-        return None;
+    fn next (&mut self) -> Option<Memo> {
+        self.next_memoref().map(|(_, memo)| memo )
     }
 }
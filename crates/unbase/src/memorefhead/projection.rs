@@ -1,68 +1,140 @@
 use super::*;
+use crate::value::Value;
+
+use std::collections::{HashMap,HashSet};
+
+impl KeyframeDistance {
+    /// Whether either threshold has been reached. None disables that threshold
+    pub fn reaches (&self, max_edits: Option<usize>, max_bytes: Option<usize>) -> bool {
+        max_edits.is_some_and(|max| self.edits >= max) || max_bytes.is_some_and(|max| self.bytes >= max)
+    }
+    /// The distance once the given memo is added atop. A FullyMaterialized memo is a keyframe, and starts it over
+    pub fn after (&self, memo: &Memo) -> Self {
+        match memo.body {
+            MemoBody::FullyMaterialized{..} => KeyframeDistance::default(),
+            _ => KeyframeDistance{ edits: self.edits + 1, bytes: self.bytes + memo.approximate_size() }
+        }
+    }
+}
 
 impl MemoRefHead {
-    /*pub fn fully_materialize( &self, slab: &Slab ) {
-        // TODO: consider doing as-you-go distance counting to the nearest materialized memo for each descendent
-        //       as part of the list management. That way we won't have to incur the below computational effort.
+    /// The number and size of the memos since the nearest FullyMaterialized memo on each path. Stops counting as soon
+    /// as either threshold is reached, such that measuring a long chain is cheap
+    pub fn keyframe_distance ( &self, slab: &Slab, max_edits: Option<usize>, max_bytes: Option<usize> ) -> KeyframeDistance {
+        let mut distance = KeyframeDistance::default();
+        if max_edits.is_none() && max_bytes.is_none() {
+            return distance;
+        }
 
-        for memo in self.causal_memo_iter(slab){
-            match memo.inner.body {
-                MemoBody::FullyMaterialized { v: _, r: _ } => {},
-                _                           => { return false }
+        // The iterator doesn't proceed past a materialized memo, but does yield it
+        for memo in self.causal_memo_iter(slab) {
+            if let MemoBody::FullyMaterialized{..} = memo.body {
+                continue;
+            }
+
+            distance = distance.after(&memo);
+            if distance.reaches(max_edits, max_bytes) {
+                break;
+            }
+        }
+
+        distance
+    }
+    /// Whether this head has strayed far enough from its keyframes that the slab is configured to materialize it
+    pub fn should_materialize ( &self, slab: &Slab ) -> bool {
+        let (max_edits, max_bytes) = (slab.config.materialize_after_edits, slab.config.materialize_after_bytes);
+        self.keyframe_distance(slab, max_edits, max_bytes).reaches(max_edits, max_bytes)
+    }
+    /// The body of a FullyMaterialized memo recording every field and relation of the subject as of this head.
+    /// Concurrent values are resolved as project_value would resolve them
+    pub fn project_materialized_body ( &self, context: &Context ) -> MemoBody {
+        let mut keys  : HashSet<String>         = HashSet::new();
+        let mut slots : HashSet<RelationSlotId> = HashSet::new();
+
+        for memo in self.causal_memo_iter(&context.slab) {
+            match memo.body {
+                MemoBody::Counter(_, ref deltas)  => keys.extend(deltas.keys().cloned()),
+                MemoBody::SetAdd(ref adds)        => keys.extend(adds.keys().cloned()),
+                MemoBody::SetRemove(ref removes)  => keys.extend(removes.keys().cloned()),
+                _ => {}
+            }
+            if let Some((values, _)) = memo.get_values() {
+                keys.extend(values.keys().cloned());
+            }
+            if let Some((relations, _)) = memo.get_relations() {
+                slots.extend(relations.keys().cloned());
+            }
+        }
+
+        let mut values = HashMap::new();
+        for key in keys {
+            if let Some(value) = self.project_materialized_value(context, &key) {
+                values.insert(key, value);
+            }
+        }
+
+        // Nothing precedes a FullyMaterialized memo, so cleared slots are simply omitted rather than tombstoned
+        let mut relations = HashMap::new();
+        for slot in slots {
+            if let Ok(relation) = self.project_relation(context, slot) {
+                relations.insert(slot, Some(relation));
             }
         }
 
-        true
-    }*/
+        MemoBody::FullyMaterialized{ v: values, r: RelationSlotSubjectHead(relations) }
+    }
 
     // Kind of a brute force way to do this
     // TODO: Consider calculating deltas during memoref application,
     //       and use that to perform a minimum cost subject_head_link edit
     pub fn project_all_relation_links (&self, slab: &Slab) -> Vec<RelationLink> {
-        let mut relation_links : [SubjectId; SUBJECT_MAX_RELATIONS] = [0; SUBJECT_MAX_RELATIONS];
+        // The most recent memo to mention a slot decides it. Some(None) means that slot was cleared by a tombstone
+        let mut relation_links : [Option<Option<SubjectId>>; SUBJECT_MAX_RELATIONS] = [None; SUBJECT_MAX_RELATIONS];
 
-        // TODO: how to handle relationship nullification?
+        // The iterator doesn't proceed past a materialized memo, so there's no need to stop here
         for memo in self.causal_memo_iter(slab){
-            match memo.body {
-                MemoBody::FullyMaterialized { v: _, ref r } => {
-
-                    for (slot,&(subject_id,_)) in &r.0 {
-                        relation_links[ *slot as usize ] = subject_id as SubjectId;
-                    }
-                    break;
-                    // Materialized memo means we're done here
-                },
-                MemoBody::Relation(ref r) => {
-                    for (slot,&(subject_id,_)) in r.iter() {
-                        relation_links[ *slot as usize ] = subject_id as SubjectId;
-                    }
-                },
-                _ => {}
-            }
+            Self::note_relation_links(&mut relation_links, &memo);
         }
 
-        // HACK
+        Self::conveyed_relation_links(&relation_links)
+    }
+    pub async fn project_all_relation_links_async (&self, slab: &Slab) -> Result<Vec<RelationLink>, RetrieveError> {
+        let mut relation_links : [Option<Option<SubjectId>>; SUBJECT_MAX_RELATIONS] = [None; SUBJECT_MAX_RELATIONS];
+
+        let mut iter = self.causal_memo_iter(slab);
+        while let Some(memo) = iter.next_async().await? {
+            Self::note_relation_links(&mut relation_links, &memo);
+        }
 
-        relation_links.iter().enumerate().map(|(slot_id,subject_id)| {
-            if *subject_id == 0 {
-                RelationLink{ slot_id: slot_id as RelationSlotId, subject_id: None }
-            }else{
-                RelationLink{ slot_id: slot_id as RelationSlotId, subject_id: Some(*subject_id) }
+        Ok(Self::conveyed_relation_links(&relation_links))
+    }
+    fn note_relation_links (relation_links: &mut [Option<Option<SubjectId>>; SUBJECT_MAX_RELATIONS], memo: &Memo) {
+        if let Some((relations, _)) = memo.get_relations() {
+            for (slot, relation) in relations.iter() {
+                let link = &mut relation_links[ *slot as usize ];
+                if link.is_none() {
+                    *link = Some(relation.as_ref().map(|&(subject_id, _)| subject_id ));
+                }
             }
+        }
+    }
+    fn conveyed_relation_links (relation_links: &[Option<Option<SubjectId>>; SUBJECT_MAX_RELATIONS]) -> Vec<RelationLink> {
+        // HACK - we convey every slot, rather than just those which were mentioned
+        relation_links.iter().enumerate().map(|(slot_id, link)| {
+            RelationLink{ slot_id: slot_id as RelationSlotId, subject_id: link.unwrap_or(None) }
         }).collect()
     }
 
-    pub fn project_value ( &self, context: &Context, key: &str ) -> Option<String> {
+    pub fn project_value ( &self, context: &Context, key: &str ) -> Option<Value> {
 
         //TODO: consider creating a consolidated projection routine for most/all uses
+        // A materialized memo without the key is the end of the line for its branch, which the iterator prunes
         for memo in self.causal_memo_iter(&context.slab) {
 
             //println!("# \t\\ Considering Memo {}", memo.id );
-            if let Some((values, materialized)) = memo.get_values() {
+            if let Some((values, _)) = memo.get_values() {
                 if let Some(v) = values.get(key) {
                     return Some(v.clone());
-                }else if materialized {
-                    return None; //end of the line here
                 }
             }
         }
@@ -73,17 +145,19 @@ impl MemoRefHead {
 
         for memo in self.causal_memo_iter( &context.slab ) {
 
-            if let Some((relations,materialized)) = memo.get_relations(){
+            if let Some((relations,_)) = memo.get_relations(){
                 //println!("# \t\\ Considering Memo {}, Head: {:?}, Relations: {:?}", memo.id, memo.get_parent_head(), relations );
-                if let Some(&(subject_id, ref head)) = relations.get(&key) {
-                    // BUG: the parent->child was formed prior to the revision of the child.
-                    // TODO: Should be adding the new head memo to the query context
-                    //       and superseding the referenced head due to its inclusion in the context
-
-                    return Ok((subject_id,head.clone()));
-                }else if materialized {
-                    //println!("\n# \t\\ Not Found (materialized)" );
-                    return Err(RetrieveError::NotFound);
+                match relations.get(&key) {
+                    Some(&Some((subject_id, ref head))) => {
+                        // BUG: the parent->child was formed prior to the revision of the child.
+                        // TODO: Should be adding the new head memo to the query context
+                        //       and superseding the referenced head due to its inclusion in the context
+
+                        return Ok((subject_id,head.clone()));
+                    },
+                    // Tombstone - the relation was cleared
+                    Some(&None) => return Err(RetrieveError::NotFound),
+                    None        => {}
                 }
             }
         }
@@ -91,5 +165,205 @@ impl MemoRefHead {
         //println!("\n# \t\\ Not Found" );
         Err(RetrieveError::NotFound)
     }
+    pub async fn project_value_async ( &self, context: &Context, key: &str ) -> Result<Option<Value>, RetrieveError> {
+        let mut iter = self.causal_memo_iter(&context.slab);
+
+        while let Some(memo) = iter.next_async().await? {
+            if let Some((values, _)) = memo.get_values() {
+                if let Some(v) = values.get(key) {
+                    return Ok(Some(v.clone()));
+                }
+            }
+        }
+        Ok(None)
+    }
+    pub async fn project_relation_async ( &self, context: &Context, key: RelationSlotId ) -> Result<(SubjectId,Self), RetrieveError> {
+        let mut iter = self.causal_memo_iter(&context.slab);
+
+        while let Some(memo) = iter.next_async().await? {
+            if let Some((relations,_)) = memo.get_relations(){
+                match relations.get(&key) {
+                    Some(&Some((subject_id, ref head))) => return Ok((subject_id,head.clone())),
+                    Some(&None)                         => return Err(RetrieveError::NotFound),
+                    None                                => {}
+                }
+            }
+        }
+
+        Err(RetrieveError::NotFound)
+    }
+
+    /// The value of the given field as a materialized memo should record it. Counters are recorded as their total,
+    /// and sets as their tagged elements, such that either may continue from there
+    pub fn project_materialized_value ( &self, context: &Context, key: &str ) -> Option<Value> {
+        for memo in self.causal_memo_iter(&context.slab) {
+            match memo.body {
+                MemoBody::Counter(_, ref deltas)  if deltas.contains_key(key)  => return Some(Value::I64(self.project_counter(context, key))),
+                MemoBody::SetAdd(ref adds)        if adds.contains_key(key)    => return Some(Value::Set(self.project_set_tags(context, key))),
+                MemoBody::SetRemove(ref removes)  if removes.contains_key(key) => return Some(Value::Set(self.project_set_tags(context, key))),
+                _ => {}
+            }
+            if let Some((values, _)) = memo.get_values() {
+                match values.get(key) {
+                    // Another path may yet have added to or removed from a materialized set, or incremented a counter
+                    Some(&Value::Set(_)) => return Some(Value::Set(self.project_set_tags(context, key))),
+                    Some(&Value::I64(_)) => return Some(Value::I64(self.project_counter(context, key))),
+                    Some(v)              => return Some(v.clone()),
+                    None                 => {}
+                }
+            }
+        }
+        None
+    }
+    /*
+        CRDT field projections
+
+        Unlike project_value, which takes the first value it comes across, these merge every write to the field
+        in the causal history of the head, back to the nearest plain value ( an Edit or materialized memo ) on each
+        path. A plain value serves as the base state of the field, such that set_typed_value resets it.
+    */
+
+    /// PN-Counter: the base value plus the sum of all increments since. Increments which the base descends ( as
+    /// when it's a snapshot of the counter ) are already included in it, though they may be reached by other paths
+    pub fn project_counter ( &self, context: &Context, key: &str ) -> i64 {
+        let slab = &context.slab;
+        let mut increments : Vec<(MemoRef, i64)> = Vec::new();
+        let mut base : Option<(MemoRef, i64)>     = None;
+
+        self.visit_causal_history(slab, |memoref, memo| {
+            if let MemoBody::Counter(_, ref deltas) = memo.body {
+                if let Some(delta) = deltas.get(key) {
+                    increments.push((memoref.clone(), *delta));
+                }
+                return true;
+            }
+            match memo.get_values() {
+                Some((values, materialized)) => match values.get(key) {
+                    // The first is the one project_value would choose. Nothing visited later descends it
+                    Some(v) => {
+                        base.get_or_insert((memoref.clone(), v.as_i64().unwrap_or(0)));
+                        false
+                    },
+                    None => !materialized
+                },
+                None => true
+            }
+        });
+
+        let (base_memoref, total) = match base {
+            Some((memoref, value)) => (Some(memoref), value),
+            None                   => (None, 0)
+        };
+
+        increments.iter()
+            .filter(|&(memoref, _)| base_memoref.as_ref().is_none_or(|b| !b.descends(memoref, slab) ) )
+            .fold(total, |total, &(_, delta)| total.wrapping_add(delta) )
+    }
+    /// OR-Set: the elements of the set, each with the tags of the additions which have not been removed
+    pub fn project_set_tags ( &self, context: &Context, key: &str ) -> Vec<(MemoId, Value)> {
+        let mut added   : Vec<(MemoId, Value)> = Vec::new();
+        let mut removed : Vec<(MemoId, Value)> = Vec::new();
+
+        self.visit_causal_history(&context.slab, |_, memo| {
+            match memo.body {
+                MemoBody::SetAdd(ref adds) => {
+                    if let Some(elements) = adds.get(key) {
+                        added.extend(elements.iter().map(|e| (memo.id(), e.clone()) ));
+                    }
+                    return true;
+                }
+                MemoBody::SetRemove(ref removes) => {
+                    if let Some(tagged) = removes.get(key) {
+                        removed.extend(tagged.iter().cloned());
+                    }
+                    return true;
+                }
+                _ => {}
+            }
+            match memo.get_values() {
+                Some((values, materialized)) => match values.get(key) {
+                    // A materialized set retains the tags of the additions which preceded it
+                    Some(Value::Set(tagged)) => {
+                        added.extend(tagged.iter().cloned());
+                        false
+                    },
+                    // The elements of a plain value are tagged with the id of the memo which carries it
+                    Some(Value::List(elements)) => {
+                        added.extend(elements.iter().map(|e| (memo.id(), e.clone()) ));
+                        false
+                    },
+                    Some(v) => {
+                        added.push((memo.id(), v.clone()));
+                        false
+                    },
+                    None => !materialized
+                },
+                None => true
+            }
+        });
+
+        added.retain(|tagged| !removed.contains(tagged));
+        added
+    }
+    /// OR-Set: the distinct elements of the set. Concurrent addition and removal of an element leaves it in place
+    pub fn project_set ( &self, context: &Context, key: &str ) -> Vec<Value> {
+        let mut elements : Vec<Value> = Vec::new();
+        for (_, element) in self.project_set_tags(context, key) {
+            if !elements.contains(&element) {
+                elements.push(element);
+            }
+        }
+        elements
+    }
+    /// Multi-value register: the values of all writes to the field which have not been overwritten. There will be
+    /// more than one only if they were written concurrently. Sorted by memo id, such that all slabs agree on the order
+    pub fn project_register ( &self, context: &Context, key: &str ) -> Vec<Value> {
+        let mut values : Vec<Value> = Vec::new();
+        for version in self.project_value_versions(context, key) {
+            if !values.contains(&version.value) {
+                values.push(version.value);
+            }
+        }
+        values
+    }
+    /// All writes to the given field which have not been overwritten, whether they were plain or register writes.
+    /// Unlike project_value, this exposes concurrent writes rather than choosing between them. Sorted by memo id,
+    /// such that all slabs agree on the order
+    pub fn project_value_versions ( &self, context: &Context, key: &str ) -> Vec<ValueVersion> {
+        let slab = &context.slab;
+        let mut writes : Vec<ValueVersion> = Vec::new();
+
+        self.visit_causal_history(slab, |memoref, memo| {
+            match memo.get_values() {
+                Some((values, materialized)) => match values.get(key) {
+                    Some(v) => {
+                        writes.push(ValueVersion{ memo_id: memoref.id(), value: v.clone(), memoref: memoref.clone() });
+                        false
+                    },
+                    None => !materialized
+                },
+                None => true
+            }
+        });
 
+        // A write reached by one path may yet be overwritten by a write reached by another
+        let superseded : Vec<bool> = writes.iter().map(|version| {
+            writes.iter().any(|other| other.memo_id != version.memo_id && other.memoref.descends(&version.memoref, slab) )
+        }).collect();
+
+        let mut current : Vec<ValueVersion> = writes.into_iter().zip(superseded).filter(|&(_, s)| !s ).map(|(w, _)| w ).collect();
+        current.sort_by_key(|a| a.memo_id);
+        current
+    }
+    /// Visit each memo in the causal history of this head once, in causal order, without proceeding past any memo for
+    /// which the visitor returns false ( though its ancestors may yet be reached by way of others )
+    fn visit_causal_history<F> ( &self, slab: &Slab, mut visitor: F ) where F: FnMut(&MemoRef, &Memo) -> bool {
+        let mut iter = self.causal_memo_iter(slab);
+
+        while let Some((memoref, memo)) = iter.next_memoref() {
+            if !visitor(&memoref, &memo) {
+                iter.prune();
+            }
+        }
+    }
 }
@@ -0,0 +1,300 @@
+/* Metrics
+ *
+ * Counters, gauges and histograms describing the health of slabs and transports. Network::render_metrics
+ * renders them for all local slabs and bound transports in the Prometheus text exposition format,
+ * and a MetricsServer serves that rendering over HTTP, such that a node may be scraped and graphed.
+ *
+ * Slabs record their metrics in SlabMetrics. Transports keep a TransportMetrics, which they register
+ * with the network upon binding to it. Gauges ( memorefs, dispatch queue depth, etc ) are
+ * sampled from the slab at the time of rendering, rather than being recorded.
+*/
+
+use crate::network::{Network,WeakNetwork};
+use crate::slab::Slab;
+
+use std::fmt::{self,Write as FmtWrite};
+use std::io::{self,Read,Write};
+use std::net::{TcpListener,TcpStream,SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool,AtomicU64,Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the memo retrieval latency histogram buckets
+pub const RETRIEVAL_LATENCY_BOUNDS : [f64; 10] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+/// A monotonically increasing count
+#[derive(Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc (&self) {
+        self.add(1);
+    }
+    pub fn add (&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+    pub fn get (&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Distribution of observed durations across fixed buckets
+pub struct Histogram {
+    bounds:     &'static [f64],
+    /// Observations falling within each bucket. Not cumulative, unlike their rendering
+    buckets:    Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+    count:      AtomicU64,
+}
+
+impl Histogram {
+    pub fn new (bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            buckets:    bounds.iter().map(|_| AtomicU64::new(0) ).collect(),
+            sum_micros: AtomicU64::new(0),
+            count:      AtomicU64::new(0),
+        }
+    }
+    pub fn observe (&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        // Observations beyond the last bound are only counted by the implicit +Inf bucket
+        if let Some(i) = self.bounds.iter().position(|bound| seconds <= *bound ) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn count (&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+    /// Sum of all observations, in seconds
+    pub fn sum (&self) -> f64 {
+        self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    }
+    /// ( upper bound, number of observations less than or equal to it ) for each bucket
+    pub fn cumulative_buckets (&self) -> Vec<(f64,u64)> {
+        let mut total = 0;
+        self.bounds.iter().zip(self.buckets.iter()).map(|(bound, bucket)| {
+            total += bucket.load(Ordering::Relaxed);
+            (*bound, total)
+        }).collect()
+    }
+}
+
+/// Metrics recorded by a slab
+pub struct SlabMetrics {
+    pub memos_created:            Counter,
+    pub peering_memos_sent:       Counter,
+    /// Memos requested from other slabs because they were not resident
+    pub memo_retrievals:          Counter,
+    /// Retrieval attempts which elapsed without the memo arriving
+    pub memo_retrieval_timeouts:  Counter,
+    pub memo_retrieval_latency:   Histogram,
+    /// Memos discarded by garbage collection
+    pub memos_collected:          Counter,
+    /// Subject heads applied to subscribed contexts upon the receipt of memos
+    pub subject_heads_dispatched: Counter,
+}
+
+impl Default for SlabMetrics {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl SlabMetrics {
+    pub fn new () -> Self {
+        SlabMetrics {
+            memos_created:            Counter::default(),
+            peering_memos_sent:       Counter::default(),
+            memo_retrievals:          Counter::default(),
+            memo_retrieval_timeouts:  Counter::default(),
+            memo_retrieval_latency:   Histogram::new(&RETRIEVAL_LATENCY_BOUNDS),
+            memos_collected:          Counter::default(),
+            subject_heads_dispatched: Counter::default(),
+        }
+    }
+}
+
+/// Metrics recorded by a transport
+#[derive(Default)]
+pub struct TransportMetrics {
+    pub packets_sent:     Counter,
+    pub packets_received: Counter,
+    pub bytes_sent:       Counter,
+    pub bytes_received:   Counter,
+}
+
+/// Writer for the Prometheus text exposition format
+struct Exposition {
+    out: String
+}
+
+impl Exposition {
+    fn family (&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+    fn sample<V: fmt::Display> (&mut self, name: &str, labels: &[(&str,&str)], value: V) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels : Vec<String> = labels.iter().map(|&(k,v)| format!("{}=\"{}\"", k, escape_label(v)) ).collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+    fn slab_family<V: fmt::Display, F: Fn(&Slab) -> V> (&mut self, slabs: &[Slab], name: &str, kind: &str, help: &str, value: F) {
+        self.family(name, kind, help);
+        for slab in slabs {
+            let slab_id = slab.id.to_string();
+            self.sample(name, &[("slab", slab_id.as_str())], value(slab));
+        }
+    }
+    fn transport_family<F: Fn(&TransportMetrics) -> u64> (&mut self, transports: &[(String,Arc<TransportMetrics>)], name: &str, help: &str, value: F) {
+        self.family(name, "counter", help);
+        for (transport, metrics) in transports {
+            self.sample(name, &[("transport", transport.as_str())], value(metrics));
+        }
+    }
+}
+
+fn escape_label (value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Render the metrics of all local slabs and bound transports in the Prometheus text exposition format
+pub fn render (net: &Network) -> String {
+    let mut slabs = net.get_all_local_slabs();
+    slabs.sort_by_key(|slab| slab.id );
+    let transports = net.get_transport_metrics();
+
+    let mut e = Exposition{ out: String::new() };
+
+    e.slab_family(&slabs, "unbase_memos_created_total",              "counter", "Memos created by the slab",
+                  |s| s.metrics.memos_created.get() );
+    e.slab_family(&slabs, "unbase_memos_received_total",             "counter", "Memos received from other slabs",
+                  |s| s.count_of_memos_received() );
+    e.slab_family(&slabs, "unbase_memos_redundantly_received_total", "counter", "Memos received from other slabs which were already known",
+                  |s| s.count_of_memos_reduntantly_received() );
+    e.slab_family(&slabs, "unbase_peering_memos_sent_total",         "counter", "Peering memos sent to other slabs",
+                  |s| s.metrics.peering_memos_sent.get() );
+    e.slab_family(&slabs, "unbase_memo_retrievals_total",            "counter", "Memos requested from other slabs",
+                  |s| s.metrics.memo_retrievals.get() );
+    e.slab_family(&slabs, "unbase_memo_retrieval_timeouts_total",    "counter", "Memo requests which timed out",
+                  |s| s.metrics.memo_retrieval_timeouts.get() );
+    e.slab_family(&slabs, "unbase_memos_collected_total",            "counter", "Memos discarded by garbage collection",
+                  |s| s.metrics.memos_collected.get() );
+    e.slab_family(&slabs, "unbase_subject_heads_dispatched_total",   "counter", "Subject heads applied to subscribed contexts upon the receipt of memos",
+                  |s| s.metrics.subject_heads_dispatched.get() );
+    e.slab_family(&slabs, "unbase_memorefs",                         "gauge",   "Memorefs known to the slab, whether or not their memos are resident",
+                  |s| s.count_of_memorefs_resident() );
+    e.slab_family(&slabs, "unbase_peer_slabs",                       "gauge",   "Slabs known to the slab",
+                  |s| s.peer_slab_count() );
+    e.slab_family(&slabs, "unbase_dispatch_queue_depth",             "gauge",   "Memoref batches awaiting dispatch to subscribed contexts",
+                  |s| s.dispatch_queue_depth() );
+
+    e.family("unbase_memo_retrieval_seconds", "histogram", "Time taken to retrieve memos from other slabs");
+    for slab in slabs.iter() {
+        let slab_id = slab.id.to_string();
+        let latency = &slab.metrics.memo_retrieval_latency;
+
+        for (bound, count) in latency.cumulative_buckets() {
+            let bound = bound.to_string();
+            e.sample("unbase_memo_retrieval_seconds_bucket", &[("slab", slab_id.as_str()), ("le", bound.as_str())], count);
+        }
+        e.sample("unbase_memo_retrieval_seconds_bucket", &[("slab", slab_id.as_str()), ("le", "+Inf")], latency.count());
+        e.sample("unbase_memo_retrieval_seconds_sum",    &[("slab", slab_id.as_str())], latency.sum());
+        e.sample("unbase_memo_retrieval_seconds_count",  &[("slab", slab_id.as_str())], latency.count());
+    }
+
+    e.transport_family(&transports, "unbase_transport_packets_sent_total",     "Packets sent by the transport",     |t| t.packets_sent.get() );
+    e.transport_family(&transports, "unbase_transport_packets_received_total", "Packets received by the transport", |t| t.packets_received.get() );
+    e.transport_family(&transports, "unbase_transport_bytes_sent_total",       "Bytes sent by the transport",       |t| t.bytes_sent.get() );
+    e.transport_family(&transports, "unbase_transport_bytes_received_total",   "Bytes received by the transport",   |t| t.bytes_received.get() );
+
+    e.out
+}
+
+/// Serves the rendered metrics of a network over HTTP, until dropped
+pub struct MetricsServer {
+    address: SocketAddr,
+    running: Arc<AtomicBool>,
+}
+
+impl MetricsServer {
+    /// Listen on the given address ( eg: "127.0.0.1:9898" ), responding to GET /metrics
+    pub fn start (net: &Network, address: &str) -> io::Result<MetricsServer> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+
+        let net_weak : WeakNetwork = net.weak();
+        let thread_running = running.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if !thread_running.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    match net_weak.upgrade() {
+                        Some(net) => {
+                            if let Err(e) = respond(stream, &net) {
+                                println!("WARNING - MetricsServer failed to respond: {}", e);
+                            }
+                        },
+                        None => break
+                    }
+                }
+            }
+        });
+
+        Ok(MetricsServer{
+            address,
+            running,
+        })
+    }
+    /// The address on which the server is listening
+    pub fn local_addr (&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop (&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // Wake the listener so it notices
+        let _ = TcpStream::connect(self.address);
+    }
+}
+
+fn respond (mut stream: TcpStream, net: &Network) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    // We only care about the request line, but must consume the headers before responding
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.ends_with(b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[0..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK",        "text/plain; version=0.0.4", render(net)),
+        (Some("GET"), Some(_))          => ("404 Not Found", "text/plain",                "not found\n".to_string()),
+        _                               => ("400 Bad Request", "text/plain",              "bad request\n".to_string()),
+    };
+
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, content_type, body.len())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
@@ -13,8 +13,11 @@ pub use self::transmitter::{Transmitter, TransmitterArgs};
 use std::ops::Deref;
 use std::sync::{Arc, Weak, Mutex, RwLock};
 use std::fmt;
+use std::collections::HashSet;
 use crate::slab::{Slab, WeakSlab, SlabId};
 use crate::memorefhead::MemoRefHead;
+use crate::config::NetworkConfig;
+use crate::metrics::{self,MetricsServer,TransportMetrics};
 
 
 #[derive(Clone)]
@@ -28,11 +31,16 @@ impl Deref for Network {
 }
 
 pub struct NetworkInner {
-    next_slab_id: RwLock<u32>,
+    /// Ids of all slabs presently created or restored by this process
+    issued_slab_ids: Mutex<HashSet<SlabId>>,
     slabs: RwLock<Vec<WeakSlab>>,
     transports: RwLock<Vec<Box<dyn Transport + Send + Sync>>>,
     root_index_seed: RwLock<Option<(MemoRefHead, SlabRef)>>,
+    /// Metrics of each bound transport, by name
+    transport_metrics: RwLock<Vec<(String, Arc<TransportMetrics>)>>,
+    metrics_server: Mutex<Option<MetricsServer>>,
     create_new_system: bool,
+    pub config: NetworkConfig,
 }
 
 pub struct WeakNetwork(Weak<NetworkInner>);
@@ -42,33 +50,49 @@ impl Network {
     /// This represents your joining an existing unbase system.
     /// (In production, this is the one you want)
     pub fn new() -> Network {
-        Self::new_inner(false)
+        Self::new_inner(false, NetworkConfig::default())
+    }
+    /// Network handle, for joining an existing unbase system with the given configuration.
+    /// Slabs created against this network use its slab configuration unless otherwise specified
+    pub fn new_with_config(config: NetworkConfig) -> Network {
+        Self::new_inner(false, config)
     }
     /// In test cases, you want to create a wholly new unbase system.
     /// You should not be using this in production, except the *first* time ever for that system
     pub fn create_new_system() -> Network {
-        Self::new_inner(true)
+        Self::new_inner(true, NetworkConfig::default())
+    }
+    pub fn create_new_system_with_config(config: NetworkConfig) -> Network {
+        Self::new_inner(true, config)
     }
-    fn new_inner(create_new_system: bool) -> Network {
+    fn new_inner(create_new_system: bool, config: NetworkConfig) -> Network {
 
         let net = Network(Arc::new(NetworkInner {
-            next_slab_id: RwLock::new(0),
+            issued_slab_ids: Mutex::new(HashSet::new()),
             slabs: RwLock::new(Vec::new()),
             transports: RwLock::new(Vec::new()),
             root_index_seed: RwLock::new(None),
+            transport_metrics: RwLock::new(Vec::new()),
+            metrics_server: Mutex::new(None),
             create_new_system: create_new_system,
+            config,
         }));
 
-        let localdirect = self::transport::LocalDirect::new();
-        net.add_transport(Box::new(localdirect));
+        if net.config.local_transport {
+            let localdirect = self::transport::LocalDirect::new();
+            net.add_transport(Box::new(localdirect));
+        }
+
+        if let Some(ref address) = net.config.metrics_address {
+            match MetricsServer::start(&net, address) {
+                Ok(server) => { *net.metrics_server.lock().unwrap() = Some(server); },
+                Err(e)     => println!("WARNING - Unable to serve metrics on {}: {}", address, e)
+            }
+        }
 
         net
     }
 
-    // TODO: remove this when slab ids are randomly generated
-    pub fn hack_set_next_slab_id(&self, id: SlabId) {
-        *self.next_slab_id.write().unwrap() = id;
-    }
     pub fn weak(&self) -> WeakNetwork {
         WeakNetwork(Arc::downgrade(&self.0))
     }
@@ -89,12 +113,38 @@ impl Network {
         self.transports.write().unwrap().push(transport);
     }
 
-    pub fn generate_slab_id(&self) -> u32 {
-        let mut next_slab_id = self.next_slab_id.write().unwrap();
-        let id = *next_slab_id;
-        *next_slab_id += 1;
-
-        id
+    /// Called by transports upon binding, such that their metrics are included in render_metrics
+    pub fn register_transport_metrics(&self, name: &str, metrics: Arc<TransportMetrics>) {
+        self.transport_metrics.write().unwrap().push((name.to_string(), metrics));
+    }
+    pub fn deregister_transport_metrics(&self, metrics: &Arc<TransportMetrics>) {
+        self.transport_metrics.write().unwrap().retain(|(_, m)| !Arc::ptr_eq(m, metrics));
+    }
+    pub fn get_transport_metrics(&self) -> Vec<(String, Arc<TransportMetrics>)> {
+        self.transport_metrics.read().unwrap().clone()
+    }
+    /// The metrics of all local slabs and bound transports, in the Prometheus text exposition format
+    pub fn render_metrics(&self) -> String {
+        metrics::render(self)
+    }
+    /// The address of the metrics HTTP listener, if one was started ( see NetworkConfig.metrics_address )
+    pub fn metrics_address(&self) -> Option<std::net::SocketAddr> {
+        self.metrics_server.lock().unwrap().as_ref().map(|s| s.local_addr())
+    }
+    /// Generate a random slab id which is not already in use by this process
+    pub fn generate_slab_id(&self) -> SlabId {
+        let mut issued = self.issued_slab_ids.lock().unwrap();
+        loop {
+            let id = random_slab_id();
+            if issued.insert(id) {
+                return id;
+            }
+        }
+    }
+    /// Claim a slab id which was restored from storage, such that it will not be issued to another slab.
+    /// Returns false if the id is already in use by this process
+    pub fn reserve_slab_id(&self, slab_id: SlabId) -> bool {
+        self.issued_slab_ids.lock().unwrap().insert(slab_id)
     }
     pub fn get_slab(&self, slab_id: SlabId) -> Option<Slab> {
         if let Some(weak) = self.slabs.read().unwrap().iter().find(|s| s.id == slab_id) {
@@ -174,6 +224,9 @@ impl Network {
             }
         }
 
+        // The id may now be reclaimed, eg: by reopening the slab from storage
+        self.issued_slab_ids.lock().unwrap().remove(&slab_id);
+
         // If the deregistered slab is the one that's holding the root_index_seed
         // then we need to move it to a different slab
 
@@ -199,9 +252,10 @@ impl Network {
         root_index_seed.take();
     }
     pub fn get_root_index_seed(&self, slab: &Slab) -> Option<MemoRefHead> {
-        let root_index_seed = self.root_index_seed.read().expect("root_index_seed read lock");
+        // Cloning the seed into another slab may ingest memos there, so don't hold the lock while we do it
+        let root_index_seed = self.root_index_seed.read().expect("root_index_seed read lock").clone();
 
-        match *root_index_seed {
+        match root_index_seed {
             Some((ref seed, ref from_slabref)) => {
                 if from_slabref.owning_slab_id == slab.id {
                     // seed is resident on the requesting slab
@@ -270,7 +324,7 @@ impl Network {
 impl fmt::Debug for Network {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("Network")
-            .field("next_slab_id", &self.next_slab_id.read().unwrap())
+            .field("issued_slab_ids", &*self.issued_slab_ids.lock().unwrap())
             .finish()
     }
 }
@@ -300,3 +354,8 @@ impl WeakNetwork {
         }
     }
 }
+
+/// A random, nonzero slab id. Zero is reserved as the destination of packets for any slab ( see PacketSeed )
+fn random_slab_id() -> SlabId {
+    crate::util::random::nonzero_u64()
+}
@@ -82,6 +82,16 @@ impl<'a> Visitor for PacketSeed<'a> {
            lifetime: SlabAnticipatedLifetime::Unknown
        };
 
+       // This packet arrived from elsewhere, but claims to originate from one of our own slabs
+       if self.net.get_slab( from_slab_id ).is_some() {
+           dest_slab.report_slab_id_conflict(SlabIdConflict{
+               slab_id: from_slab_id,
+               known_addresses: vec![TransportAddress::Local],
+               claimed_address: self.source_address.clone(),
+           });
+           return Err(DeError::custom("Packet claims the id of a local slab"));
+       }
+
        let origin_slabref = dest_slab.slabref_from_presence(&from_presence).expect("slabref from presence");
 
        // no need to return the memo here, as it's added to the slab
@@ -2,6 +2,7 @@
 use std::sync::mpsc;
 use super::*;
 use crate::slab::*;
+use crate::metrics::TransportMetrics;
 
 /// A trait for transmitters to implement
 pub trait DynamicDispatchTransmitter {
@@ -10,7 +11,7 @@ pub trait DynamicDispatchTransmitter {
 }
 
 enum TransmitterInternal {
-    Local(Mutex<mpsc::Sender<(SlabRef,MemoRef)>>),
+    Local(Mutex<mpsc::Sender<(SlabRef,MemoRef)>>, Arc<TransportMetrics>),
     Dynamic(Box<dyn DynamicDispatchTransmitter + Send + Sync>),
     Blackhole
 }
@@ -33,7 +34,7 @@ impl<'a> TransmitterArgs<'a>{
 impl TransmitterInternal {
     pub fn kind (&self) -> &str {
         match self {
-            &TransmitterInternal::Local(_)   => "Local",
+            &TransmitterInternal::Local(..)  => "Local",
             &TransmitterInternal::Dynamic(_) => "Dynamic",
             &TransmitterInternal::Blackhole  => "Blackhole"
         }
@@ -47,10 +48,10 @@ pub struct Transmitter {
 
 impl Transmitter {
     /// Create a new transmitter associated with a local slab.
-    pub fn new_local( to_slab_id: SlabId, tx: Mutex<mpsc::Sender<(SlabRef,MemoRef)>> ) -> Self {
+    pub fn new_local( to_slab_id: SlabId, tx: Mutex<mpsc::Sender<(SlabRef,MemoRef)>>, metrics: Arc<TransportMetrics> ) -> Self {
         Self {
             to_slab_id: to_slab_id,
-            internal: TransmitterInternal::Local( tx )
+            internal: TransmitterInternal::Local( tx, metrics )
         }
     }
     pub fn new_blackhole(to_slab_id: SlabId) -> Self {
@@ -74,8 +75,10 @@ impl Transmitter {
 
         use self::TransmitterInternal::*;
         match self.internal {
-            Local(ref tx) => {
+            Local(ref tx, ref metrics) => {
                 //println!("CHANNEL SEND from {}, {:?}", from.slab_id, memo);
+                metrics.packets_sent.inc();
+                metrics.bytes_sent.add(transport::in_memory_size(&memoref) as u64);
                 // TODO - stop assuming that this is resident on the sending slab just because we're sending it
                 // TODO - lose the stupid lock on the transmitter
                 tx.lock().unwrap().send((from.clone(),memoref)).expect("local transmitter send")
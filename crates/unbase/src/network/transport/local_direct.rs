@@ -3,13 +3,18 @@ use std::thread;
 use std::sync::mpsc;
 use crate::slab::*;
 use super::*;
+use crate::metrics::TransportMetrics;
+
+/// The most memos which will be ingested as a single batch
+const MAX_BATCH : usize = 256;
 
 #[derive(Clone)]
 pub struct LocalDirect {
     shared: Arc<Mutex<Internal>>,
 }
 struct Internal {
-    tx_threads: Vec<thread::JoinHandle<()>>
+    tx_threads: Vec<thread::JoinHandle<()>>,
+    metrics: Arc<TransportMetrics>
 }
 
 impl LocalDirect {
@@ -18,7 +23,8 @@ impl LocalDirect {
         LocalDirect {
             shared: Arc::new(Mutex::new(
                 Internal {
-                    tx_threads: Vec::new()
+                    tx_threads: Vec::new(),
+                    metrics: Arc::new(TransportMetrics::default())
                 }
             ))
         }
@@ -33,17 +39,43 @@ impl Transport for LocalDirect {
         if let &TransmitterArgs::Local(rcv_slab) = args {
             let slab = rcv_slab.weak();
             let (tx_channel, rx_channel) = mpsc::channel::<(SlabRef,MemoRef)>();
+            let metrics = self.shared.lock().unwrap().metrics.clone();
+            let tx_metrics = metrics.clone();
 
             let tx_thread : thread::JoinHandle<()> = thread::spawn(move || {
                 //let mut buf = [0; 65536];
                 //println!("Started TX Thread");
-                while let Ok((from_slabref, memoref)) = rx_channel.recv() {
-                    //println!("LocalDirect Slab({}) RECEIVED {:?} from {}", slab.id, memoref, from_slabref.slab_id);
+                while let Ok(first) = rx_channel.recv() {
+                    // Take whatever else has already arrived, so memos sent together are ingested together
+                    let mut received = vec![first];
+                    while received.len() < MAX_BATCH {
+                        match rx_channel.try_recv() {
+                            Ok(next) => received.push(next),
+                            Err(_)   => break
+                        }
+                    }
+                    // Sends are counted by the transmitter
+                    let bytes : usize = received.iter().map(|(_, memoref)| in_memory_size(memoref) ).sum();
+                    metrics.packets_received.add(received.len() as u64);
+                    metrics.bytes_received.add(bytes as u64);
+
                     if let Some(slab) = slab.upgrade(){
-                        // clone_for_slab adds the memo to the slab, because memos cannot exist outside of an owning slab
+                        // A batch must originate from a single slab, so split the received memos into runs by sender
+                        let mut received = received.into_iter().peekable();
+                        while let Some((from_slabref, memoref)) = received.next() {
+                            let mut run = vec![memoref];
+                            while let Some((next_slabref, _)) = received.peek() {
+                                if next_slabref.slab_id != from_slabref.slab_id {
+                                    break;
+                                }
+                                run.push(received.next().unwrap().1);
+                            }
 
-                        let owned_slabref = from_slabref.clone_for_slab(&slab);
-                        memoref.clone_for_slab(&owned_slabref, &slab, true);
+                            //println!("LocalDirect Slab({}) RECEIVED {:?} from {}", slab.id, run, from_slabref.slab_id);
+                            // cloning adds the memos to the slab, because memos cannot exist outside of an owning slab
+                            let owned_slabref = from_slabref.clone_for_slab(&slab);
+                            MemoRef::clone_all_for_slab(&run, &owned_slabref, &slab);
+                        }
                     }
                 }
             });
@@ -51,15 +83,19 @@ impl Transport for LocalDirect {
             // TODO: Remove the mutex here. Consider moving transmitter out of slabref.
             //       Instead, have relevant parties request a transmitter clone from the network
             self.shared.lock().unwrap().tx_threads.push(tx_thread);
-            Some(Transmitter::new_local(args.get_slab_id(), Mutex::new(tx_channel)))
+            Some(Transmitter::new_local(args.get_slab_id(), Mutex::new(tx_channel), tx_metrics))
         }else{
             None
         }
 
     }
 
-    fn bind_network(&self, _net: &Network) {}
-    fn unbind_network(&self, _net: &Network) {}
+    fn bind_network(&self, net: &Network) {
+        net.register_transport_metrics("local", self.shared.lock().unwrap().metrics.clone());
+    }
+    fn unbind_network(&self, net: &Network) {
+        net.deregister_transport_metrics(&self.shared.lock().unwrap().metrics);
+    }
 
     fn get_return_address  ( &self, address: &TransportAddress ) -> Option<TransportAddress> {
         if let TransportAddress::Local = *address {
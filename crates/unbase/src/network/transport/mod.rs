@@ -14,6 +14,8 @@ pub use self::blackhole::Blackhole;
 pub use super::transmitter::{Transmitter, DynamicDispatchTransmitter};
 
 use crate::network::*;
+use crate::slab::MemoRef;
+use std::mem::size_of;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum TransportAddress{
@@ -28,6 +30,12 @@ pub enum TransportAddress{
     ShamefulTCP // SHAME! SHAME! SHAME! ( yes, I _really_ want to discourage people from using TCP )
 }
 
+/// The bytes conveyed by a transport which passes memos in memory rather than serializing them. Those which the
+/// sender no longer holds are counted as the memoref alone
+pub (crate) fn in_memory_size (memoref: &MemoRef) -> usize {
+    memoref.get_memo_if_resident().map_or(size_of::<MemoRef>(), |memo| memo.approximate_size() )
+}
+
 pub trait Transport {
     fn make_transmitter(  &self, args: &TransmitterArgs  ) -> Option<Transmitter>;
     fn is_local        (  &self ) -> bool;
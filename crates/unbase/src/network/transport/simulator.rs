@@ -5,6 +5,7 @@ use std::sync::{Arc,Mutex};
 use crate::slab::*;
 use itertools::partition;
 use crate::network::*;
+use crate::metrics::TransportMetrics;
 
 // Minkowski stuff: Still ridiculous, but necessary for our purposes.
 pub struct XYZPoint{
@@ -35,7 +36,7 @@ impl SimEvent {
             println!("Simulator.deliver FROM {} TO {} -> {}({:?}): {:?} {:?} {:?}",
                 &self.from_slabref.slab_id,
                 &to_slab.id,
-                &self.memoref.id,
+                &self.memoref.id(),
                 &self.memoref.subject_id,
                 &memo.body,
                 &memo.parents.memo_ids(),
@@ -61,6 +62,7 @@ impl fmt::Debug for SimEvent{
 pub struct Simulator {
     shared: Arc<Mutex<SimulatorInternal>>,
     speed_of_light: u64,
+    metrics: Arc<TransportMetrics>,
 }
 struct SimulatorInternal {
     clock: u64,
@@ -71,6 +73,7 @@ impl Simulator {
     pub fn new() -> Self{
         Simulator {
             speed_of_light: 1, // 1 distance unit per time unit
+            metrics: Arc::new(TransportMetrics::default()),
             shared: Arc::new(Mutex::new(
                 SimulatorInternal {
                     clock: 0,
@@ -102,6 +105,8 @@ impl Simulator {
 
             events = shared.queue.drain(0..split_index).collect();
         }
+        self.metrics.packets_received.add(events.len() as u64);
+        self.metrics.bytes_received.add(events.iter().map(|event| in_memory_size(&event.memoref) as u64 ).sum());
         for event in events {
             event.deliver();
         }
@@ -135,8 +140,12 @@ impl Transport for Simulator {
         }
 
     }
-    fn bind_network(&self, _net: &Network) {}
-    fn unbind_network(&self, _net: &Network) {}
+    fn bind_network(&self, net: &Network) {
+        net.register_transport_metrics("simulator", self.metrics.clone());
+    }
+    fn unbind_network(&self, net: &Network) {
+        net.deregister_transport_metrics(&self.metrics);
+    }
     fn get_return_address  ( &self, address: &TransportAddress ) -> Option<TransportAddress> {
         if let TransportAddress::Local = *address {
             Some(TransportAddress::Local)
@@ -187,6 +196,8 @@ impl DynamicDispatchTransmitter for SimulatorTransmitter {
             memoref: memoref
         };
 
+        self.simulator.metrics.packets_sent.inc();
+        self.simulator.metrics.bytes_sent.add(in_memory_size(&evt.memoref) as u64);
         self.simulator.add_event( evt );
     }
 }
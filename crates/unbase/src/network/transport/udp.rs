@@ -12,6 +12,7 @@ use crate::util::serde::DeserializeSeed;
 
 use crate::util::serde::{SerializeHelper,SerializeWrapper};
 use super::packet::serde::PacketSeed;
+use crate::metrics::TransportMetrics;
 //use std::time;
 
 use serde_json;// {serialize as bin_serialize, deserialize as bin_deserialize};
@@ -27,7 +28,8 @@ struct TransportUDPInternal {
     rx_thread: Option<thread::JoinHandle<()>>,
     tx_channel: Option<Arc<Mutex<Option<mpsc::Sender<(TransportAddressUDP,Packet)>>>>>,
     network: Option<WeakNetwork>,
-    address: TransportAddressUDP
+    address: TransportAddressUDP,
+    metrics: Arc<TransportMetrics>
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -59,7 +61,8 @@ impl TransportUDP {
         let socket = Arc::new( UdpSocket::bind( bind_address.address.clone() ).expect("UdpSocket::bind") );
         //socket.set_read_timeout( Some(time::Duration::from_millis(2000)) ).expect("set_read_timeout call failed");
 
-        let (tx_thread,tx_channel) = Self::setup_tx_thread(socket.clone(), bind_address.clone());
+        let metrics = Arc::new(TransportMetrics::default());
+        let (tx_thread,tx_channel) = Self::setup_tx_thread(socket.clone(), bind_address.clone(), metrics.clone());
 
         TransportUDP {
             shared: Arc::new(Mutex::new(
@@ -69,13 +72,14 @@ impl TransportUDP {
                     tx_thread: Some(tx_thread),
                     tx_channel: Some(Arc::new(Mutex::new(Some(tx_channel)))),
                     network: None,
-                    address: bind_address
+                    address: bind_address,
+                    metrics
                 }
             ))
         }
     }
 
-    fn setup_tx_thread (socket: Arc<UdpSocket>, inbound_address: TransportAddressUDP ) -> (thread::JoinHandle<()>,mpsc::Sender<(TransportAddressUDP, Packet)>){
+    fn setup_tx_thread (socket: Arc<UdpSocket>, inbound_address: TransportAddressUDP, metrics: Arc<TransportMetrics> ) -> (thread::JoinHandle<()>,mpsc::Sender<(TransportAddressUDP, Packet)>){
 
         let (tx_channel, rx_channel) = mpsc::channel::<(TransportAddressUDP,Packet)>();
 
@@ -97,6 +101,8 @@ impl TransportUDP {
 
                 //HACK: we're trusting that each memo is smaller than 64k
                 socket.send_to(&b, &to_address.address).expect("Failed to send");
+                metrics.packets_sent.inc();
+                metrics.bytes_sent.add(b.len() as u64);
                 //println!("SENT UDP PACKET ({}) {}", &to_address.address, &String::from_utf8(b).unwrap());
             }
     });
@@ -196,6 +202,8 @@ impl Transport for TransportUDP {
         }
 
         let rx_socket = shared.socket.clone();
+        let rx_metrics = shared.metrics.clone();
+        net.register_transport_metrics(&shared.address.to_string(), shared.metrics.clone());
         //let dispatcher = TransportUDPDispatcher::new(net.clone());
 
         let net_weak = net.weak();
@@ -203,6 +211,8 @@ impl Transport for TransportUDP {
             let mut buf = [0; 65536];
 
             while let Ok((amt, src)) = rx_socket.recv_from(&mut buf) {
+                rx_metrics.packets_received.inc();
+                rx_metrics.bytes_received.add(amt as u64);
 
                 if let Some(net) = net_weak.upgrade() {
 
@@ -1,4 +1,9 @@
 use super::*;
+use super::storage::StorageError;
+use sha2::{Sha256, Digest};
+
+/// How many subject ids are reserved each time the counters are persisted
+const SUBJECT_ID_RESERVATION : u64 = 1024;
 
 impl Deref for Slab {
     type Target = SlabInner;
@@ -9,7 +14,45 @@ impl Deref for Slab {
 
 impl Slab {
     pub fn new(net: &Network) -> Slab {
-        let slab_id = net.generate_slab_id();
+        Self::new_with_config(net, net.config.slab.clone())
+    }
+    pub fn new_with_config(net: &Network, config: SlabConfig) -> Slab {
+        Self::new_with_config_and_storage(net, config, Box::new(storage::Blackhole::new())).expect("blackhole storage")
+    }
+    /// Create a slab whose contents are written through to the provided storage backend.
+    /// If the storage contains a previously persisted slab, that slab is reopened with its
+    /// id, counters, memos, peerlists and root index seed intact.
+    pub fn new_with_storage(net: &Network, storage: Box<dyn StorageBackend + Send + Sync>) -> Result<Slab,StorageError> {
+        Self::new_with_config_and_storage(net, net.config.slab.clone(), storage)
+    }
+    pub fn new_with_config_and_storage(net: &Network, config: SlabConfig, storage: Box<dyn StorageBackend + Send + Sync>) -> Result<Slab,StorageError> {
+        let stored_slab_id : Option<SlabId> = Self::load_record(&*storage, &StorageKey::SlabId)?;
+
+        let counters = match Self::load_record::<(u64,u64,u64)>(&*storage, &StorageKey::Counters)? {
+            Some((subject_ids_reserved, memos_received, memos_redundantly_received)) => SlabCounters {
+                last_subject_id: subject_ids_reserved,
+                subject_ids_reserved,
+                memos_received,
+                memos_redundantly_received,
+            },
+            None => SlabCounters {
+                last_subject_id: config.initial_subject_counter as u64,
+                subject_ids_reserved: config.initial_subject_counter as u64,
+                memos_received: 0,
+                memos_redundantly_received: 0,
+            }
+        };
+
+        let slab_id = match stored_slab_id {
+            Some(slab_id) => {
+                // Two copies of the same storage must not be open at once
+                if !net.reserve_slab_id(slab_id) {
+                    return Err(StorageError::SlabIdInUse(slab_id));
+                }
+                slab_id
+            },
+            None => net.generate_slab_id()
+        };
 
         let my_ref_inner = SlabRefInner {
             slab_id: slab_id,
@@ -21,47 +64,65 @@ impl Slab {
 
         let my_ref = SlabRef(Arc::new(my_ref_inner));
         // TODO: figure out how to reconcile this with the simulator
-        let (memoref_dispatch_tx_channel, memoref_dispatch_rx_channel) = mpsc::channel::<MemoRef>();
+        let dispatch_pool = DispatchPool::new(config.dispatch_workers, config.dispatch_queue_limit);
 
         let inner = SlabInner {
             id: slab_id,
             memorefs_by_id:        RwLock::new(HashMap::new()),
+            unhashed_memorefs:     Mutex::new(HashMap::new()),
+            collected:             RwLock::new(HashMap::new()),
             memo_wait_channels:    Mutex::new(HashMap::new()),
             subject_subscriptions: RwLock::new(HashMap::new()),
 
-            counters: RwLock::new(SlabCounters {
-                last_memo_id: 5000,
-                last_subject_id: 9000,
-                memos_received: 0,
-                memos_redundantly_received: 0,
-            }),
+            counters: RwLock::new(counters),
+            durability_targets: RwLock::new(DurabilityTargets::new(config.default_durability_target)),
+            residency: Mutex::new(ResidencyTracker::new(config.memory_budget, EvictionPolicy::LeastRecentlyUsed)),
+            evicting: AtomicBool::new(false),
+            peering_queue: Mutex::new(PeeringQueue::new(config.peering_window())),
+            peering_received: Condvar::new(),
+            peering_received_lock: Mutex::new(()),
+            plumtree: Mutex::new(PlumtreeState::new()),
 
-            memoref_dispatch_tx_channel: Some(Mutex::new(memoref_dispatch_tx_channel)),
-            memoref_dispatch_thread: RwLock::new(None),
+            dispatch_pool,
 
             my_ref: my_ref,
             peer_refs: RwLock::new(Vec::new()),
+            peer_selector: RwLock::new(config.peer_selection.build()),
+            peer_rtts: RwLock::new(HashMap::new()),
+            outstanding_requests: Mutex::new(OutstandingRequests::new()),
             net: net.clone(),
+            storage,
+            restoring: AtomicBool::new(false),
+            restored_heads: RwLock::new(HashMap::new()),
+            slab_id_conflicts: Mutex::new(Vec::new()),
+            integrity_findings: Mutex::new(Vec::new()),
+            closed: AtomicBool::new(false),
+            config,
+            metrics: SlabMetrics::new(),
             dropping: false
         };
 
         let me = Slab(Arc::new(inner));
         net.register_local_slab(&me);
 
-        let weak_self = me.weak();
+        if stored_slab_id.is_some() {
+            me.restore_from_storage()?;
+        }else{
+            me.persist_slab_id();
+        }
 
-        // TODO: this should really be a thread pool, or get_memo should be changed to be nonblocking somhow
-        *me.memoref_dispatch_thread.write().unwrap() = Some(thread::spawn(move || {
-            while let Ok(memoref) = memoref_dispatch_rx_channel.recv() {
-                if let Some(slab) = weak_self.upgrade(){
-                    slab.dispatch_memoref(memoref);
-                }
+        let weak_slab = me.weak();
+        me.dispatch_pool.start(move |subject_id, memorefs| {
+            if let Some(slab) = weak_slab.upgrade(){
+                slab.dispatch_subject_memorefs(subject_id, memorefs);
             }
-        }));
+        });
 
-        net.conditionally_generate_root_index_seed(&me);
+        if net.conditionally_generate_root_index_seed(&me) {
+            me.persist_root_index_seed();
+        }
 
-        me
+        Ok(me)
     }
     pub fn weak (&self) -> WeakSlab {
         WeakSlab {
@@ -98,20 +159,72 @@ impl Slab {
             return;
         }
     }
+    /// Register interest in a memo, which will be sent through the returned channel upon its arrival
     pub fn memo_wait_channel (&self, memo_id: MemoId ) -> mpsc::Receiver<Memo> {
-        let (tx, rx) = channel::<Memo>();
-
+        let (tx, rx) = mpsc::channel::<Memo>();
+        self.add_memo_waiter(memo_id, MemoWaiter::Blocking(tx));
+        rx
+    }
+    /// Like memo_wait_channel, but for those who would rather await the memo than block on it
+    pub fn memo_wait_future (&self, memo_id: MemoId ) -> oneshot::Receiver<Memo> {
+        let (tx, rx) = oneshot::channel::<Memo>();
+        self.add_memo_waiter(memo_id, MemoWaiter::Async(tx));
+        rx
+    }
+    fn add_memo_waiter (&self, memo_id: MemoId, waiter: MemoWaiter) {
         match self.memo_wait_channels.lock().unwrap().entry(memo_id) {
-            Entry::Vacant(o)       => { o.insert( vec![tx] ); }
-            Entry::Occupied(mut o) => { o.get_mut().push(tx); }
+            Entry::Vacant(o)       => { o.insert( vec![waiter] ); }
+            Entry::Occupied(mut o) => { o.get_mut().push(waiter); }
         };
-
-        rx
     }
     pub fn generate_subject_id(&self) -> SubjectId {
-        let mut counters = self.counters.write().unwrap();
-        counters.last_subject_id += 1;
-        (self.id as u64).rotate_left(32) | counters.last_subject_id as u64
+        let mut reserved = false;
+        let subject_id = {
+            let mut counters = self.counters.write().unwrap();
+            let subject_id = loop {
+                counters.last_subject_id = counters.last_subject_id.checked_add(1).expect("subject id counter exhausted");
+
+                // zero denotes the absence of a subject in relation links. One count of the counter maps to it
+                match Self::subject_id_for_count(self.id, counters.last_subject_id) {
+                    0          => continue,
+                    subject_id => break subject_id
+                }
+            };
+
+            if counters.last_subject_id > counters.subject_ids_reserved {
+                counters.subject_ids_reserved = counters.last_subject_id.saturating_add(SUBJECT_ID_RESERVATION);
+                reserved = true;
+            }
+            subject_id
+        };
+
+        // The reservation must be stored before any subject id under it is issued
+        if reserved {
+            self.persist_counters();
+        }
+
+        subject_id
+    }
+    /// Slab ids are as wide as subject ids, so the two can't simply be packed together. Instead, the count is
+    /// scrambled with keys taken from a hash of the whole slab id. Each step is reversible, so the subject ids of a
+    /// given slab never coincide with one another. Those of different slabs are as likely to as any two random ids
+    fn subject_id_for_count (slab_id: SlabId, count: u64) -> SubjectId {
+        let mut hasher = Sha256::new();
+        hasher.input(slab_id.to_le_bytes());
+        let hash = hasher.result();
+
+        let mut keys = [[0u8; 8]; 2];
+        keys[0].copy_from_slice(&hash.as_slice()[0..8]);
+        keys[1].copy_from_slice(&hash.as_slice()[8..16]);
+
+        // The finalizer of splitmix64, which is a bijection
+        let mix = |mut x: u64| {
+            x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+            x ^ (x >> 31)
+        };
+
+        mix(mix(count ^ u64::from_le_bytes(keys[0])) ^ u64::from_le_bytes(keys[1]))
     }
 }
 
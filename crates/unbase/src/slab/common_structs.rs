@@ -1,5 +1,6 @@
 use super::*;
 use crate::network::TransportAddress;
+use std::cmp::Reverse;
 
 /// SlabPresence represents the expected reachability of a given Slab
 /// Including Transport address and anticipated lifetime
@@ -25,6 +26,16 @@ impl fmt::Debug for SlabPresence {
     }
 }
 
+/// A slab id was claimed by more than one slab. This would otherwise lead to the two slabs' memos being confused
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlabIdConflict {
+    pub slab_id: SlabId,
+    /// Addresses at which we already knew the slab to be present
+    pub known_addresses: Vec<TransportAddress>,
+    /// The address from which the conflicting claim was made
+    pub claimed_address: TransportAddress,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum SlabAnticipatedLifetime {
     Ephmeral,
@@ -78,6 +89,37 @@ impl MemoPeerList {
         peerlist.push(peer);
         true
     }
+    /// Record a peer in the peerlist of a memoref which we hold. Unlike apply_peer, NonParticipating peers
+    /// are forgotten rather than recorded, and the most recently recorded peers are kept at the front
+    pub fn record_peer(&mut self, peer: MemoPeer) -> bool {
+        let peerlist = &mut self.0;
+
+        if peer.status == MemoPeeringStatus::NonParticipating {
+            let len = peerlist.len();
+            peerlist.retain(|p| p.slabref.slab_id != peer.slabref.slab_id);
+            return peerlist.len() != len;
+        }
+
+        let mut acted = true;
+        if let Some(i) = peerlist.iter().position(|p| p.slabref.slab_id == peer.slabref.slab_id) {
+            acted = peerlist.remove(i).status != peer.status;
+        }
+
+        peerlist.insert(0, peer);
+        acted
+    }
+    /// Retain at most `limit` peers, preferring those most likely to furnish the memo when asked:
+    /// by status, then by the anticipated lifetime of their slab, then by how recently they were recorded
+    pub fn prune(&mut self, limit: usize) -> bool {
+        if self.0.len() <= limit {
+            return false;
+        }
+
+        // The sort is stable, so recency breaks ties
+        self.0.sort_by_key(|p| Reverse(p.retention_rank()));
+        self.0.truncate(limit);
+        true
+    }
 }
 
 impl Deref for MemoPeerList {
@@ -93,6 +135,19 @@ pub struct MemoPeer {
     pub status: MemoPeeringStatus,
 }
 
+impl MemoPeer {
+    /// Preference for retaining this peer over others in a bounded peerlist. Higher is better
+    fn retention_rank(&self) -> (u8, DurabilityScore) {
+        let status = match self.status {
+            MemoPeeringStatus::Resident         => 3,
+            MemoPeeringStatus::Participating    => 2,
+            MemoPeeringStatus::Unknown          => 1,
+            MemoPeeringStatus::NonParticipating => 0,
+        };
+        (status, self.slabref.get_anticipated_lifetime().durability_weight())
+    }
+}
+
 #[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
 pub enum MemoPeeringStatus {
     Resident,
@@ -102,7 +157,7 @@ pub enum MemoPeeringStatus {
 }
 
 #[derive(Clone, Debug)]
-pub struct RelationSlotSubjectHead(pub HashMap<RelationSlotId, (SubjectId, MemoRefHead)>);
+pub struct RelationSlotSubjectHead(pub HashMap<RelationSlotId, Option<(SubjectId, MemoRefHead)>>);
 
 impl RelationSlotSubjectHead {
     pub fn clone_for_slab(&self, from_slabref: &SlabRef, to_slab: &Slab) -> Self {
@@ -111,8 +166,8 @@ impl RelationSlotSubjectHead {
         // panic!("check here to make sure that peers are being properly constructed for the root_index_seed");
         let new = self.0
             .iter()
-            .map(|(slot_id, &(subject_id, ref mrh))| {
-                (*slot_id, (subject_id, mrh.clone_for_slab(from_slabref, to_slab, false)))
+            .map(|(slot_id, relation)| {
+                (*slot_id, relation.as_ref().map(|&(subject_id, ref mrh)| (subject_id, mrh.clone_for_slab(from_slabref, to_slab, false))))
             })
             .collect();
 
@@ -123,14 +178,20 @@ impl RelationSlotSubjectHead {
     }
     pub fn single(slot_id: RelationSlotId, subject_id: SubjectId, head: MemoRefHead) -> Self {
         let mut hashmap = HashMap::new();
-        hashmap.insert(slot_id, (subject_id, head));
+        hashmap.insert(slot_id, Some((subject_id, head)));
+        RelationSlotSubjectHead(hashmap)
+    }
+    /// A tombstone for the given slot, which clears any relation previously set there
+    pub fn cleared(slot_id: RelationSlotId) -> Self {
+        let mut hashmap = HashMap::new();
+        hashmap.insert(slot_id, None);
         RelationSlotSubjectHead(hashmap)
     }
 }
 
 impl Deref for RelationSlotSubjectHead {
-    type Target = HashMap<RelationSlotId, (SubjectId, MemoRefHead)>;
-    fn deref(&self) -> &HashMap<RelationSlotId, (SubjectId, MemoRefHead)> {
+    type Target = HashMap<RelationSlotId, Option<(SubjectId, MemoRefHead)>>;
+    fn deref(&self) -> &HashMap<RelationSlotId, Option<(SubjectId, MemoRefHead)>> {
         &self.0
     }
 }
@@ -1,52 +1,138 @@
 
 use super::*;
+use crate::error::IntegrityError;
+use std::mem;
+
+/// The constituents of a memo received from another slab, for batched ingress via reconstitute_memos.
+/// The parents, body and peerlist must already belong to the receiving slab
+pub struct MemoParts {
+    pub id:         MemoId,
+    pub subject_id: Option<SubjectId>,
+    pub parents:    MemoRefHead,
+    pub body:       MemoBody,
+    pub peerlist:   MemoPeerList,
+}
 
 impl Slab {
     pub fn new_memo ( &self, subject_id: Option<SubjectId>, parents: MemoRefHead, body: MemoBody) -> MemoRef {
-        let mut counters = self.counters.write().unwrap();
-        counters.last_memo_id += 1;
-        let memo_id = (self.id as u64).rotate_left(32) | counters.last_memo_id as u64;
-
-        //println!("# Slab({}).new_memo(id: {},subject_id: {:?}, parents: {:?}, body: {:?})", self.id, memo_id, subject_id, parents.memo_ids(), body );
+        //println!("# Slab({}).new_memo(subject_id: {:?}, parents: {:?}, body: {:?})", self.id, subject_id, parents.memo_ids(), body );
 
+        // The id is calculated if and when it's needed. Until then, the memo is known only by its memoref
         let memo = Memo::new(MemoInner {
-            id:    memo_id,
+            id:    LazyMemoId::new(self),
             owning_slab_id: self.id,
             subject_id: subject_id,
             parents: parents,
             body: body
         });
 
-        let (memoref, _had_memoref) = self.assert_memoref(memo.id, memo.subject_id, MemoPeerList(Vec::new()), Some(memo) );
-        self.consider_emit_memo(&memoref);
+        let memoref = MemoRef(Arc::new(
+            MemoRefInner {
+                id:             memo.id.clone(),
+                owning_slab_id: self.id,
+                subject_id,
+                peerlist:       RwLock::new(MemoPeerList::new(Vec::new())),
+                ptr:            RwLock::new(MemoRefPtr::Resident(memo))
+            }
+        ));
+
+        self.unhashed_memorefs.lock().unwrap().insert(memoref.id.key(), memoref.clone());
+        self.track_resident_memo(&memoref);
+        self.persist_memoref(&memoref);
+
+        self.metrics.memos_created.inc();
+
+        // A slab which has been shut down keeps to itself
+        if !self.is_closed() {
+            self.emit_new_memo(&memoref);
+        }
+        self.conditionally_evict_memos();
 
         memoref
     }
-    pub fn reconstitute_memo ( &self, memo_id: MemoId, subject_id: Option<SubjectId>, parents: MemoRefHead, body: MemoBody, origin_slabref: &SlabRef, peerlist: &MemoPeerList ) -> (Memo,MemoRef,bool){
+    pub fn reconstitute_memo ( &self, memo_id: MemoId, subject_id: Option<SubjectId>, parents: MemoRefHead, body: MemoBody, origin_slabref: &SlabRef, peerlist: &MemoPeerList ) -> Result<(Memo,MemoRef,bool),IntegrityError>{
         //println!("Slab({}).reconstitute_memo({})", self.id, memo_id );
+
+        let (memo, memoref, had_memoref) = self.ingest_memo(memo_id, subject_id, parents, body, origin_slabref, peerlist.clone())?;
+
+        self.count_received_memos(1, if had_memoref { 1 } else { 0 });
+
+        if let Some(subject_id) = memoref.subject_id {
+            self.dispatch_pool.dispatch(subject_id, vec![memoref.clone()]);
+        }
+
+        self.conditionally_evict_memos();
+
+        Ok((memo, memoref, had_memoref))
+    }
+    /// Batched equivalent of reconstitute_memo, for memos received together from the same slab.
+    ///
+    /// Counters are updated, and each subject's head dispatched to its subscribers once per batch rather than once per memo.
+    /// Results are returned in the order of the memos provided
+    pub fn reconstitute_memos ( &self, memos: Vec<MemoParts>, origin_slabref: &SlabRef ) -> Vec<Result<(Memo,MemoRef,bool),IntegrityError>> {
+        let mut results = Vec::with_capacity(memos.len());
+        let mut subject_updates : HashMap<SubjectId, Vec<MemoRef>> = HashMap::new();
+        let mut received = 0u64;
+        let mut redundant = 0u64;
+
+        for parts in memos {
+            let result = self.ingest_memo(parts.id, parts.subject_id, parts.parents, parts.body, origin_slabref, parts.peerlist);
+
+            if let Ok((_, ref memoref, had_memoref)) = result {
+                received += 1;
+                if had_memoref {
+                    redundant += 1;
+                }
+                if let Some(subject_id) = memoref.subject_id {
+                    subject_updates.entry(subject_id).or_default().push(memoref.clone());
+                }
+            }
+
+            results.push(result);
+        }
+
+        self.count_received_memos(received, redundant);
+
+        for (subject_id, memorefs) in subject_updates {
+            self.dispatch_pool.dispatch(subject_id, memorefs);
+        }
+
+        self.conditionally_evict_memos();
+
+        results
+    }
+    /// Verify and store a memo received from another slab, and respond to it as needed
+    fn ingest_memo ( &self, memo_id: MemoId, subject_id: Option<SubjectId>, parents: MemoRefHead, body: MemoBody, origin_slabref: &SlabRef, peerlist: MemoPeerList ) -> Result<(Memo,MemoRef,bool),IntegrityError>{
         // TODO: find a way to merge this with assert_memoref to avoid doing duplicative work with regard to peerlist application
 
+        if self.is_closed() {
+            return Err(IntegrityError::SlabClosed(self.id));
+        }
+
         let memo = Memo::new(MemoInner {
-            id:             memo_id,
+            id:             LazyMemoId::known(memo_id),
             owning_slab_id: self.id,
             subject_id:     subject_id,
             parents:        parents,
             body:           body
         });
 
-        let (memoref, had_memoref) = self.assert_memoref(memo.id, memo.subject_id, peerlist.clone(), Some(memo.clone()) );
+        // Peering, presence and request memos are addressed to their recipient, and their bodies are
+        // tailored accordingly ( Eg: the recipient is omitted from peerlists ) so we can only verify the rest
+        if memo.does_peering() && !memo.verify_id() {
+            println!("WARNING - Slab({}) rejected memo {:?} from Slab({}) - id does not match contents", self.id, memo_id, origin_slabref.slab_id );
+            return Err(IntegrityError::MemoIdMismatch(memo_id));
+        }
 
-        {
-            let mut counters = self.counters.write().unwrap();
-            counters.memos_received += 1;
-            if had_memoref {
-                counters.memos_redundantly_received += 1;
-            }
+        let was_resident = self.get_memoref(&memo_id).is_some_and(|m| m.is_resident() );
+        if !was_resident {
+            self.note_memo_arrived(memo_id, origin_slabref);
         }
-        //println!("Slab({}).reconstitute_memo({}) B -> {:?}", self.id, memo_id, memoref );
 
+        let (memoref, had_memoref) = self.assert_memoref(memo_id, memo.subject_id, peerlist, Some(memo.clone()) );
+        //println!("Slab({}).reconstitute_memo({}) B -> {:?}", self.id, memo_id, memoref );
 
-        self.consider_emit_memo(&memoref);
+        self.emit_received_memo(&memoref, origin_slabref, was_resident);
 
         if let Some(ref memo) = memoref.get_memo_if_resident() {
 
@@ -56,42 +142,51 @@ impl Slab {
 
         }
 
-        if let Some(ref tx_mutex) = self.memoref_dispatch_tx_channel {
-            tx_mutex.lock().unwrap().send(memoref.clone()).unwrap()
-        }
-
-        (memo, memoref, had_memoref)
+        Ok((memo, memoref, had_memoref))
+    }
+    /// These are persisted along with the subject id reservation, or on shutdown, rather than with every receipt
+    fn count_received_memos ( &self, received: u64, redundant: u64 ) {
+        let mut counters = self.counters.write().unwrap();
+        counters.memos_received += received;
+        counters.memos_redundantly_received += redundant;
     }
     pub fn residentize_memoref(&self, memoref: &MemoRef, memo: Memo) -> bool {
         //println!("# Slab({}).MemoRef({}).residentize()", self.id, memoref.id);
 
         assert!(memoref.owning_slab_id == self.id);
-        assert!( memoref.id == memo.id );
+        assert!( memoref.id() == memo.id() );
 
-        let mut ptr = memoref.ptr.write().unwrap();
+        let residentized = {
+            let mut ptr = memoref.ptr.write().unwrap();
 
-        if let MemoRefPtr::Remote = *ptr {
-            *ptr = MemoRefPtr::Resident( memo );
+            if let MemoRefPtr::Remote = *ptr {
+                *ptr = MemoRefPtr::Resident( memo );
+                true
+            }else{
+                false
+            }
+        };
+
+        if residentized {
+            self.track_resident_memo(memoref);
+            self.persist_memoref(memoref);
 
             // should this be using do_peering_for_memo?
             // doing it manually for now, because I think we might only want to do
             // a concise update to reflect our peering status change
 
-            let peering_memoref = self.new_memo(
-                None,
-                MemoRefHead::from_memoref(memoref.clone()),
-                MemoBody::Peering(
-                    memoref.id,
-                    memoref.subject_id,
-                    MemoPeerList::new(vec![ MemoPeer{
-                        slabref: self.my_ref.clone(),
-                        status: MemoPeeringStatus::Resident
-                    }])
-                )
+            let entry = (
+                memoref.id(),
+                memoref.subject_id,
+                MemoPeerList::new(vec![ MemoPeer{
+                    slabref: self.my_ref.clone(),
+                    status: MemoPeeringStatus::Resident
+                }])
             );
 
-            for peer in memoref.peerlist.read().unwrap().iter() {
-                peer.slabref.send( &self.my_ref, &peering_memoref );
+            let peers : Vec<SlabRef> = memoref.peerlist.read().unwrap().iter().map(|p| p.slabref.clone() ).collect();
+            for peer in peers.iter() {
+                self.send_peering( peer, Some(memoref), vec![entry.clone()] );
             }
 
             // residentized
@@ -125,59 +220,67 @@ impl Slab {
             }
         }
 
-        let peering_memoref = self.new_memo_basic(
-            None,
-            MemoRefHead::from_memoref(memoref.clone()),
-            MemoBody::Peering(
-                memoref.id,
-                memoref.subject_id,
-                MemoPeerList::new(vec![MemoPeer{
-                    slabref: self.my_ref.clone(),
-                    status: MemoPeeringStatus::Participating
-                }])
-            )
+        self.untrack_resident_memo(memoref.id());
+        self.persist_memoref(memoref);
+
+        let entry = (
+            memoref.id(),
+            memoref.subject_id,
+            MemoPeerList::new(vec![MemoPeer{
+                slabref: self.my_ref.clone(),
+                status: MemoPeeringStatus::Participating
+            }])
         );
 
         //self.consider_emit_memo(&memoref);
 
         for peer in send_peers.iter() {
-            peer.slabref.send( &self.my_ref, &peering_memoref );
+            self.send_peering( &peer.slabref, Some(memoref), vec![entry.clone()] );
         }
 
         Ok(())
     }
-    pub fn request_memo (&self, memoref: &MemoRef) -> u8 {
+    pub fn request_memo (&self, memoref: &MemoRef) -> usize {
         //println!("Slab({}).request_memo({})", self.id, memoref.id );
 
         let request_memo = self.new_memo_basic(
             None,
             MemoRefHead::new(), // TODO: how should this be parented?
             MemoBody::MemoRequest(
-                vec![memoref.id],
+                vec![memoref.id()],
                 self.my_ref.clone()
             )
         );
 
-        let mut sent = 0u8;
-        for peer in memoref.peerlist.read().unwrap().iter().take(5) {
-            //println!("Slab({}).request_memo({}) from {}", self.id, memoref.id, peer.slabref.slab_id );
-            peer.slabref.send( &self.my_ref, &request_memo.clone() );
-            sent += 1;
+        let candidates : Vec<SlabRef> = memoref.peerlist.read().unwrap().iter()
+            .filter(|p| p.status != MemoPeeringStatus::NonParticipating )
+            .map(|p| p.slabref.clone() )
+            .collect();
+
+        let mut asked = Vec::new();
+        for slabref in self.select_peers(candidates, SelectionPurpose::Retrieval).iter().take(self.config.request_fanout) {
+            //println!("Slab({}).request_memo({}) from {}", self.id, memoref.id, slabref.slab_id );
+            slabref.send( &self.my_ref, &request_memo.clone() );
+            asked.push(slabref.slab_id);
+        }
+
+        let sent = asked.len();
+        if sent > 0 {
+            self.note_memo_requested(memoref.id(), asked);
         }
 
         sent
     }
     pub fn assert_memoref( &self, memo_id: MemoId, subject_id: Option<SubjectId>, peerlist: MemoPeerList, memo: Option<Memo>) -> (MemoRef, bool) {
-
         let had_memoref;
         let memoref = match self.memorefs_by_id.write().unwrap().entry(memo_id) {
             Entry::Vacant(o)   => {
                 let mr = MemoRef(Arc::new(
                     MemoRefInner {
-                        id: memo_id,
+                        id: LazyMemoId::known(memo_id),
                         owning_slab_id: self.id,
                         subject_id: subject_id,
-                        peerlist: RwLock::new(peerlist),
+                        peerlist: RwLock::new(MemoPeerList::new(Vec::new())),
                         ptr:      RwLock::new(match memo {
                             Some(m) => {
                                 assert!(self.id == m.owning_slab_id);
@@ -189,6 +292,10 @@ impl Slab {
                 ));
 
                 had_memoref = false;
+                // Peerlists received from others may be unbounded, and mention NonParticipating peers
+                if let Err(finding) = mr.apply_peers( &peerlist, self.config.peerlist_limit ) {
+                    self.note_integrity_finding(finding);
+                }
                 o.insert( mr ).clone()// TODO: figure out how to prolong the borrow here & avoid clone
             }
             Entry::Occupied(o) => {
@@ -201,13 +308,37 @@ impl Slab {
                         *ptr = MemoRefPtr::Resident(m)
                     }
                 }
-                mr.apply_peers( &peerlist );
+                if let Err(finding) = mr.apply_peers( &peerlist, self.config.peerlist_limit ) {
+                    self.note_integrity_finding(finding);
+                }
                 mr.clone()
             }
         };
 
+        self.track_resident_memo(&memoref);
+        self.persist_memoref(&memoref);
+
         (memoref, had_memoref)
     }
+    /// Called once the id of a memo created by this slab has been calculated, such that it may be found by id
+    pub (super) fn register_hashed_memoref (&self, id: &LazyMemoId, memo_id: MemoId) {
+        let memoref = self.unhashed_memorefs.lock().unwrap().remove(&id.key());
+
+        if let Some(memoref) = memoref {
+            self.memorefs_by_id.write().unwrap().entry(memo_id).or_insert_with(|| memoref.clone() );
+            self.track_hashed_memo(&memoref);
+        }
+    }
+    /// Look up a memoref by id. Memos which have yet to be hashed cannot be found this way
+    pub (super) fn get_memoref (&self, memo_id: &MemoId) -> Option<MemoRef> {
+        self.memorefs_by_id.read().unwrap().get(memo_id).cloned()
+    }
+    /// All memorefs held by this slab, including those which have yet to be hashed
+    pub (super) fn all_memorefs (&self) -> Vec<MemoRef> {
+        let mut memorefs : Vec<MemoRef> = self.memorefs_by_id.read().unwrap().values().cloned().collect();
+        memorefs.extend(self.unhashed_memorefs.lock().unwrap().values().cloned());
+        memorefs
+    }
     pub fn assert_slabref(&self, slab_id: SlabId, presence: &[SlabPresence] ) -> SlabRef {
         //println!("# Slab({}).assert_slabref({}, {:?})", self.id, slab_id, presence );
 
@@ -251,6 +382,11 @@ impl Slab {
         for p in presence.iter(){
             assert!(slab_id == p.slab_id, "presence slab_id does not match the provided slab_id");
 
+            if let Some(conflict) = slabref.conflicting_presence(p) {
+                self.report_slab_id_conflict(conflict);
+                continue;
+            }
+
             let mut _maybe_slab = None;
             let args = if p.address.is_local() {
                 // playing silly games with borrow lifetimes.
@@ -281,4 +417,13 @@ impl Slab {
         return slabref;
 
     }
+    /// Record that some other slab has claimed an id which is already spoken for. The claim is not applied
+    pub fn report_slab_id_conflict(&self, conflict: SlabIdConflict) {
+        println!("WARNING - Slab({}) detected a conflicting claim for slab id {} from {:?}", self.id, conflict.slab_id, conflict.claimed_address );
+        self.slab_id_conflicts.lock().unwrap().push(conflict);
+    }
+    /// Slab id conflicts detected since the last call
+    pub fn take_slab_id_conflicts(&self) -> Vec<SlabIdConflict> {
+        mem::take(&mut *self.slab_id_conflicts.lock().unwrap())
+    }
 }
@@ -13,7 +13,7 @@ impl fmt::Debug for Slab {
 impl Slab {
     // Counters,stats, reporting
     pub fn count_of_memorefs_resident( &self ) -> u32 {
-        self.memorefs_by_id.read().unwrap().len() as u32
+        (self.memorefs_by_id.read().unwrap().len() + self.unhashed_memorefs.lock().unwrap().len()) as u32
     }
     pub fn count_of_memos_received( &self ) -> u64 {
         self.counters.read().unwrap().memos_received as u64
@@ -0,0 +1,311 @@
+/*
+    Memoref dispatch: Newly received memorefs are conveyed to the contexts subscribed to their subjects.
+    Memorefs are dispatched in per-subject batches, such that a batch of memos received together results
+    in a single subject head application for each subject, rather than one for each memo.
+
+    Applying a subject head may entail retrieving memos from remote slabs, so this happens on a pool of
+    worker threads rather than on the thread which received the memo. Batches are queued per subject, and
+    any idle worker takes up the next subject which is ready. A subject is only dispatched by one worker
+    at a time, which preserves the order of dispatch within a subject, while a slow subject delays no
+    other subject so long as there is a worker to spare.
+
+    The number of batches awaiting dispatch is bounded. Once the bound is reached, whoever is dispatching
+    waits for a worker to take up a batch, so a stalled subject holds up the receipt of further memos
+    rather than letting them accumulate without limit. Workers themselves never wait, as they may be the
+    ones to make room.
+*/
+
+use super::*;
+use std::collections::{HashSet,VecDeque};
+
+pub struct DispatchPool {
+    size:    usize,
+    /// The most batches which may await dispatch at once
+    limit:   usize,
+    /// The queues, a condvar signalled when a subject is ready, and another when a batch is taken up
+    queues:  Arc<(Mutex<DispatchQueues>,Condvar,Condvar)>,
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
+}
+
+struct DispatchQueues {
+    /// Batches awaiting dispatch, by subject
+    pending: HashMap<SubjectId,VecDeque<Vec<MemoRef>>>,
+    /// Subjects with pending batches which no worker is dispatching, in the order they became ready
+    ready:   VecDeque<SubjectId>,
+    /// Subjects presently being dispatched by a worker
+    active:  HashSet<SubjectId>,
+    /// The number of batches awaiting dispatch
+    depth:   usize,
+    /// False once the pool has been shut down
+    open:    bool,
+}
+
+impl DispatchPool {
+    pub fn new (size: usize, limit: usize) -> Self {
+        DispatchPool {
+            size: size.max(1),
+            limit: limit.max(1),
+            queues: Arc::new((Mutex::new(DispatchQueues{
+                pending: HashMap::new(),
+                ready:   VecDeque::new(),
+                active:  HashSet::new(),
+                depth:   0,
+                open:    true,
+            }), Condvar::new(), Condvar::new())),
+            threads: Mutex::new(Vec::new()),
+        }
+    }
+    /// Start the workers, which pass each batch to the given handler
+    pub fn start<F> (&self, handler: F) where F: Fn(SubjectId,Vec<MemoRef>) + Send + Sync + 'static {
+        let handler = Arc::new(handler);
+        let mut threads = self.threads.lock().unwrap();
+
+        for _ in 0..self.size {
+            let queues = self.queues.clone();
+            let handler = handler.clone();
+
+            threads.push(thread::spawn(move || {
+                let (ref lock, ref condvar, ref space) = *queues;
+
+                while let Some((subject_id, memorefs)) = Self::take_ready(lock, condvar, space) {
+                    handler(subject_id, memorefs);
+
+                    let mut queues = lock.lock().unwrap();
+                    queues.active.remove(&subject_id);
+
+                    // Go to the back of the line if there's more for this subject, such that others get their turn
+                    if queues.pending.contains_key(&subject_id) {
+                        queues.ready.push_back(subject_id);
+                        condvar.notify_one();
+                    }
+                }
+            }));
+        }
+    }
+    /// Wait for a subject which is ready, and claim its next batch. None once the pool is shut down and drained
+    fn take_ready (lock: &Mutex<DispatchQueues>, condvar: &Condvar, space: &Condvar) -> Option<(SubjectId,Vec<MemoRef>)> {
+        let mut queues = lock.lock().unwrap();
+        loop {
+            if let Some(subject_id) = queues.ready.pop_front() {
+                let memorefs = match queues.pending.entry(subject_id) {
+                    Entry::Occupied(mut o) => {
+                        let memorefs = o.get_mut().pop_front().unwrap_or_default();
+                        if o.get().is_empty() {
+                            o.remove();
+                        }
+                        memorefs
+                    },
+                    Entry::Vacant(_) => Vec::new()
+                };
+                queues.active.insert(subject_id);
+                queues.depth -= 1;
+                space.notify_one();
+                return Some((subject_id, memorefs));
+            }
+            // Anything which becomes ready hereafter is taken up by the worker which readied it
+            if !queues.open {
+                return None;
+            }
+            queues = condvar.wait(queues).unwrap();
+        }
+    }
+    /// Queue memorefs of the given subject for dispatch after any others of that subject. If the queue is full,
+    /// wait for a worker to take up a batch, unless we are one of the workers
+    pub fn dispatch (&self, subject_id: SubjectId, memorefs: Vec<MemoRef>) {
+        if memorefs.is_empty() {
+            return;
+        }
+
+        let on_worker = self.threads.lock().unwrap().iter().any(|t| t.thread().id() == thread::current().id() );
+
+        let (ref lock, ref condvar, ref space) = *self.queues;
+        let mut queues = lock.lock().unwrap();
+        while queues.open && queues.depth >= self.limit && !on_worker {
+            queues = space.wait(queues).unwrap();
+        }
+        if !queues.open {
+            return;
+        }
+
+        let newly_pending = !queues.pending.contains_key(&subject_id);
+        queues.pending.entry(subject_id).or_insert_with(VecDeque::new).push_back(memorefs);
+        queues.depth += 1;
+
+        // A subject already being dispatched is made ready again by its worker once that batch is done
+        if newly_pending && !queues.active.contains(&subject_id) {
+            queues.ready.push_back(subject_id);
+            condvar.notify_one();
+        }
+    }
+    /// The number of batches awaiting dispatch
+    pub fn queue_depth (&self) -> usize {
+        self.queues.0.lock().unwrap().depth
+    }
+    /// Stop accepting memorefs, and wait for the workers to drain the queues
+    pub fn shutdown (&self) {
+        {
+            let (ref lock, ref condvar, ref space) = *self.queues;
+            lock.lock().unwrap().open = false;
+            condvar.notify_all();
+            space.notify_all();
+        }
+
+        for t in self.threads.lock().unwrap().drain(..) {
+            // The last reference to the slab may have been released by one of the workers
+            if t.thread().id() != thread::current().id() {
+                t.join().expect("join memoref dispatch thread");
+            }
+        }
+    }
+}
+
+impl Slab {
+    /// The number of memoref batches awaiting dispatch to subscribed contexts
+    pub fn dispatch_queue_depth (&self) -> usize {
+        self.dispatch_pool.queue_depth()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::{Arc,Mutex};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use crate::{Network, Slab, Value};
+    use crate::slab::{MemoBody, MemoId, MemoRef};
+    use super::DispatchPool;
+
+    #[test]
+    fn dispatch_preserves_subject_order_without_blocking_other_subjects() {
+        let net = Network::create_new_system();
+        let slab = Slab::new(&net);
+
+        let memoref = |n: u32| {
+            let mut values = HashMap::new();
+            values.insert("n".to_string(), Value::from(n.to_string()));
+            slab.new_memo_basic_noparent(None, MemoBody::Edit(values))
+        };
+        let (a, b, c, x, y) = (memoref(1), memoref(2), memoref(3), memoref(4), memoref(5));
+
+        let dispatched : Arc<Mutex<Vec<(u64,MemoId)>>> = Arc::new(Mutex::new(Vec::new()));
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let (held_tx, held_rx) = mpsc::channel::<()>();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+
+        // Two workers, and two subjects which a partition by subject id would have assigned to the same one
+        let pool = DispatchPool::new(2, 16);
+        {
+            let dispatched = dispatched.clone();
+            let gate_rx = Mutex::new(gate_rx);
+            let held_tx = Mutex::new(held_tx);
+            let done_tx = Mutex::new(done_tx);
+            let a = a.clone();
+
+            pool.start(move |subject_id, memorefs: Vec<MemoRef>| {
+                dispatched.lock().unwrap().push((subject_id, memorefs[0].id()));
+
+                // Subject 1 is held up on its first batch until we say so
+                if memorefs[0] == a {
+                    held_tx.lock().unwrap().send(()).unwrap();
+                    gate_rx.lock().unwrap().recv().unwrap();
+                }
+                done_tx.lock().unwrap().send(()).unwrap();
+            });
+        }
+
+        pool.dispatch(1, vec![a.clone()]);
+        pool.dispatch(1, vec![b.clone()]);
+        pool.dispatch(1, vec![c.clone()]);
+        pool.dispatch(3, vec![x.clone()]);
+        pool.dispatch(3, vec![y.clone()]);
+
+        // Subject 3 is dispatched in full while subject 1 is held up, and subject 1 goes no further meanwhile
+        held_rx.recv().unwrap();
+        done_rx.recv().unwrap();
+        done_rx.recv().unwrap();
+        {
+            let mut dispatched = dispatched.lock().unwrap().clone();
+            dispatched.sort_by_key(|d| d.0);
+            assert_eq!(dispatched, vec![(1, a.id()), (3, x.id()), (3, y.id())]);
+        }
+        assert_eq!(pool.queue_depth(), 2);
+
+        gate_tx.send(()).unwrap();
+        for _ in 0..3 {
+            done_rx.recv().unwrap();
+        }
+
+        let dispatched = dispatched.lock().unwrap();
+        let subject_1 : Vec<MemoId> = dispatched.iter().filter(|d| d.0 == 1).map(|d| d.1).collect();
+        assert_eq!(subject_1, vec![a.id(), b.id(), c.id()]);
+        assert_eq!(pool.queue_depth(), 0);
+
+        pool.shutdown();
+    }
+
+    #[test]
+    fn dispatch_waits_for_room_once_the_queue_is_full() {
+        let net = Network::create_new_system();
+        let slab = Slab::new(&net);
+
+        let memoref = |n: u32| {
+            let mut values = HashMap::new();
+            values.insert("n".to_string(), Value::from(n.to_string()));
+            slab.new_memo_basic_noparent(None, MemoBody::Edit(values))
+        };
+        let memorefs : Vec<MemoRef> = (0..6).map(memoref).collect();
+
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let (held_tx, held_rx) = mpsc::channel::<()>();
+
+        // One worker, held up on every batch until we say so, and room for two batches behind it
+        let pool = Arc::new(DispatchPool::new(1, 2));
+        {
+            let gate_rx = Mutex::new(gate_rx);
+            let held_tx = Mutex::new(held_tx);
+            pool.start(move |_subject_id, _memorefs: Vec<MemoRef>| {
+                held_tx.lock().unwrap().send(()).unwrap();
+                gate_rx.lock().unwrap().recv().unwrap();
+            });
+        }
+
+        let (queued_tx, queued_rx) = mpsc::channel::<usize>();
+        let dispatcher = {
+            let pool = pool.clone();
+            let memorefs = memorefs.clone();
+            thread::spawn(move || {
+                for (i, memoref) in memorefs.into_iter().enumerate() {
+                    pool.dispatch(i as u64 % 3, vec![memoref]);
+                    queued_tx.send(i).unwrap();
+                }
+            })
+        };
+
+        // The worker takes up the first batch, two more fill the queue, and the fourth must wait
+        held_rx.recv().unwrap();
+        for i in 0..3 {
+            assert_eq!(queued_rx.recv().unwrap(), i);
+        }
+        assert!(queued_rx.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(pool.queue_depth(), 2);
+
+        // Each batch taken up makes room for one more, and the queue never exceeds its bound
+        for i in 3..6 {
+            gate_tx.send(()).unwrap();
+            held_rx.recv().unwrap();
+            assert_eq!(queued_rx.recv().unwrap(), i);
+            assert!(pool.queue_depth() <= 2);
+        }
+        dispatcher.join().unwrap();
+
+        for _ in 0..2 {
+            gate_tx.send(()).unwrap();
+            held_rx.recv().unwrap();
+        }
+        gate_tx.send(()).unwrap();
+        pool.shutdown();
+        assert_eq!(pool.queue_depth(), 0);
+    }
+}
@@ -0,0 +1,103 @@
+/*
+    Durability Score:  An estimate of how well protected a memo is against loss, derived from its MemoPeerList.
+                       Each Resident peer contributes according to the anticipated lifetime of that slab.
+                       Participating peers know of the memo, and will likely hold it again, so they contribute half as much.
+
+    Durability Target: The score which we want a given memo to reach. Slabs will push replicas of resident memos
+                       to additional peers until the target is met.
+*/
+
+use super::*;
+
+pub type DurabilityScore = u32;
+
+/// Durability targets for a slab. Targets may be set for individual memos, or for all memos of a given subject.
+/// The most specific target applies.
+pub struct DurabilityTargets {
+    /// Applies to memos which do peering, absent a more specific target
+    pub default: DurabilityScore,
+    by_subject: HashMap<SubjectId,DurabilityScore>,
+    by_memo:    HashMap<MemoId,DurabilityScore>,
+}
+
+impl DurabilityTargets {
+    pub fn new (default: DurabilityScore) -> Self {
+        DurabilityTargets {
+            default,
+            by_subject: HashMap::new(),
+            by_memo:    HashMap::new(),
+        }
+    }
+}
+
+impl SlabAnticipatedLifetime {
+    /// The durability score contributed by a Resident peer with this anticipated lifetime
+    pub fn durability_weight (&self) -> DurabilityScore {
+        match *self {
+            SlabAnticipatedLifetime::Ephmeral => 1,
+            SlabAnticipatedLifetime::Session  => 2,
+            SlabAnticipatedLifetime::Unknown  => 2,
+            SlabAnticipatedLifetime::Long     => 4,
+            SlabAnticipatedLifetime::VeryLong => 8,
+        }
+    }
+}
+
+impl MemoPeer {
+    pub fn durability_score (&self) -> DurabilityScore {
+        let weight = self.slabref.get_anticipated_lifetime().durability_weight();
+        match self.status {
+            MemoPeeringStatus::Resident      => weight,
+            MemoPeeringStatus::Participating => weight / 2,
+            _                                => 0
+        }
+    }
+}
+
+impl Slab {
+    /// Score the durability of a memo based on its present peerlist. Our own copy is not counted.
+    pub fn memo_durability_score (&self, memoref: &MemoRef) -> DurabilityScore {
+        memoref.peerlist.read().unwrap().iter().map(|peer| peer.durability_score() ).sum()
+    }
+    /// Determine the durability target for a given memo
+    pub fn memo_durability_target (&self, memo: &Memo) -> DurabilityScore {
+        if !memo.does_peering() {
+            // This is necessary to prevent memo routing loops for now, as
+            // memoref.is_peered_with_slabref() obviously doesn't work for non-peered memos
+            // something here should change when we switch to gossip/plumtree, but
+            // I'm not sufficiently clear on that at the time of this writing
+            return 0;
+        }
+
+        let targets = self.durability_targets.read().unwrap();
+
+        // Nobody can have set a target for a memo whose id is yet to be calculated
+        if let Some(target) = memo.id.get().and_then(|memo_id| targets.by_memo.get(&memo_id) ) {
+            return *target;
+        }
+        if let Some(subject_id) = memo.subject_id {
+            if let Some(target) = targets.by_subject.get(&subject_id) {
+                return *target;
+            }
+        }
+
+        targets.default
+    }
+    /// Returns true if the memo has met its durability target, according to what we know of its peers
+    pub fn memo_is_durable (&self, memoref: &MemoRef) -> bool {
+        match memoref.get_memo_if_resident() {
+            Some(memo) => self.memo_durability_score(memoref) >= self.memo_durability_target(&memo),
+            // It's somebody else's job to keep it around
+            None       => true
+        }
+    }
+    pub fn set_default_durability_target (&self, target: DurabilityScore) {
+        self.durability_targets.write().unwrap().default = target;
+    }
+    pub fn set_subject_durability_target (&self, subject_id: SubjectId, target: DurabilityScore) {
+        self.durability_targets.write().unwrap().by_subject.insert(subject_id, target);
+    }
+    pub fn set_memo_durability_target (&self, memo_id: MemoId, target: DurabilityScore) {
+        self.durability_targets.write().unwrap().by_memo.insert(memo_id, target);
+    }
+}
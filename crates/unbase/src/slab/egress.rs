@@ -1,22 +1,160 @@
 use super::*;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// How a slab disseminates the memos it creates and receives to its peers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmissionStrategy {
+    /// Send each memo to as many peers as are necessary to meet its durability target
+    Direct,
+    /// Disseminate memos to all slabs through an epidemic broadcast tree. See plumtree.rs
+    Plumtree,
+}
+
+impl FromStr for EmissionStrategy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "Direct"   | "direct"   => Ok(EmissionStrategy::Direct),
+            "Plumtree" | "plumtree" => Ok(EmissionStrategy::Plumtree),
+            _                       => Err(())
+        }
+    }
+}
 
 impl Slab {
+    /// Emit a memo which was created by this slab
+    pub (super) fn emit_new_memo(&self, memoref: &MemoRef) {
+        match self.config.emission_strategy {
+            EmissionStrategy::Direct   => self.consider_emit_memo(memoref),
+            EmissionStrategy::Plumtree => self.plumtree_push(memoref, None),
+        }
+    }
+    /// Emit a memo which we received from another slab. `was_resident` indicates whether we already had it
+    pub (super) fn emit_received_memo(&self, memoref: &MemoRef, origin_slabref: &SlabRef, was_resident: bool) {
+        match self.config.emission_strategy {
+            EmissionStrategy::Direct   => self.consider_emit_memo(memoref),
+            EmissionStrategy::Plumtree => self.plumtree_receive(memoref, origin_slabref, was_resident),
+        }
+    }
     pub fn consider_emit_memo(&self, memoref: &MemoRef) {
-        // Emit memos for durability and notification purposes
+        // Emit memos for durability purposes, regardless of the emission strategy
         // At present, some memos like peering and slab presence are emitted manually.
-        // TODO: This will almost certainly have to change once gossip/plumtree functionality is added
 
-        // TODO: test each memo for durability_score and emit accordingly
         if let Some(memo) = memoref.get_memo_if_resident() {
-            let needs_peers = self.check_peering_target(&memo);
+            let target = self.memo_durability_target(&memo);
+            self.emit_memo_toward_target(memoref, target);
+        }
+    }
+    /// Send the memo to peers which aren't already peered with it, until the projected durability score meets the target.
+    /// Returns the peers it was sent to
+    fn emit_memo_toward_target(&self, memoref: &MemoRef, target: DurabilityScore) -> Vec<SlabRef> {
+        let mut projected_score = self.memo_durability_score(memoref);
+        let mut sent = Vec::new();
+
+        // The peerlist retains no more than peerlist_limit peers. Sending the memo to more would only see them pruned
+        // from it, whereupon they would no longer count toward the target, and be sent the memo all over again
+        let mut room = self.config.peerlist_limit.saturating_sub(memoref.peerlist.read().unwrap().len());
 
-            //println!("Slab({}).consider_emit_memo {} - A ({:?})", self.id, memoref.id, &*self.peer_refs.read().unwrap() );
-            for peer_ref in self.peer_refs.read().unwrap().iter().filter(|x| !memoref.is_peered_with_slabref(x) ).take( needs_peers as usize ) {
+        //println!("Slab({}).consider_emit_memo {} - A ({:?})", self.id, memoref.id, &*self.peer_refs.read().unwrap() );
+        let candidates : Vec<SlabRef> = self.peer_refs.read().unwrap().iter().filter(|x| !memoref.is_peered_with_slabref(x) ).cloned().collect();
 
-                //println!("# Slab({}).emit_memos - EMIT Memo {} to Slab {}", self.id, memo.id, peer_ref.slab_id );
-                peer_ref.send( &self.my_ref, memoref );
+        for peer_ref in self.select_peers(candidates, SelectionPurpose::Replication).into_iter() {
+            if projected_score >= target || room == 0 {
+                break;
             }
+            room -= 1;
+
+            //println!("# Slab({}).emit_memos - EMIT Memo {} to Slab {}", self.id, memo.id, peer_ref.slab_id );
+            peer_ref.send( &self.my_ref, memoref );
+
+            // Assume they'll keep it. We'll find out for sure when they send us a peering memo
+            projected_score += peer_ref.get_anticipated_lifetime().durability_weight();
+            sent.push(peer_ref);
         }
+
+        sent
+    }
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
     }
+    /// Gracefully leave the network: Hand off any resident memos which would be under-replicated
+    /// in our absence, and wait for the recipients to acknowledge them. Then tell our peers that we're leaving,
+    /// such that they stop counting on us, and close the slab.
+    ///
+    /// A closed slab rejects any memos sent to it, and no longer emits memos of its own. Returns false if
+    /// some of the handoffs went unacknowledged within the handoff timeout
+    pub fn shutdown(&self) -> bool {
+        let resident : Vec<MemoRef> = self.all_memorefs().into_iter()
+            .filter(|m| m.is_resident() )
+            .collect();
+
+        let mut handoffs : Vec<(MemoRef,Vec<SlabRef>)> = Vec::new();
+
+        for memoref in resident.iter() {
+            if let Some(memo) = memoref.get_memo_if_resident() {
+                if !memo.does_peering() {
+                    continue;
+                }
+
+                let mut target = self.memo_durability_target(&memo);
 
+                // Whatever the target, at least one other slab had better have it once we're gone
+                let has_resident_peer = memoref.peerlist.read().unwrap().iter().any(|p| p.status == MemoPeeringStatus::Resident );
+                if !has_resident_peer && target == 0 {
+                    target = 1;
+                }
+
+                let recipients = self.emit_memo_toward_target(memoref, target);
+                if !recipients.is_empty() {
+                    handoffs.push((memoref.clone(), recipients));
+                }
+            }
+        }
+
+        let acknowledged = self.await_handoff(&handoffs);
+
+        // Peering updates still waiting out their window must precede the goodbye
+        self.flush_peering();
+
+        let goodbye_memoref = self.new_memo_basic_noparent(None, MemoBody::Goodbye(self.id));
+
+        for peer_ref in self.peer_refs.read().unwrap().iter() {
+            peer_ref.send( &self.my_ref, &goodbye_memoref );
+        }
+
+        self.closed.store(true, Ordering::SeqCst);
+        self.dispatch_pool.shutdown();
+        self.stop_graft_timer();
+        self.net.deregister_local_slab(self.id);
+
+        self.persist_counters();
+        if let Err(e) = self.storage.flush() {
+            println!("WARNING - Slab({}) failed to flush storage: {:?}", self.id, e );
+        }
+
+        acknowledged
+    }
+    /// Wait for each recipient of a handoff to tell us that the memo is resident with them.
+    /// Returns false if they didn't all do so within the handoff timeout
+    fn await_handoff(&self, handoffs: &[(MemoRef,Vec<SlabRef>)]) -> bool {
+        let deadline = Instant::now() + self.config.handoff_timeout();
+        let mut lock = self.peering_received_lock.lock().unwrap();
+
+        loop {
+            let acknowledged = handoffs.iter().all(|(memoref, recipients)| {
+                let peerlist = memoref.peerlist.read().unwrap();
+                recipients.iter().all(|r| peerlist.iter().any(|p| p.slabref.slab_id == r.slab_id && p.status == MemoPeeringStatus::Resident ) )
+            });
+            if acknowledged {
+                return true;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            lock = self.peering_received.wait_timeout(lock, deadline - now).unwrap().0;
+        }
+    }
 }
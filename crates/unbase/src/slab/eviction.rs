@@ -0,0 +1,259 @@
+/*
+    Memory budgeting: The slab keeps track of the approximate size of its resident memos.
+    Once the configured budget is exceeded, memos are remotized in the order selected by the
+    eviction policy, until we're back under budget or run out of eligible memos.
+
+    A memo is only eligible for eviction if:
+        * It's not pinned by the head of a resident subject, context, or the root index seed
+        * It's been replicated to at least one other slab which holds it as Resident
+        * It has met its durability target
+*/
+
+use super::*;
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EvictionPolicy {
+    LeastRecentlyUsed,
+    LeastFrequentlyUsed,
+}
+
+/// Memos which may not be evicted
+pub struct PinnedMemos {
+    pub memo_ids:    HashSet<MemoId>,
+    /// Subjects whose heads we were unable to determine. None of their memos may be evicted
+    pub subject_ids: HashSet<SubjectId>,
+    /// The heads from which the above were gathered
+    pub heads:       Vec<MemoRefHead>,
+}
+
+pub struct ResidencyTracker {
+    /// Approximate number of bytes of resident memos to retain. None for unlimited
+    pub budget: Option<usize>,
+    pub policy: EvictionPolicy,
+    resident_bytes: usize,
+    clock: u64,
+    entries: HashMap<MemoId, ResidencyEntry>,
+}
+
+struct ResidencyEntry {
+    size: usize,
+    last_access: u64,
+    access_count: u64,
+}
+
+impl Default for PinnedMemos {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl PinnedMemos {
+    pub fn new () -> Self {
+        PinnedMemos {
+            memo_ids:    HashSet::new(),
+            subject_ids: HashSet::new(),
+            heads:       Vec::new(),
+        }
+    }
+    pub fn add_head (&mut self, head: &MemoRefHead) {
+        for memoref in head.iter() {
+            if let Some(memo_id) = memoref.id_if_calculated() {
+                self.memo_ids.insert(memo_id);
+            }
+        }
+        self.heads.push(head.clone());
+    }
+    pub fn contains (&self, memoref: &MemoRef) -> bool {
+        // Memos which are yet to be hashed have never been sent anywhere, and must be retained regardless
+        match memoref.id_if_calculated() {
+            Some(memo_id) => if self.memo_ids.contains(&memo_id) {
+                return true;
+            },
+            None => return true
+        }
+        match memoref.subject_id {
+            Some(subject_id) => self.subject_ids.contains(&subject_id),
+            None             => false
+        }
+    }
+}
+
+impl ResidencyTracker {
+    pub fn new (budget: Option<usize>, policy: EvictionPolicy) -> Self {
+        ResidencyTracker {
+            budget,
+            policy,
+            resident_bytes: 0,
+            clock: 0,
+            entries: HashMap::new(),
+        }
+    }
+    fn track (&mut self, memo: &Memo) {
+        self.clock += 1;
+        let clock = self.clock;
+
+        let memo_id = match memo.id.get() {
+            Some(memo_id) => memo_id,
+            None          => {
+                // Accounted for, but not eligible for eviction until it's been hashed. See track_hashed
+                self.resident_bytes += memo.approximate_size();
+                return;
+            }
+        };
+
+        if let Entry::Vacant(e) = self.entries.entry(memo_id) {
+            let size = memo.approximate_size();
+            self.resident_bytes += size;
+            e.insert(ResidencyEntry{
+                size,
+                last_access: clock,
+                access_count: 1,
+            });
+        }
+    }
+    /// Track a memo by id, which was previously tracked while it was yet to be hashed
+    fn track_hashed (&mut self, memo: &Memo) {
+        self.resident_bytes -= memo.approximate_size();
+        self.track(memo);
+    }
+
+    fn untrack (&mut self, memo_id: MemoId) {
+        if let Some(entry) = self.entries.remove(&memo_id) {
+            self.resident_bytes -= entry.size;
+        }
+    }
+    fn touch (&mut self, memo_id: MemoId) {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(&memo_id) {
+            entry.last_access = self.clock;
+            entry.access_count += 1;
+        }
+    }
+    fn over_budget (&self) -> bool {
+        match self.budget {
+            Some(budget) => self.resident_bytes > budget,
+            None         => false
+        }
+    }
+    /// Resident memo ids, in the order in which they should be evicted
+    fn eviction_order (&self) -> Vec<MemoId> {
+        let mut candidates : Vec<(MemoId, u64, u64)> = self.entries.iter()
+            .map(|(memo_id, entry)| (*memo_id, entry.access_count, entry.last_access))
+            .collect();
+
+        match self.policy {
+            EvictionPolicy::LeastRecentlyUsed   => candidates.sort_by_key(|c| c.2),
+            EvictionPolicy::LeastFrequentlyUsed => candidates.sort_by_key(|c| (c.1, c.2)),
+        }
+
+        candidates.into_iter().map(|c| c.0).collect()
+    }
+}
+
+impl Slab {
+    pub fn set_memory_budget (&self, budget: Option<usize>) {
+        self.residency.lock().unwrap().budget = budget;
+    }
+    pub fn set_eviction_policy (&self, policy: EvictionPolicy) {
+        self.residency.lock().unwrap().policy = policy;
+    }
+    /// Approximate number of bytes presently occupied by resident memos
+    pub fn resident_memory_usage (&self) -> usize {
+        self.residency.lock().unwrap().resident_bytes
+    }
+    pub (super) fn track_resident_memo (&self, memoref: &MemoRef) {
+        if let Some(memo) = memoref.get_memo_if_resident() {
+            self.residency.lock().unwrap().track(&memo);
+        }
+    }
+    pub (super) fn track_hashed_memo (&self, memoref: &MemoRef) {
+        if let Some(memo) = memoref.get_memo_if_resident() {
+            self.residency.lock().unwrap().track_hashed(&memo);
+        }
+    }
+    pub (super) fn untrack_resident_memo (&self, memo_id: MemoId) {
+        self.residency.lock().unwrap().untrack(memo_id);
+    }
+    /// Record an access to a resident memo, for the benefit of the eviction policy
+    pub fn touch_memo (&self, memoref: &MemoRef) {
+        // Memos which are yet to be hashed have never been sent anywhere, so they can't be evicted anyway
+        if let Some(memo_id) = memoref.id_if_calculated() {
+            self.residency.lock().unwrap().touch(memo_id);
+        }
+    }
+    pub (super) fn conditionally_evict_memos (&self) {
+        if self.residency.lock().unwrap().over_budget() {
+            self.evict_memos();
+        }
+    }
+    /// Remotize eligible memos until we are within the memory budget. Returns the number of memos remotized
+    pub fn evict_memos (&self) -> usize {
+        // Remotizing a memo issues a peering memo, which may land us right back here
+        if self.evicting.swap(true, Ordering::SeqCst) {
+            return 0;
+        }
+
+        let evicted = match self.pinned_memos() {
+            Some(pinned) => self.evict_unpinned_memos(&pinned),
+            None         => 0  // Unable to determine what's pinned. Try again next time
+        };
+
+        self.evicting.store(false, Ordering::SeqCst);
+        evicted
+    }
+    fn evict_unpinned_memos (&self, pinned: &PinnedMemos) -> usize {
+        let order = self.residency.lock().unwrap().eviction_order();
+
+        let mut evicted = 0;
+        for memo_id in order {
+            if !self.residency.lock().unwrap().over_budget() {
+                break;
+            }
+
+            let memoref = match self.memorefs_by_id.read().unwrap().get(&memo_id) {
+                Some(memoref) => memoref.clone(),
+                None          => continue
+            };
+
+            if pinned.contains(&memoref) || !self.memo_is_safely_peered(&memoref) {
+                continue;
+            }
+
+            if self.remotize_memoref(&memoref).is_ok() {
+                evicted += 1;
+            }
+        }
+
+        evicted
+    }
+    fn memo_is_safely_peered (&self, memoref: &MemoRef) -> bool {
+        let has_resident_peer = memoref.peerlist.read().unwrap().iter().any(|p| p.status == MemoPeeringStatus::Resident );
+        has_resident_peer && self.memo_is_durable(memoref)
+    }
+    /// Gather the heads of all subscribed contexts and resident subjects, plus the root index seed
+    pub (super) fn pinned_memos (&self) -> Option<PinnedMemos> {
+        let mut pinned = PinnedMemos::new();
+
+        if let Some(seed) = self.get_root_index_seed() {
+            pinned.add_head(&seed);
+        }
+
+        let mut contexts : Vec<Context> = Vec::new();
+        for weakcontext in self.subject_subscriptions.read().unwrap().values().flat_map(|s| s.iter() ) {
+            if let Some(context) = weakcontext.upgrade() {
+                if contexts.iter().all(|c| c.cmp(&context) ) {
+                    contexts.push(context);
+                }
+            }
+        }
+
+        for context in contexts.iter() {
+            if !context.collect_pinned_memos(&mut pinned) {
+                return None;
+            }
+        }
+
+        Some(pinned)
+    }
+}
@@ -0,0 +1,179 @@
+/*
+    Garbage collection: Projection stops at the first FullyMaterialized memo it encounters, so once a
+    materialized memo exists in a head, its ancestors are never needed to project that head.
+
+    A GC pass walks every known head ( those of subscribed contexts and resident subjects, the root index
+    seed, and the relation heads of the memos it encounters ) stopping at materialized memos. Anything
+    reached is live. Ancestors of those materialized memos which are not live are collected, provided that:
+        * They are resident. Remote memorefs have no body to speak of
+        * They have met their durability target
+        * The heads of their subject were all determinable
+
+    Walking the heads calculates the ids of the memos encountered, as collection is recorded by id.
+    Collected memos are removed from memorefs_by_id and storage, and our peers are told that we no longer
+    have them. We retain only the ids of their parents, such that a stale memoref may still be found to
+    be descended by the materialized memo which superseded it, and such that later passes may reach
+    ancestors which were kept back ( eg: for want of durability ) once they become collectable.
+*/
+
+use super::*;
+use std::collections::HashSet;
+
+impl Slab {
+    /// Collect memos which are superseded by materialized memos in every known head. Returns the number collected
+    pub fn collect_garbage (&self) -> usize {
+        let pinned = match self.pinned_memos() {
+            Some(pinned) => pinned,
+            None         => return 0  // Unable to determine the heads. Try again next time
+        };
+
+        let shadowed = self.shadowed_memos(&pinned);
+
+        let mut collected = 0;
+        for memoref in shadowed {
+            if self.memo_is_durable(&memoref) && self.collect_memoref(&memoref) {
+                collected += 1;
+            }
+        }
+
+        self.metrics.memos_collected.add(collected as u64);
+        collected
+    }
+    /// Resident memorefs which are shadowed by materialized memos in every known head
+    fn shadowed_memos (&self, pinned: &PinnedMemos) -> Vec<MemoRef> {
+        let mut live : HashSet<MemoId> = HashSet::new();
+        let mut undetermined : HashSet<SubjectId> = pinned.subject_ids.clone();
+        let mut shadow_roots : Vec<MemoRef> = Vec::new();
+
+        let mut queue : Vec<MemoRef> = pinned.heads.iter().flat_map(|h| h.iter().cloned() ).collect();
+
+        while let Some(memoref) = queue.pop() {
+            if !live.insert(memoref.id()) {
+                continue;
+            }
+
+            let memo = match memoref.get_memo_if_resident() {
+                Some(memo) => memo,
+                None => {
+                    // We can't see past it without retrieving it, so we can't be sure what's shadowed
+                    if let Some(subject_id) = memoref.subject_id {
+                        undetermined.insert(subject_id);
+                    }
+                    continue;
+                }
+            };
+
+            let relations = match memo.body {
+                MemoBody::Relation(ref r)                         => Some(r),
+                MemoBody::FullyMaterialized{ v: _, ref r }        => Some(r),
+                MemoBody::PartiallyMaterialized{ v: _, ref r }    => Some(r),
+                _                                                 => None
+            };
+            if let Some(relations) = relations {
+                for (_, head) in relations.values().filter_map(|r| r.as_ref() ) {
+                    queue.extend(head.iter().cloned());
+                }
+            }
+            if let MemoBody::SlabPresence{ p: _, r: Some(ref head) } = memo.body {
+                queue.extend(head.iter().cloned());
+            }
+
+            match memo.body {
+                MemoBody::FullyMaterialized{ v: _, r: _ } => shadow_roots.extend(memo.parents.iter().cloned()),
+                _                                         => queue.extend(memo.parents.iter().cloned()),
+            }
+        }
+
+        let mut shadowed = Vec::new();
+        let mut visited : HashSet<MemoId> = HashSet::new();
+        let mut queue = shadow_roots;
+
+        while let Some(memoref) = queue.pop() {
+            if live.contains(&memoref.id()) || !visited.insert(memoref.id()) {
+                continue;
+            }
+            if memoref.subject_id.is_none_or(|s| undetermined.contains(&s) ) {
+                continue;
+            }
+
+            if let Some(memo) = memoref.get_memo_if_resident() {
+                queue.extend(memo.parents.iter().cloned());
+                shadowed.push(memoref);
+            } else {
+                // Collected by an earlier pass, which may have kept some of its ancestors back
+                let parent_ids = self.collected.read().unwrap().get(&memoref.id()).cloned().unwrap_or_default();
+                queue.extend(parent_ids.iter().filter_map(|id| self.get_memoref(id) ));
+            }
+        }
+
+        shadowed
+    }
+    /// Discard a resident memo, retaining only the ids of its parents
+    fn collect_memoref (&self, memoref: &MemoRef) -> bool {
+        let parent_ids = {
+            let mut ptr = memoref.ptr.write().unwrap();
+            let parent_ids = match *ptr {
+                MemoRefPtr::Resident(ref memo) => memo.parents.memo_ids(),
+                MemoRefPtr::Remote             => return false
+            };
+            *ptr = MemoRefPtr::Remote;
+            parent_ids
+        };
+
+        self.collected.write().unwrap().insert(memoref.id(), parent_ids.clone());
+        self.memorefs_by_id.write().unwrap().remove(&memoref.id());
+        self.untrack_resident_memo(memoref.id());
+        self.persist_collected_memo(memoref.id(), &parent_ids);
+
+        let entry = (
+            memoref.id(),
+            memoref.subject_id,
+            MemoPeerList::new(vec![MemoPeer{
+                slabref: self.my_ref.clone(),
+                status: MemoPeeringStatus::NonParticipating
+            }])
+        );
+
+        // The collected memo is not made a parent, as that would keep it around
+        let peers : Vec<SlabRef> = memoref.peerlist.read().unwrap().iter().map(|p| p.slabref.clone() ).collect();
+        for peer in peers.iter() {
+            self.send_peering( peer, None, vec![entry.clone()] );
+        }
+
+        true
+    }
+    /// Returns true if the given memo was collected by a GC pass
+    pub fn memo_is_collected (&self, memo_id: &MemoId) -> bool {
+        self.collected.read().unwrap().contains_key(memo_id)
+    }
+    /// Determine whether a collected memo descends the given memoref, by way of the parent ids we retained.
+    /// None if the memo was not collected
+    pub (super) fn collected_memo_descends (&self, memo_id: &MemoId, memoref: &MemoRef) -> Option<bool> {
+        // Ancestors which weren't collected along with it ( because they weren't resident ) must be asked themselves
+        let mut frontier : Vec<MemoRef> = Vec::new();
+        {
+            let collected = self.collected.read().unwrap();
+            let mut stack : Vec<MemoId> = collected.get(memo_id)?.clone();
+            let mut visited : HashSet<MemoId> = HashSet::new();
+
+            while let Some(id) = stack.pop() {
+                if id == memoref.id() {
+                    return Some(true);
+                }
+                if !visited.insert(id) {
+                    continue;
+                }
+                match collected.get(&id) {
+                    Some(parent_ids) => stack.extend(parent_ids.iter().cloned()),
+                    None => {
+                        if let Some(ancestor) = self.get_memoref(&id) {
+                            frontier.push(ancestor);
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(frontier.iter().any(|ancestor| ancestor.descends(memoref, self) ))
+    }
+}
@@ -4,31 +4,28 @@ impl Slab {
     // NOTE: this is run inside a dedicated thread, as fetches from other slabs may be required for
     // apply_subject_head ( which calls descends, which calls get_memo, which blocks )
     // QUESTION: could this be managed with a marker?
-    pub fn dispatch_memoref (&self, memoref : MemoRef){
-        //println!("# \t\\ Slab({}).dispatch_memoref({})", self.id, &memoref.id );
+    pub fn dispatch_subject_memorefs (&self, subject_id: SubjectId, memorefs: Vec<MemoRef>){
+        //println!("# \t\\ Slab({}).dispatch_subject_memorefs({}, {:?})", self.id, subject_id, memorefs );
 
-        if let Some(subject_id) = memoref.subject_id {
+        let maybe_sub : Option<Vec<WeakContext>> = {
+            // we want to make sure the lock is released before continuing
+            self.subject_subscriptions.read().unwrap().get( &subject_id ).cloned()
+        };
 
-            let maybe_sub : Option<Vec<WeakContext>> = {
-                // we want to make sure the lock is released before continuing
-                if let Some(ref s) = self.subject_subscriptions.read().unwrap().get( &subject_id ) {
-                    Some((*s).clone())
-                }else{
-                    None
-                }
-            };
+        if let Some(subscribers) = maybe_sub {
 
-            if let Some(subscribers) = maybe_sub {
+            // Memos of the same batch may descend one another, so reduce them to a proper head first
+            let mut head = MemoRefHead::new();
+            head.apply_memorefs(&memorefs, self);
 
-                for weakcontext in subscribers {
+            for weakcontext in subscribers {
 
-                    if let Some(context) = weakcontext.upgrade() {
+                if let Some(context) = weakcontext.upgrade() {
 
-                        context.apply_subject_head( subject_id, &memoref.to_head(), true );
-                    }
+                    context.apply_subject_head( subject_id, &head, true );
+                    self.metrics.subject_heads_dispatched.inc();
                 }
             }
-
         }
     }
 
@@ -45,10 +42,13 @@ impl Slab {
 
                         // HACK - this should be done inside the deserialize
                         for memoref in root_index_seed.iter() {
-                            memoref.update_peer(origin_slabref, MemoPeeringStatus::Resident);
+                            if let Err(finding) = memoref.update_peer(origin_slabref, MemoPeeringStatus::Resident, self.config.peerlist_limit) {
+                                self.note_integrity_finding(finding);
+                            }
                         }
 
                         self.net.apply_root_index_seed( &presence, root_index_seed, &self.my_ref );
+                        self.persist_root_index_seed();
                     }
                     &None => {}
                 }
@@ -82,46 +82,92 @@ impl Slab {
                     }
                 }
             }
-            MemoBody::Peering(memo_id, subject_id, ref peerlist ) => {
-                let (peered_memoref,_had_memo) = self.assert_memoref( memo_id, subject_id, peerlist.clone(), None );
+            MemoBody::Peering(ref entries) => {
+                for &(memo_id, subject_id, ref peerlist) in entries.iter() {
+                    let (peered_memoref,_had_memo) = self.assert_memoref( memo_id, subject_id, peerlist.clone(), None );
+
+                    // Under Plumtree, this is how lazy peers announce memos to us. We may have heard of the memo
+                    // already ( eg: as the parent of another ) without having it
+                    if !peered_memoref.is_resident() && self.config.emission_strategy == EmissionStrategy::Plumtree
+                        && peerlist.iter().any(|p| p.slabref.slab_id == origin_slabref.slab_id && p.status == MemoPeeringStatus::Resident ) {
+                        self.plumtree_announced(memo_id, origin_slabref);
+                    }
 
-                // Don't peer with yourself
-                for peer in peerlist.iter().filter(|p| p.slabref.0.slab_id != self.id ) {
-                    peered_memoref.update_peer( &peer.slabref, peer.status.clone());
+                    // Don't peer with yourself
+                    for peer in peerlist.iter().filter(|p| p.slabref.0.slab_id != self.id ) {
+                        if let Err(finding) = peered_memoref.update_peer( &peer.slabref, peer.status.clone(), self.config.peerlist_limit) {
+                            self.note_integrity_finding(finding);
+                        }
+                    }
+                    self.persist_peerlist(&peered_memoref);
                 }
+
+                let _lock = self.peering_received_lock.lock().unwrap();
+                self.peering_received.notify_all();
             },
             MemoBody::MemoRequest(ref desired_memo_ids, ref requesting_slabref ) => {
 
                 if requesting_slabref.0.slab_id != self.id {
+                    let mut missing = Vec::new();
+
                     for desired_memo_id in desired_memo_ids {
-                        if let Some(desired_memoref) = self.memorefs_by_id.read().unwrap().get(&desired_memo_id) {
+                        if let Some(desired_memoref) = self.get_memoref(desired_memo_id) {
 
                             if desired_memoref.is_resident() {
-                                requesting_slabref.send(&self.my_ref, desired_memoref)
+                                requesting_slabref.send(&self.my_ref, &desired_memoref)
                             } else {
                                 // Somebody asked me for a memo I don't have
                                 // It would be neighborly to tell them I don't have it
                                 self.do_peering(&memoref,requesting_slabref);
                             }
                         }else{
-                            let peering_memoref = self.new_memo(
+                            missing.push((
+                                *desired_memo_id,
                                 None,
-                                MemoRefHead::from_memoref(memoref.clone()),
-                                MemoBody::Peering(
-                                    *desired_memo_id,
-                                    None,
-                                    MemoPeerList::new(vec![MemoPeer{
-                                        slabref: self.my_ref.clone(),
-                                        status: MemoPeeringStatus::NonParticipating
-                                    }])
-                                )
-                            );
-                            requesting_slabref.send(&self.my_ref, &peering_memoref)
+                                MemoPeerList::new(vec![MemoPeer{
+                                    slabref: self.my_ref.clone(),
+                                    status: MemoPeeringStatus::NonParticipating
+                                }])
+                            ));
                         }
                     }
+
+                    self.send_peering(requesting_slabref, Some(memoref), missing);
+                }
+            }
+            MemoBody::Prune(pruning_slab_id) => {
+                if pruning_slab_id == origin_slabref.slab_id {
+                    self.handle_prune(origin_slabref);
+                }
+            }
+            MemoBody::Graft(ref memo_ids, grafting_slab_id) => {
+                if grafting_slab_id == origin_slabref.slab_id {
+                    self.handle_graft(memo_ids, origin_slabref);
                 }
             }
+            // Only the departing slab may say goodbye for itself
+            MemoBody::Goodbye(departing_slab_id) if departing_slab_id == origin_slabref.slab_id && departing_slab_id != self.id => {
+                self.handle_goodbye(origin_slabref);
+            }
             _ => {}
         }
     }
+    /// A peer slab is leaving. Forget it, mark it NonParticipating for every memo it was peered with,
+    /// and re-replicate any resident memos which are now below their durability target
+    fn handle_goodbye(&self, departing_slabref: &SlabRef ){
+        self.peer_refs.write().unwrap().retain(|r| r.slab_id != departing_slabref.slab_id );
+        self.plumtree_forget_peer(departing_slabref.slab_id);
+
+        let affected : Vec<MemoRef> = self.all_memorefs().into_iter()
+            .filter(|m| m.peerlist.read().unwrap().iter().any(|p| p.slabref.slab_id == departing_slabref.slab_id ) )
+            .collect();
+
+        for memoref in affected.iter() {
+            if let Err(finding) = memoref.update_peer(departing_slabref, MemoPeeringStatus::NonParticipating, self.config.peerlist_limit) {
+                self.note_integrity_finding(finding);
+            }
+            self.persist_peerlist(memoref);
+            self.consider_emit_memo(memoref);
+        }
+    }
 }
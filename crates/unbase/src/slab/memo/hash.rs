@@ -0,0 +1,283 @@
+/* Content addressed MemoIds
+ *
+ * A MemoId is the SHA-256 of a deterministic encoding of the subject id, parent ids and body.
+ * Identical memos therefore receive identical ids, and any slab may verify that a memo it
+ * receives has not been tampered with in transit.
+ *
+ * Ids are calculated lazily ( see TODO.txt ) as memos which never leave the slab that created them
+ * have no need of one. Within a slab, memos are linked to their parents directly by MemoRef, so
+ * only transmission, storage and OR-Set tagging call for the id, via Memo::id / MemoRef::id.
+ * Until then, the memoref is held in Slab.unhashed_memorefs rather than memorefs_by_id. Whichever call
+ * calculates the id has the LazyMemoId tell the slab, which moves the memoref across.
+*/
+
+use super::*;
+use sha2::{Sha256, Digest};
+
+impl Memo {
+    /// Calculate the content addressed id for the given memo contents
+    pub fn calculate_id (subject_id: Option<SubjectId>, parents: &MemoRefHead, body: &MemoBody) -> MemoId {
+        let mut hasher = Sha256::new();
+
+        input_option_u64(&mut hasher, subject_id);
+        input_head(&mut hasher, parents);
+        body.input_hash(&mut hasher);
+
+        let mut memo_id : MemoId = [0u8; 32];
+        memo_id.copy_from_slice(hasher.result().as_slice());
+        memo_id
+    }
+    /// Returns true if this memo's id matches its contents. Ids which are yet to be calculated trivially do
+    pub fn verify_id (&self) -> bool {
+        match self.id.get() {
+            Some(memo_id) => memo_id == Memo::calculate_id(self.subject_id, &self.parents, &self.body),
+            None          => true
+        }
+    }
+}
+
+impl MemoBody {
+    fn input_hash (&self, hasher: &mut Sha256) {
+        match *self {
+            MemoBody::SlabPresence{ ref p, ref r } => {
+                hasher.input([0u8]);
+                hasher.input(p.slab_id.to_le_bytes());
+                input_str(hasher, &p.address.to_string());
+                hasher.input([lifetime_discriminant(&p.lifetime)]);
+                match *r {
+                    Some(ref head) => {
+                        hasher.input([1u8]);
+                        input_head(hasher, head);
+                    }
+                    None => hasher.input([0u8])
+                }
+            }
+            MemoBody::Relation(ref r) => {
+                hasher.input([1u8]);
+                input_relations(hasher, r);
+            }
+            MemoBody::Edit(ref v) => {
+                hasher.input([2u8]);
+                input_values(hasher, v);
+            }
+            MemoBody::FullyMaterialized{ ref v, ref r } => {
+                hasher.input([3u8]);
+                input_values(hasher, v);
+                input_relations(hasher, r);
+            }
+            MemoBody::PartiallyMaterialized{ ref v, ref r } => {
+                hasher.input([4u8]);
+                input_values(hasher, v);
+                input_relations(hasher, r);
+            }
+            MemoBody::Peering(ref entries) => {
+                hasher.input([5u8]);
+                hasher.input((entries.len() as u64).to_le_bytes());
+                for &(ref memo_id, subject_id, ref peerlist) in entries.iter() {
+                    hasher.input(memo_id);
+                    input_option_u64(hasher, subject_id);
+
+                    let mut peers : Vec<(SlabId,u8)> = peerlist.iter().map(|p| (p.slabref.slab_id, status_discriminant(&p.status)) ).collect();
+                    peers.sort();
+                    hasher.input((peers.len() as u64).to_le_bytes());
+                    for (slab_id, status) in peers {
+                        hasher.input(slab_id.to_le_bytes());
+                        hasher.input([status]);
+                    }
+                }
+            }
+            MemoBody::MemoRequest(ref memo_ids, ref slabref) => {
+                hasher.input([6u8]);
+                hasher.input((memo_ids.len() as u64).to_le_bytes());
+                for memo_id in memo_ids.iter() {
+                    hasher.input(memo_id);
+                }
+                hasher.input(slabref.slab_id.to_le_bytes());
+            }
+            MemoBody::Goodbye(slab_id) => {
+                hasher.input([7u8]);
+                hasher.input(slab_id.to_le_bytes());
+            }
+            MemoBody::Prune(slab_id) => {
+                hasher.input([8u8]);
+                hasher.input(slab_id.to_le_bytes());
+            }
+            MemoBody::Graft(ref memo_ids, slab_id) => {
+                hasher.input([9u8]);
+                hasher.input((memo_ids.len() as u64).to_le_bytes());
+                for memo_id in memo_ids.iter() {
+                    hasher.input(memo_id);
+                }
+                hasher.input(slab_id.to_le_bytes());
+            }
+            MemoBody::Counter(slab_id, ref deltas) => {
+                hasher.input([10u8]);
+                hasher.input(slab_id.to_le_bytes());
+
+                let mut keys : Vec<&String> = deltas.keys().collect();
+                keys.sort();
+                hasher.input((keys.len() as u64).to_le_bytes());
+                for k in keys {
+                    input_str(hasher, k);
+                    hasher.input(deltas[k].to_le_bytes());
+                }
+            }
+            MemoBody::SetAdd(ref adds) => {
+                hasher.input([11u8]);
+
+                let mut keys : Vec<&String> = adds.keys().collect();
+                keys.sort();
+                hasher.input((keys.len() as u64).to_le_bytes());
+                for k in keys {
+                    input_str(hasher, k);
+                    input_value(hasher, &Value::List(adds[k].clone()));
+                }
+            }
+            MemoBody::SetRemove(ref removes) => {
+                hasher.input([12u8]);
+
+                let mut keys : Vec<&String> = removes.keys().collect();
+                keys.sort();
+                hasher.input((keys.len() as u64).to_le_bytes());
+                for k in keys {
+                    input_str(hasher, k);
+                    hasher.input((removes[k].len() as u64).to_le_bytes());
+                    for (tag, element) in removes[k].iter() {
+                        hasher.input(tag);
+                        input_value(hasher, element);
+                    }
+                }
+            }
+            MemoBody::Register(ref v) => {
+                hasher.input([13u8]);
+                input_values(hasher, v);
+            }
+        }
+    }
+}
+
+/// Enums are hashed by explicit discriminant, such that renaming a variant does not change any ids
+fn lifetime_discriminant (lifetime: &SlabAnticipatedLifetime) -> u8 {
+    match *lifetime {
+        SlabAnticipatedLifetime::Ephmeral => 0,
+        SlabAnticipatedLifetime::Session  => 1,
+        SlabAnticipatedLifetime::Long     => 2,
+        SlabAnticipatedLifetime::VeryLong => 3,
+        SlabAnticipatedLifetime::Unknown  => 4,
+    }
+}
+
+fn status_discriminant (status: &MemoPeeringStatus) -> u8 {
+    match *status {
+        MemoPeeringStatus::Resident         => 0,
+        MemoPeeringStatus::Participating    => 1,
+        MemoPeeringStatus::NonParticipating => 2,
+        MemoPeeringStatus::Unknown          => 3,
+    }
+}
+
+fn input_str (hasher: &mut Sha256, s: &str) {
+    hasher.input((s.len() as u64).to_le_bytes());
+    hasher.input(s.as_bytes());
+}
+
+fn input_option_u64 (hasher: &mut Sha256, value: Option<u64>) {
+    match value {
+        Some(v) => {
+            hasher.input([1u8]);
+            hasher.input(v.to_le_bytes());
+        }
+        None => hasher.input([0u8])
+    }
+}
+
+/// Parents are a set, so their order must not affect the id
+fn input_head (hasher: &mut Sha256, head: &MemoRefHead) {
+    let mut memo_ids = head.memo_ids();
+    memo_ids.sort();
+
+    hasher.input((memo_ids.len() as u64).to_le_bytes());
+    for memo_id in memo_ids.iter() {
+        hasher.input(memo_id);
+    }
+}
+
+fn input_values (hasher: &mut Sha256, values: &HashMap<String, Value>) {
+    let mut keys : Vec<&String> = values.keys().collect();
+    keys.sort();
+
+    hasher.input((keys.len() as u64).to_le_bytes());
+    for k in keys {
+        input_str(hasher, k);
+        input_value(hasher, &values[k]);
+    }
+}
+
+/// Values are tagged with their type, such that ( eg ) the string "1" and the integer 1 receive different ids
+fn input_value (hasher: &mut Sha256, value: &Value) {
+    match *value {
+        Value::Null => hasher.input([0u8]),
+        Value::Bool(b) => {
+            hasher.input([1u8]);
+            hasher.input([b as u8]);
+        }
+        Value::I64(i) => {
+            hasher.input([2u8]);
+            hasher.input(i.to_le_bytes());
+        }
+        Value::F64(f) => {
+            hasher.input([3u8]);
+            hasher.input(f.to_bits().to_le_bytes());
+        }
+        Value::String(ref s) => {
+            hasher.input([4u8]);
+            input_str(hasher, s);
+        }
+        Value::Bytes(ref b) => {
+            hasher.input([5u8]);
+            hasher.input((b.len() as u64).to_le_bytes());
+            hasher.input(b);
+        }
+        Value::List(ref l) => {
+            hasher.input([6u8]);
+            hasher.input((l.len() as u64).to_le_bytes());
+            for v in l.iter() {
+                input_value(hasher, v);
+            }
+        }
+        Value::Map(ref m) => {
+            hasher.input([7u8]);
+            input_values(hasher, m);
+        }
+        Value::Subject(subject_id) => {
+            hasher.input([8u8]);
+            hasher.input(subject_id.to_le_bytes());
+        }
+        Value::Set(ref s) => {
+            hasher.input([9u8]);
+            hasher.input((s.len() as u64).to_le_bytes());
+            for (tag, v) in s.iter() {
+                hasher.input(tag);
+                input_value(hasher, v);
+            }
+        }
+    }
+}
+
+fn input_relations (hasher: &mut Sha256, relations: &RelationSlotSubjectHead) {
+    let mut slots : Vec<&RelationSlotId> = relations.keys().collect();
+    slots.sort();
+
+    hasher.input((slots.len() as u64).to_le_bytes());
+    for slot_id in slots {
+        hasher.input([*slot_id]);
+        match relations[slot_id] {
+            Some((subject_id, ref head)) => {
+                hasher.input([1u8]);
+                hasher.input(subject_id.to_le_bytes());
+                input_head(hasher, head);
+            }
+            None => hasher.input([0u8])
+        }
+    }
+}
@@ -2,18 +2,33 @@
  * A memo is an immutable message.
 */
 pub mod serde;
+mod hash;
 
 use std::collections::HashMap;
 use std::{fmt};
-use std::sync::Arc;
+use std::sync::{Arc,OnceLock};
 
 use crate::subject::{SubjectId};
+use crate::value::Value;
 use crate::slab::MemoRef;
 use crate::network::{SlabRef,SlabPresence};
 use super::*;
 
-//pub type MemoId = [u8; 32];
-pub type MemoId = u64;
+pub type MemoId = [u8; 32];
+
+/// The id of a memo, which is only calculated from its contents once it's needed ( See hash.rs ).
+/// Clones share the id, such that a memo and its memoref learn of it at the same time
+#[derive(Clone)]
+pub struct LazyMemoId(Arc<LazyMemoIdInner>);
+
+struct LazyMemoIdInner {
+    id:   OnceLock<MemoId>,
+    /// The slab which created the memo, which is told once the id is calculated, such that it may find the memo by id
+    slab: Option<WeakSlab>,
+}
+
+/// A peering update for a single memo, several of which may be conveyed in one Peering memo
+pub type PeeringEntry = (MemoId, Option<SubjectId>, MemoPeerList);
 
 // All portions of this struct should be immutable
 
@@ -28,7 +43,7 @@ impl Deref for Memo {
 }
 
 pub struct MemoInner {
-    pub id: u64,
+    pub id: LazyMemoId,
     pub subject_id: Option<SubjectId>,
     pub owning_slab_id: SlabId,
     pub parents: MemoRefHead,
@@ -39,11 +54,26 @@ pub struct MemoInner {
 pub enum MemoBody{
     SlabPresence{ p: SlabPresence, r: Option<MemoRefHead> }, // TODO: split out root_index_seed conveyance to another memobody type
     Relation(RelationSlotSubjectHead),
-    Edit(HashMap<String, String>),
-    FullyMaterialized     { v: HashMap<String, String>, r: RelationSlotSubjectHead },
-    PartiallyMaterialized { v: HashMap<String, String>, r: RelationSlotSubjectHead },
-    Peering(MemoId,Option<SubjectId>,MemoPeerList),
-    MemoRequest(Vec<MemoId>,SlabRef)
+    Edit(HashMap<String, Value>),
+    FullyMaterialized     { v: HashMap<String, Value>, r: RelationSlotSubjectHead },
+    PartiallyMaterialized { v: HashMap<String, Value>, r: RelationSlotSubjectHead },
+    Peering(Vec<PeeringEntry>),
+    MemoRequest(Vec<MemoId>,SlabRef),
+    Goodbye(SlabId),
+    /// Plumtree: The given slab received memos from us redundantly, and asks that we only announce memos to it
+    Prune(SlabId),
+    /// Plumtree: The given slab was announced the memos but never received them, and asks that we send them,
+    /// and push memos to it henceforth
+    Graft(Vec<MemoId>,SlabId),
+    /// PN-Counter: an increment ( or decrement ) of each of the given fields by the given slab. The slab id is
+    /// included so that identical increments made concurrently by two slabs are not conflated into one memo
+    Counter(SlabId, HashMap<String, i64>),
+    /// OR-Set: elements added to each of the given fields. The id of this memo is the tag of each addition
+    SetAdd(HashMap<String, Vec<Value>>),
+    /// OR-Set: the additions observed by the writer, by tag and element, which are to be removed from each of the given fields
+    SetRemove(HashMap<String, Vec<(MemoId, Value)>>),
+    /// Multi-value register: a write to each of the given fields, which concurrent writes do not overwrite
+    Register(HashMap<String, Value>),
 }
 
 
@@ -69,20 +99,73 @@ impl fmt::Debug for Memo{
     }
 }
 
+impl LazyMemoId {
+    /// An id which is yet to be calculated, for a memo created by the given slab
+    pub fn new (slab: &Slab) -> Self {
+        LazyMemoId(Arc::new(LazyMemoIdInner{ id: OnceLock::new(), slab: Some(slab.weak()) }))
+    }
+    /// An id which is already known, Eg: because the memo was received from another slab
+    pub fn known (memo_id: MemoId) -> Self {
+        LazyMemoId(Arc::new(LazyMemoIdInner{ id: OnceLock::from(memo_id), slab: None }))
+    }
+    /// The id, if it has been calculated
+    pub fn get (&self) -> Option<MemoId> {
+        self.0.id.get().cloned()
+    }
+    /// Calculation is not serialized, as it would be by OnceLock::get_or_init, lest a thread wait on another which
+    /// holds a lock it needs. Whoever finishes first wins, which is of no consequence as they arrive at the same id.
+    /// The winner tells the slab
+    pub fn get_or_calculate<F> (&self, calculate: F) -> MemoId where F: FnOnce() -> MemoId {
+        if let Some(memo_id) = self.0.id.get() {
+            return *memo_id;
+        }
+
+        let memo_id = calculate();
+        if self.0.id.set(memo_id).is_ok() {
+            if let Some(slab) = self.0.slab.as_ref().and_then(|s| s.upgrade()) {
+                slab.register_hashed_memoref(self, memo_id);
+            }
+        }
+        *self.0.id.get().unwrap()
+    }
+    /// Identifies this id and its clones, whether or not it has been calculated
+    pub (super) fn key (&self) -> usize {
+        &*self.0 as *const LazyMemoIdInner as usize
+    }
+}
+
+impl fmt::Debug for LazyMemoId{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(memo_id) => memo_id.fmt(fmt),
+            None          => fmt.write_str("<not yet calculated>")
+        }
+    }
+}
+
 impl Memo {
     pub fn new (inner: MemoInner) -> Self {
         Memo(Arc::new(inner))
     }
+    /// The content addressed id of this memo, which is calculated the first time it's needed
+    pub fn id (&self) -> MemoId {
+        self.id.get_or_calculate(|| Memo::calculate_id(self.subject_id, &self.parents, &self.body) )
+    }
     pub fn get_parent_head (&self) -> MemoRefHead {
         self.parents.clone()
     }
-    pub fn get_values (&self) -> Option<(HashMap<String, String>,bool)> {
+    pub fn get_values (&self) -> Option<(HashMap<String, Value>,bool)> {
 
         match self.body {
             MemoBody::Edit(ref v)
                 => Some((v.clone(),false)),
             MemoBody::FullyMaterialized { ref v, r: _ }
                 => Some((v.clone(),true)),
+            // Only authoritative for the keys it contains
+            MemoBody::PartiallyMaterialized { ref v, r: _ }
+                => Some((v.clone(),false)),
+            MemoBody::Register(ref v)
+                => Some((v.clone(),false)),
             _   => None
         }
     }
@@ -93,6 +176,8 @@ impl Memo {
                 => Some((r.clone(),false)),
             MemoBody::FullyMaterialized { v: _, ref r }
                 => Some((r.clone(),true)),
+            MemoBody::PartiallyMaterialized { v: _, ref r }
+                => Some((r.clone(),false)),
             _   => None
         }
     }
@@ -101,17 +186,67 @@ impl Memo {
             MemoBody::MemoRequest(_,_) => {
                 false
             }
-            MemoBody::Peering(_,_,_) => {
+            MemoBody::Peering(_) => {
                 false
             }
             MemoBody::SlabPresence{p:_, r:_} => {
                 false
             }
+            MemoBody::Goodbye(_) => {
+                false
+            }
+            MemoBody::Prune(_) => {
+                false
+            }
+            MemoBody::Graft(_,_) => {
+                false
+            }
             _ => {
                 true
             }
         }
     }
+    /// A rough estimate of the memory occupied by this memo, for the purposes of memory budgeting
+    pub fn approximate_size (&self) -> usize {
+        use std::mem::size_of;
+
+        let head_size      = |head: &MemoRefHead| head.len() * size_of::<MemoRef>();
+        let values_size    = |v: &HashMap<String, Value>| {
+            v.iter().map(|(k,v)| k.len() + size_of::<String>() + v.approximate_size() ).sum::<usize>()
+        };
+        let relations_size = |r: &RelationSlotSubjectHead| {
+            r.values().map(|relation| {
+                size_of::<RelationSlotId>() + size_of::<SubjectId>() + relation.as_ref().map(|(_, head)| head_size(head) ).unwrap_or(0)
+            }).sum::<usize>()
+        };
+
+        let body_size = match self.body {
+            MemoBody::SlabPresence{ p: _, ref r } => {
+                size_of::<SlabPresence>() + r.as_ref().map(&head_size).unwrap_or(0)
+            }
+            MemoBody::Relation(ref r)                            => relations_size(r),
+            MemoBody::Edit(ref v)                                => values_size(v),
+            MemoBody::FullyMaterialized{ ref v, ref r }          => values_size(v) + relations_size(r),
+            MemoBody::PartiallyMaterialized{ ref v, ref r }      => values_size(v) + relations_size(r),
+            MemoBody::Peering(ref entries)                       => {
+                entries.iter().map(|(_, _, peerlist)| size_of::<PeeringEntry>() + peerlist.len() * size_of::<MemoPeer>() ).sum()
+            }
+            MemoBody::MemoRequest(ref memo_ids, _)               => memo_ids.len() * size_of::<MemoId>(),
+            MemoBody::Goodbye(_)                                 => size_of::<SlabId>(),
+            MemoBody::Prune(_)                                   => size_of::<SlabId>(),
+            MemoBody::Graft(ref memo_ids, _)                     => memo_ids.len() * size_of::<MemoId>() + size_of::<SlabId>(),
+            MemoBody::Counter(_, ref deltas)                     => deltas.keys().map(|k| k.len() + size_of::<String>() + size_of::<i64>() ).sum(),
+            MemoBody::SetAdd(ref adds)                           => {
+                adds.iter().map(|(k,l)| k.len() + size_of::<String>() + l.iter().map(|v| v.approximate_size() ).sum::<usize>() ).sum()
+            }
+            MemoBody::SetRemove(ref removes)                     => {
+                removes.iter().map(|(k,l)| k.len() + size_of::<String>() + l.iter().map(|(_, v)| size_of::<MemoId>() + v.approximate_size() ).sum::<usize>() ).sum()
+            }
+            MemoBody::Register(ref v)                            => values_size(v),
+        };
+
+        size_of::<MemoInner>() + head_size(&self.parents) + body_size
+    }
     pub fn descends (&self, memoref: &MemoRef, slab: &Slab) -> bool {
         //TODO: parallelize this
         //TODO: Use sparse-vector/beacon to avoid having to trace out the whole lineage
@@ -133,18 +268,30 @@ impl Memo {
         }
         return false;
     }
-    pub fn clone_for_slab (&self, from_slabref: &SlabRef, to_slab: &Slab, peerlist: &MemoPeerList) -> Memo {
+    pub fn clone_for_slab (&self, from_slabref: &SlabRef, to_slab: &Slab, peerlist: &MemoPeerList) -> Option<Memo> {
         assert!(from_slabref.owning_slab_id == to_slab.id, "Memo clone_for_slab owning slab should be identical");
 
         //println!("Slab({}).Memo.clone_for_slab(memo: {}, from: {}, to: {}, peers: {:?})", self.owning_slab_id, self.id, from_slabref.slab_id, to_slab.id, peerlist );
         to_slab.reconstitute_memo(
-            self.id,
+            self.id(),
             self.subject_id,
             self.parents.clone_for_slab(from_slabref, to_slab, false),
             self.body.clone_for_slab(from_slabref, to_slab),
             from_slabref,
             peerlist
-        ).0
+        ).ok().map(|r| r.0)
+    }
+    /// Like clone_for_slab, but rather than reconstituting the memo immediately, provide its constituents for batched ingress
+    pub fn clone_parts_for_slab (&self, from_slabref: &SlabRef, to_slab: &Slab, peerlist: MemoPeerList) -> MemoParts {
+        assert!(from_slabref.owning_slab_id == to_slab.id, "Memo clone_parts_for_slab owning slab should be identical");
+
+        MemoParts {
+            id:         self.id(),
+            subject_id: self.subject_id,
+            parents:    self.parents.clone_for_slab(from_slabref, to_slab, false),
+            body:       self.body.clone_for_slab(from_slabref, to_slab),
+            peerlist,
+        }
     }
 }
 
@@ -176,12 +323,35 @@ impl MemoBody {
             &MemoBody::PartiallyMaterialized{ ref v, ref r } => {
                 MemoBody::PartiallyMaterialized{ v: v.clone(), r: r.clone_for_slab(from_slabref, to_slab)}
             }
-            &MemoBody::Peering(memo_id, subject_id, ref peerlist) => {
-                MemoBody::Peering(memo_id,subject_id,peerlist.clone_for_slab(to_slab))
+            MemoBody::Peering(entries) => {
+                MemoBody::Peering(
+                    entries.iter().map(|&(memo_id, subject_id, ref peerlist)| (memo_id, subject_id, peerlist.clone_for_slab(to_slab)) ).collect()
+                )
             }
             &MemoBody::MemoRequest(ref memo_ids, ref slabref) =>{
                 MemoBody::MemoRequest(memo_ids.clone(), slabref.clone_for_slab(to_slab))
             }
+            &MemoBody::Goodbye(slab_id) => {
+                MemoBody::Goodbye(slab_id)
+            }
+            &MemoBody::Prune(slab_id) => {
+                MemoBody::Prune(slab_id)
+            }
+            &MemoBody::Graft(ref memo_ids, slab_id) => {
+                MemoBody::Graft(memo_ids.clone(), slab_id)
+            }
+            &MemoBody::Counter(slab_id, ref deltas) => {
+                MemoBody::Counter(slab_id, deltas.clone())
+            }
+            MemoBody::SetAdd(adds) => {
+                MemoBody::SetAdd(adds.clone())
+            }
+            MemoBody::SetRemove(removes) => {
+                MemoBody::SetRemove(removes.clone())
+            }
+            MemoBody::Register(v) => {
+                MemoBody::Register(v.clone())
+            }
         }
 
     }
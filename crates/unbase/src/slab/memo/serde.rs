@@ -13,20 +13,22 @@ use ::serde::de::*;
 
 struct RelationMRHSeed<'a> { dest_slab: &'a Slab, origin_slabref: &'a SlabRef  }
 struct SubjectMRHSeed<'a> { dest_slab: &'a Slab, origin_slabref: &'a SlabRef  }
-pub struct MemoBodySeed<'a> { dest_slab: &'a Slab, origin_slabref: &'a SlabRef }
+pub struct MemoBodySeed<'a> { pub dest_slab: &'a Slab, pub origin_slabref: &'a SlabRef }
 #[derive(Clone)]
 pub struct MBMemoRequestSeed<'a> { dest_slab: &'a Slab, origin_slabref: &'a SlabRef  }
 struct MBSlabPresenceSeed <'a> { dest_slab: &'a Slab, origin_slabref: &'a SlabRef  }
 struct MBFullyMaterializedSeed<'a> { dest_slab: &'a Slab, origin_slabref: &'a SlabRef  }
 // TODO convert this to a non-seed deserializer
 struct MBPeeringSeed<'a> { dest_slab: &'a Slab }
+#[derive(Clone)]
+struct PeeringEntrySeed<'a> { dest_slab: &'a Slab }
 
 impl StatefulSerialize for Memo {
     fn serialize<S>(&self, serializer: S, helper: &SerializeHelper) -> Result<S::Ok, S::Error>
         where S: Serializer
     {
         let mut seq = serializer.serialize_seq(Some(4))?;
-        seq.serialize_element( &self.id )?;
+        seq.serialize_element( &self.id() )?;
         seq.serialize_element( &self.subject_id )?;
         seq.serialize_element( &SerializeWrapper( &self.body, helper ) )?;
         seq.serialize_element( &SerializeWrapper( &self.parents, helper ) )?;
@@ -71,11 +73,9 @@ impl StatefulSerialize for MemoBody {
                 sv.serialize_field("v", v)?;
                 sv.end()
             },
-            Peering( ref memo_id, ref subject_id, ref peerlist ) =>{
-                let mut sv = serializer.serialize_struct_variant("MemoBody", 5, "Peering", 3)?;
-                sv.serialize_field("i", memo_id )?;
-                sv.serialize_field("j", subject_id )?;
-                sv.serialize_field("l", &SerializeWrapper(peerlist,helper) )?;
+            Peering( ref entries ) =>{
+                let mut sv = serializer.serialize_struct_variant("MemoBody", 5, "Peering", 1)?;
+                sv.serialize_field("p", &SerializeWrapper(entries,helper) )?;
                 sv.end()
             }
             MemoRequest( ref memo_ids, ref slabref ) =>{
@@ -84,12 +84,45 @@ impl StatefulSerialize for MemoBody {
                 sv.serialize_field("s", &SerializeWrapper(slabref, helper))?;
                 sv.end()
             }
+            Goodbye( ref slab_id ) => {
+                serializer.serialize_newtype_variant("MemoBody", 7, "Goodbye", slab_id )
+            }
+            Prune( ref slab_id ) => {
+                serializer.serialize_newtype_variant("MemoBody", 8, "Prune", slab_id )
+            }
+            Graft( ref memo_ids, ref slab_id ) => {
+                serializer.serialize_newtype_variant("MemoBody", 9, "Graft", &(memo_ids, slab_id) )
+            }
+            Counter( ref slab_id, ref deltas ) => {
+                serializer.serialize_newtype_variant("MemoBody", 10, "Counter", &(slab_id, deltas) )
+            }
+            SetAdd( ref adds ) => {
+                serializer.serialize_newtype_variant("MemoBody", 11, "SetAdd", adds )
+            }
+            SetRemove( ref removes ) => {
+                serializer.serialize_newtype_variant("MemoBody", 12, "SetRemove", removes )
+            }
+            Register( ref v ) => {
+                serializer.serialize_newtype_variant("MemoBody", 13, "Register", v )
+            }
         }
 
     }
 }
 
 
+impl StatefulSerialize for PeeringEntry {
+    fn serialize<S>(&self, serializer: S, helper: &SerializeHelper) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut seq = serializer.serialize_tuple(3)?;
+        seq.serialize_element( &self.0 )?;
+        seq.serialize_element( &self.1 )?;
+        seq.serialize_element( &SerializeWrapper( &self.2, helper ) )?;
+        seq.end()
+    }
+}
+
 impl StatefulSerialize for (SubjectId,MemoRefHead) {
     fn serialize<S>(&self, serializer: S, helper: &SerializeHelper) -> Result<S::Ok, S::Error>
         where S: Serializer
@@ -152,9 +185,10 @@ impl<'a> Visitor for MemoSeed<'a>{
            }
        };
 
-        let _memo = self.dest_slab.reconstitute_memo(id, subject_id, parents, body, self.origin_slabref, &self.peerlist ).0;
-
-        Ok(())
+        match self.dest_slab.reconstitute_memo(id, subject_id, parents, body, self.origin_slabref, &self.peerlist ) {
+            Ok(_)  => Ok(()),
+            Err(e) => Err(DeError::custom(format!("{:?}", e)))
+        }
     }
 }
 
@@ -165,7 +199,14 @@ enum MBVariant {
     FullyMaterialized,
     PartiallyMaterialized,
     Peering,
-    MemoRequest
+    MemoRequest,
+    Goodbye,
+    Prune,
+    Graft,
+    Counter,
+    SetAdd,
+    SetRemove,
+    Register
 }
 
 const MEMOBODY_VARIANTS: &'static [&'static str] = &[
@@ -175,7 +216,14 @@ const MEMOBODY_VARIANTS: &'static [&'static str] = &[
     "FullyMaterialized",
     "PartiallyMaterialized",
     "Peering",
-    "MemoRequest"
+    "MemoRequest",
+    "Goodbye",
+    "Prune",
+    "Graft",
+    "Counter",
+    "SetAdd",
+    "SetRemove",
+    "Register"
 ];
 
 impl<'a> DeserializeSeed for MemoBodySeed<'a> {
@@ -201,10 +249,22 @@ impl<'a> Visitor for MemoBodySeed<'a> {
             (MBVariant::Relation,          variant) => variant.visit_newtype_seed(RelationMRHSeed{ dest_slab: self.dest_slab, origin_slabref: self.origin_slabref }).map(MemoBody::Relation),
             (MBVariant::Edit,              variant) => variant.visit_newtype().map(MemoBody::Edit),
             (MBVariant::FullyMaterialized, variant) => variant.visit_newtype_seed(MBFullyMaterializedSeed{ dest_slab: self.dest_slab, origin_slabref: self.origin_slabref }),
-        //  (MBVariant::PartiallyMaterialized, variant) => variant.visit_newtype().map(MemoBody::PartiallyMaterialized),
+            (MBVariant::PartiallyMaterialized, variant) => {
+                // Serialized identically to FullyMaterialized
+                variant.visit_newtype_seed(MBFullyMaterializedSeed{ dest_slab: self.dest_slab, origin_slabref: self.origin_slabref }).map(|body| match body {
+                    MemoBody::FullyMaterialized{ v, r } => MemoBody::PartiallyMaterialized{ v, r },
+                    body                                => body
+                })
+            },
             (MBVariant::Peering,           variant) => variant.visit_newtype_seed(MBPeeringSeed{ dest_slab: self.dest_slab }),
             (MBVariant::MemoRequest,       variant) => variant.visit_newtype_seed(MBMemoRequestSeed{ dest_slab: self.dest_slab, origin_slabref: self.origin_slabref }),
-            _ => unimplemented!()
+            (MBVariant::Goodbye,           variant) => variant.visit_newtype().map(MemoBody::Goodbye),
+            (MBVariant::Prune,             variant) => variant.visit_newtype().map(MemoBody::Prune),
+            (MBVariant::Graft,             variant) => variant.visit_newtype().map(|(memo_ids, slab_id): (Vec<MemoId>, SlabId)| MemoBody::Graft(memo_ids, slab_id)),
+            (MBVariant::Counter,           variant) => variant.visit_newtype().map(|(slab_id, deltas): (SlabId, HashMap<String, i64>)| MemoBody::Counter(slab_id, deltas)),
+            (MBVariant::SetAdd,            variant) => variant.visit_newtype().map(MemoBody::SetAdd),
+            (MBVariant::SetRemove,         variant) => variant.visit_newtype().map(MemoBody::SetRemove),
+            (MBVariant::Register,          variant) => variant.visit_newtype().map(MemoBody::Register),
 
         }
     }
@@ -236,6 +296,13 @@ impl Visitor for MBVariantVisitor
             "PartiallyMaterialized"   => Ok(MBVariant::PartiallyMaterialized),
             "Peering"                 => Ok(MBVariant::Peering),
             "MemoRequest"             => Ok(MBVariant::MemoRequest),
+            "Goodbye"                 => Ok(MBVariant::Goodbye),
+            "Prune"                   => Ok(MBVariant::Prune),
+            "Graft"                   => Ok(MBVariant::Graft),
+            "Counter"                 => Ok(MBVariant::Counter),
+            "SetAdd"                  => Ok(MBVariant::SetAdd),
+            "SetRemove"               => Ok(MBVariant::SetRemove),
+            "Register"                => Ok(MBVariant::Register),
             _ => Err(serde::DeError::unknown_field(value, MEMOBODY_VARIANTS)),
         }
     }
@@ -301,8 +368,9 @@ impl<'a> Visitor for RelationMRHSeed<'a> {
         let mut values = HashMap::new();
 
         while let Some(slot) = visitor.visit_key()? {
-             let (subject_id ,mrh ) = visitor.visit_value_seed(SubjectMRHSeed{ dest_slab: self.dest_slab, origin_slabref: self.origin_slabref })?;
-             values.insert(slot, (subject_id,mrh));
+             // None is a tombstone, clearing the slot
+             let relation = visitor.visit_value_seed(OptionSeed(SubjectMRHSeed{ dest_slab: self.dest_slab, origin_slabref: self.origin_slabref }))?;
+             values.insert(slot, relation);
         }
 
         Ok(RelationSlotSubjectHead(values))
@@ -439,27 +507,58 @@ impl<'a> Visitor for MBPeeringSeed<'a> {
     fn visit_map<Visitor>(self, mut visitor: Visitor) -> Result<Self::Value, Visitor::Error>
         where Visitor: MapVisitor,
     {
-        let mut memo_ids : Option<MemoId> = None;
-        let mut subject_id: Option<Option<SubjectId>> = None;
-        let mut peerlist   : Option<MemoPeerList> = None;
+        let mut entries : Option<Vec<PeeringEntry>> = None;
         while let Some(key) = visitor.visit_key()? {
             match key {
-                'i' => memo_ids  = visitor.visit_value()?,
-                'j' => subject_id = visitor.visit_value()?,
-                'l' => peerlist  = Some(MemoPeerList::new(visitor.visit_value_seed(VecSeed(MemoPeerSeed{ dest_slab: self.dest_slab }))?)),
+                'p' => entries = Some(visitor.visit_value_seed(VecSeed(PeeringEntrySeed{ dest_slab: self.dest_slab }))?),
                 _   => {}
             }
         }
 
-        if memo_ids.is_some() && subject_id.is_some() && peerlist.is_some() {
-
-            Ok(MemoBody::Peering(
-                memo_ids.unwrap(),
-                subject_id.unwrap(),
-                peerlist.unwrap() ))
-        }else{
-            Err(DeError::invalid_length(0, &self))
+        match entries {
+            Some(entries) => Ok(MemoBody::Peering(entries)),
+            None          => Err(DeError::invalid_length(0, &self))
         }
+    }
+}
+
+impl<'a> DeserializeSeed for PeeringEntrySeed<'a> {
+    type Value = PeeringEntry;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+impl<'a> Visitor for PeeringEntrySeed<'a> {
+    type Value = PeeringEntry;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("MemoBody::Peering entry")
+    }
+    fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+        where V: SeqVisitor
+    {
+        let memo_id : MemoId = match visitor.visit()? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(0, &self));
+            }
+        };
+        let subject_id : Option<SubjectId> = match visitor.visit()? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(1, &self));
+            }
+        };
+        let peers : Vec<MemoPeer> = match visitor.visit_seed(VecSeed(MemoPeerSeed{ dest_slab: self.dest_slab }))? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(2, &self));
+            }
+        };
 
+        Ok((memo_id, subject_id, MemoPeerList::new(peers)))
     }
 }
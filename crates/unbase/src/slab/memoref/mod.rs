@@ -5,6 +5,10 @@ use crate::error::RetrieveError;
 
 use std::sync::{Arc,RwLock};
 use std::fmt;
+use std::time::Instant;
+
+use futures::future::{self,Either};
+use timer::Delay;
 
 
 #[derive(Clone)]
@@ -18,7 +22,7 @@ impl Deref for MemoRef {
 }
 
 pub struct MemoRefInner {
-    pub id:       MemoId,
+    pub id:       LazyMemoId,
     pub owning_slab_id: SlabId,
     pub subject_id: Option<SubjectId>,
     pub peerlist: RwLock<MemoPeerList>,
@@ -40,24 +44,51 @@ impl MemoRefPtr {
 }
 
 impl MemoRef {
+    /// The content addressed id of the memo, which is calculated the first time it's needed.
+    /// Only memos created by this slab may lack one, and those are always resident until they have one
+    pub fn id (&self) -> MemoId {
+        if let Some(memo_id) = self.0.id.get() {
+            return memo_id;
+        }
+        match self.get_memo_if_resident() {
+            Some(memo) => memo.id(),
+            None       => panic!("MemoRef.id - remote memo has no id")
+        }
+    }
+    /// The id of the memo, if it has been calculated
+    pub fn id_if_calculated (&self) -> Option<MemoId> {
+        self.0.id.get()
+    }
+    pub fn has_id (&self) -> bool {
+        self.id_if_calculated().is_some()
+    }
     pub fn to_head (&self) -> MemoRefHead {
         MemoRefHead::from_memoref(self.clone())
     }
-    pub fn apply_peers ( &self, apply_peerlist: &MemoPeerList ) -> bool {
+    /// Apply the peers of the given list, retaining at most `limit` peers. Returns true if the peerlist changed.
+    /// A list which mentions the owning slab is applied less that entry, which is returned as a finding
+    pub fn apply_peers ( &self, apply_peerlist: &MemoPeerList, limit: usize ) -> Result<bool,IntegrityFinding> {
 
         let peerlist = &mut *self.peerlist.write().unwrap();
         let mut acted = false;
-        for apply_peer in apply_peerlist.0.clone() {
+        let mut self_peer = false;
+        // The given list is ordered most recent first, as is ours
+        for apply_peer in apply_peerlist.0.iter().rev() {
             if apply_peer.slabref.slab_id == self.owning_slab_id {
-                println!("WARNING - not allowed to apply self-peer");
-                //panic!("memoref.apply_peers is not allowed to apply for self-peers");
+                self_peer = true;
                 continue;
             }
-            if peerlist.apply_peer(apply_peer) {
+            if peerlist.record_peer(apply_peer.clone()) {
                 acted = true;
             }
         }
-        acted
+        peerlist.prune(limit);
+
+        if self_peer {
+            Err(IntegrityFinding::SelfPeer{ memo_id: self.id() })
+        }else{
+            Ok(acted)
+        }
     }
     pub fn get_peerlist_for_peer (&self, my_ref: &SlabRef, maybe_dest_slab_id: Option<SlabId>) -> MemoPeerList {
         //println!("MemoRef({}).get_peerlist_for_peer({:?},{:?})", self.id, my_ref, maybe_dest_slab_id);
@@ -103,54 +134,97 @@ impl MemoRef {
 
         status
     }
+    /// Retrieve the memo, blocking the calling thread until it arrives. Must not be called from within an executor
     pub fn get_memo (&self, slab: &Slab) -> Result<Memo,RetrieveError> {
 //        println!("Slab({}).MemoRef({}).get_memo()", self.owning_slab_id, self.id );
         assert!(self.owning_slab_id == slab.id,"requesting slab does not match owning slab");
 
-        // This seems pretty crude, but using channels for now in the interest of expediency
-        let channel;
-        {
-            if let MemoRefPtr::Resident(ref memo) = *self.ptr.read().unwrap() {
-                return Ok(memo.clone());
-            }
-
-            if slab.request_memo(self) > 0 {
-                channel = slab.memo_wait_channel(self.id);
-            }else{
-                return Err(RetrieveError::NotFound)
-            }
+        if let Some(memo) = self.get_memo_if_resident() {
+            slab.touch_memo(self);
+            return Ok(memo);
         }
 
+        // Register the waiter before requesting, lest the memo arrive in between.
         // By sending the memo itself through the channel
         // we guarantee that there's no funny business with request / remotize timing
+        let channel = slab.memo_wait_channel(self.id());
 
+        if let Some(memo) = self.get_memo_if_resident() {
+            slab.touch_memo(self);
+            return Ok(memo);
+        }
+
+        let timeout = slab.config.retrieval_timeout();
+        let started = Instant::now();
+        slab.metrics.memo_retrievals.inc();
 
-        use std::time;
-        let timeout = time::Duration::from_millis(1000);
+        for _ in 0..slab.config.retrieval_attempts {
+            if slab.request_memo(self) == 0 {
+                return Err(RetrieveError::NotFound)
+            }
 
-        for _ in 0..3 {
             match channel.recv_timeout(timeout) {
-                Ok(memo)       =>{
+                Ok(memo) => {
                     //println!("Slab({}).MemoRef({}).get_memo() received memo: {}", self.owning_slab_id, self.id, memo.id );
+                    slab.metrics.memo_retrieval_latency.observe(started.elapsed());
                     return Ok(memo)
                 }
-                Err(rcv_error) => {
-
-                    use std::sync::mpsc::RecvTimeoutError::*;
-                    match rcv_error {
-                        Timeout => {}
-                        Disconnected => {
-                            return Err(RetrieveError::SlabError)
-                        }
-                    }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(RetrieveError::SlabError)
                 }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    slab.metrics.memo_retrieval_timeouts.inc();
+                    // have another go around
+                }
+            }
+        }
+
+        Err(RetrieveError::NotFoundByDeadline)
+    }
+    /// Retrieve the memo, requesting it from our peers if it isn't resident
+    pub async fn get_memo_async (&self, slab: &Slab) -> Result<Memo,RetrieveError> {
+//        println!("Slab({}).MemoRef({}).get_memo_async()", self.owning_slab_id, self.id );
+        assert!(self.owning_slab_id == slab.id,"requesting slab does not match owning slab");
+
+        if let Some(memo) = self.get_memo_if_resident() {
+            slab.touch_memo(self);
+            return Ok(memo);
+        }
+
+        let timeout = slab.config.retrieval_timeout();
+        let started = Instant::now();
+        slab.metrics.memo_retrievals.inc();
+
+        for _ in 0..slab.config.retrieval_attempts {
+            // Register the waiter before requesting, lest the memo arrive in between.
+            // By sending the memo itself through the channel
+            // we guarantee that there's no funny business with request / remotize timing
+            let channel = slab.memo_wait_future(self.id());
+
+            if let Some(memo) = self.get_memo_if_resident() {
+                slab.touch_memo(self);
+                return Ok(memo);
             }
 
-            // have another go around
-            if slab.request_memo( &self ) == 0 {
+            if slab.request_memo(self) == 0 {
                 return Err(RetrieveError::NotFound)
             }
 
+            match future::select(channel, Delay::new(timeout)).await {
+                Either::Left((Ok(memo), _)) => {
+                    //println!("Slab({}).MemoRef({}).get_memo_async() received memo: {}", self.owning_slab_id, self.id, memo.id );
+                    slab.metrics.memo_retrieval_latency.observe(started.elapsed());
+                    return Ok(memo)
+                }
+                Either::Left((Err(_canceled), _)) => {
+                    // The slab dropped our waiter without sending
+                    return Err(RetrieveError::SlabError)
+                }
+                Either::Right(_) => {
+                    slab.metrics.memo_retrieval_timeouts.inc();
+                    // have another go around
+                }
+            }
         }
 
         Err(RetrieveError::NotFoundByDeadline)
@@ -158,6 +232,12 @@ impl MemoRef {
     }
     pub fn descends (&self, memoref: &MemoRef, slab: &Slab) -> bool {
         assert!(self.owning_slab_id == slab.id);
+        if !self.is_resident() {
+            // Collected memos can't be retrieved, but we know their lineage
+            if let Some(descends) = slab.collected_memo_descends(&self.id(), memoref) {
+                return descends;
+            }
+        }
         match self.get_memo( slab ) {
             Ok(my_memo) => {
                 if my_memo.descends(&memoref, slab) {
@@ -171,55 +251,39 @@ impl MemoRef {
 
         false
     }
-    pub fn update_peer (&self, slabref: &SlabRef, status: MemoPeeringStatus) -> bool {
-
-        let mut acted = false;
-        let mut found = false;
-        let ref mut list = self.peerlist.write().unwrap().0;
-        for peer in list.iter_mut() {
-            if peer.slabref.slab_id == self.owning_slab_id {
-                println!("WARNING - not allowed to apply self-peer");
-                //panic!("memoref.update_peers is not allowed to apply for self-peers");
-                continue;
-            }
-            if peer.slabref.slab_id == slabref.slab_id {
-                found = true;
-                if peer.status != status {
-                    acted = true;
-                    peer.status = status.clone();
-                }
-                // TODO remove the peer entirely for MemoPeeringStatus::NonParticipating
-                // TODO prune excess peers - Should keep this list O(10) peers
-            }
+    /// Record the peering status of the given slab for this memo, retaining at most `limit` peers. Returns true if
+    /// the peerlist changed. Slabs which are NonParticipating are removed from the peerlist. The owning slab is refused
+    pub fn update_peer (&self, slabref: &SlabRef, status: MemoPeeringStatus, limit: usize) -> Result<bool,IntegrityFinding> {
+        if slabref.slab_id == self.owning_slab_id {
+            return Err(IntegrityFinding::SelfPeer{ memo_id: self.id() });
         }
 
-        if !found {
-            acted = true;
-            list.push(MemoPeer{
-                slabref: slabref.clone(),
-                status: status.clone()
-            })
-        }
+        let mut peerlist = self.peerlist.write().unwrap();
+        let acted = peerlist.record_peer(MemoPeer{
+            slabref: slabref.clone(),
+            status
+        });
+        peerlist.prune(limit);
 
-        acted
+        Ok(acted)
     }
     pub fn clone_for_slab (&self, from_slabref: &SlabRef, to_slab: &Slab, include_memo: bool ) -> Self{
         assert!(from_slabref.owning_slab_id == to_slab.id,"MemoRef clone_for_slab owning slab should be identical");
         assert!(from_slabref.slab_id != to_slab.id,       "MemoRef clone_for_slab dest slab should not be identical");
         //println!("Slab({}).Memoref.clone_for_slab({})", self.owning_slab_id, self.id);
 
-        // Because our from_slabref is already owned by the destination slab, there is no need to do peerlist.clone_for_slab
-        let peerlist = self.get_peerlist_for_peer(from_slabref, Some(to_slab.id));
+        // Our from_slabref is already owned by the destination slab, but the rest of our peers are not
+        let peerlist = self.get_peerlist_for_peer(from_slabref, Some(to_slab.id)).clone_for_slab(to_slab);
         //println!("Slab({}).Memoref.clone_for_slab({}) C -> {:?}", self.owning_slab_id, self.id, peerlist);
 
         // TODO - reduce the redundant work here. We're basically asserting the memoref twice
         let memoref = to_slab.assert_memoref(
-            self.id,
+            self.id(),
             self.subject_id,
             peerlist.clone(),
             match include_memo {
                 true => match *self.ptr.read().unwrap() {
-                    MemoRefPtr::Resident(ref m) => Some(m.clone_for_slab(from_slabref, to_slab, &peerlist)),
+                    MemoRefPtr::Resident(ref m) => m.clone_for_slab(from_slabref, to_slab, &peerlist),
                     MemoRefPtr::Remote          => None
                 },
                 false => None
@@ -231,6 +295,25 @@ impl MemoRef {
 
         memoref
     }
+    /// Clone several memorefs ( and their resident memos ) from another slab at once, such that
+    /// the receiving slab dispatches each affected subject only once. See Slab::reconstitute_memos
+    pub fn clone_all_for_slab (memorefs: &[MemoRef], from_slabref: &SlabRef, to_slab: &Slab ) {
+        assert!(from_slabref.owning_slab_id == to_slab.id,"MemoRef clone_all_for_slab owning slab should be identical");
+        assert!(from_slabref.slab_id != to_slab.id,       "MemoRef clone_all_for_slab dest slab should not be identical");
+
+        let mut batch = Vec::with_capacity(memorefs.len());
+
+        for memoref in memorefs {
+            let peerlist = memoref.get_peerlist_for_peer(from_slabref, Some(to_slab.id)).clone_for_slab(to_slab);
+
+            match memoref.get_memo_if_resident() {
+                Some(memo) => batch.push( memo.clone_parts_for_slab(from_slabref, to_slab, peerlist) ),
+                None       => { to_slab.assert_memoref(memoref.id(), memoref.subject_id, peerlist, None); }
+            }
+        }
+
+        to_slab.reconstitute_memos(batch, from_slabref);
+    }
 }
 
 impl fmt::Debug for MemoRef{
@@ -251,8 +334,11 @@ impl fmt::Debug for MemoRef{
 
 impl PartialEq for MemoRef {
     fn eq(&self, other: &MemoRef) -> bool {
-        // TODO: handle the comparision of pre-hashed memos as well as hashed memos
-        self.id == other.id
+        if Arc::ptr_eq(&self.0, &other.0) {
+            return true;
+        }
+        // Otherwise we have to compare ids, calculating them if need be
+        self.id() == other.id()
     }
 }
 
@@ -29,7 +29,7 @@ impl StatefulSerialize for MemoRef {
         use super::MemoRefPtr::*;
 
         let mut seq = serializer.serialize_seq(Some(4))?;
-        seq.serialize_element(&self.id)?;
+        seq.serialize_element(&self.id())?;
         seq.serialize_element(&self.subject_id)?;
         seq.serialize_element(&match &*self.ptr.read().unwrap() {
             &Remote      => false,
@@ -124,14 +124,17 @@ impl<'a> Visitor for MemoRefSeed<'a> {
            }
         };
 
-        peers.push(MemoPeer{
-            slabref: self.origin_slabref.clone(),
-            status: if has_memo {
-                MemoPeeringStatus::Resident
-            } else {
-                MemoPeeringStatus::Participating
-            }
-        });
+        // The origin slab is a peer, unless we're decoding our own stored records
+        if self.origin_slabref.slab_id != self.dest_slab.id {
+            peers.push(MemoPeer{
+                slabref: self.origin_slabref.clone(),
+                status: if has_memo {
+                    MemoPeeringStatus::Resident
+                } else {
+                    MemoPeeringStatus::Participating
+                }
+            });
+        }
 
         Ok(self.dest_slab.assert_memoref(memo_id, subject_id, MemoPeerList::new(peers), None).0 )
     }
@@ -1,9 +1,20 @@
 
 
 pub use self::common_structs::*;
+pub use self::durability::{DurabilityScore,DurabilityTargets};
+pub use self::eviction::{EvictionPolicy,PinnedMemos};
+use self::eviction::ResidencyTracker;
+use self::peering::PeeringQueue;
+use self::dispatch::DispatchPool;
+use self::plumtree::PlumtreeState;
+use self::selection::OutstandingRequests;
+pub use self::selection::{PeerSelector,PeerSelection,PeerCandidate,SelectionPurpose,RandomSelector,RoundRobinSelector,LowestLatencySelector,HighestLifetimeSelector};
+pub use self::egress::EmissionStrategy;
+pub use self::verify::{IntegrityFinding,IntegrityReport};
 pub use self::slabref::{SlabRef,SlabRefInner};
 pub use self::memoref::{MemoRef,MemoRefInner,MemoRefPtr};
-pub use self::memo::{MemoId,Memo,MemoInner,MemoBody};
+pub use self::memo::{MemoId,LazyMemoId,Memo,MemoInner,MemoBody,PeeringEntry};
+pub use self::core::MemoParts;
 pub use self::memoref::serde as memoref_serde;
 pub use self::memo::serde as memo_serde;
 
@@ -11,16 +22,21 @@ use crate::subject::SubjectId;
 use crate::memorefhead::*;
 use crate::context::{Context,WeakContext};
 use crate::network::{Network,Transmitter,TransmitterArgs,TransportAddress};
+use crate::config::SlabConfig;
+use crate::metrics::SlabMetrics;
+use self::storage::{StorageBackend,StorageKey};
 
 use std::ops::Deref;
-use std::sync::{Arc,Weak,RwLock,Mutex};
+use std::sync::{Arc,Weak,RwLock,Mutex,Condvar};
 use std::sync::mpsc;
-use std::sync::mpsc::channel;
+use std::sync::atomic::{AtomicBool,Ordering};
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 use std::fmt;
 use std::thread;
 
+use futures::channel::oneshot;
+
 
 // NOTE: All slab code is broken down into functional areas:
 mod basics;
@@ -33,73 +49,98 @@ mod common_structs;
 mod memo;
 mod slabref;
 mod memoref;
-
-pub type SlabId = u32;
+mod persistence;
+mod durability;
+mod eviction;
+mod gc;
+mod verify;
+mod peering;
+mod dispatch;
+mod plumtree;
+mod selection;
+pub mod storage;
+
+pub type SlabId = u64;
 
 #[derive(Clone)]
 pub struct Slab(Arc<SlabInner>);
 pub struct SlabInner{
     pub id: SlabId,
     memorefs_by_id: RwLock<HashMap<MemoId,MemoRef>>,
-    memo_wait_channels: Mutex<HashMap<MemoId,Vec<mpsc::Sender<Memo>>>>, // TODO: HERE HERE HERE - convert to per thread wait channel senders?
+    /// Memos created by this slab whose ids have yet to be calculated, by LazyMemoId::key. See memo/hash.rs
+    unhashed_memorefs: Mutex<HashMap<usize,MemoRef>>,
+    /// Memos discarded by garbage collection, and the ids of their parents
+    collected: RwLock<HashMap<MemoId,Vec<MemoId>>>,
+    memo_wait_channels: Mutex<HashMap<MemoId,Vec<MemoWaiter>>>,
     subject_subscriptions: RwLock<HashMap<SubjectId, Vec<WeakContext>>>,
 
     counters: RwLock<SlabCounters>,
+    durability_targets: RwLock<DurabilityTargets>,
+    residency: Mutex<ResidencyTracker>,
+    evicting: AtomicBool,
+    peering_queue: Mutex<PeeringQueue>,
+    /// Notified whenever peering is received, for the benefit of a shutdown awaiting acknowledgement of its handoff
+    peering_received: Condvar,
+    peering_received_lock: Mutex<()>,
+    plumtree: Mutex<PlumtreeState>,
 
-    memoref_dispatch_tx_channel: Option<Mutex<mpsc::Sender<MemoRef>>>,
-    memoref_dispatch_thread: RwLock<Option<thread::JoinHandle<()>>>,
+    dispatch_pool: DispatchPool,
 
     pub my_ref: SlabRef,
     peer_refs: RwLock<Vec<SlabRef>>,
+    peer_selector: RwLock<Box<dyn PeerSelector + Send + Sync>>,
+    peer_rtts: RwLock<HashMap<SlabId,std::time::Duration>>,
+    outstanding_requests: Mutex<OutstandingRequests>,
     net: Network,
+    storage: Box<dyn StorageBackend + Send + Sync>,
+    restoring: AtomicBool,
+    /// The head of each subject as found in storage when the slab was reopened, for new contexts to start from
+    restored_heads: RwLock<HashMap<SubjectId, MemoRefHead>>,
+    slab_id_conflicts: Mutex<Vec<SlabIdConflict>>,
+    /// Findings which arose in the course of operation ( eg: a self-peer which was refused ) for verify to report
+    integrity_findings: Mutex<Vec<IntegrityFinding>>,
+    /// Set once the slab has been shut down, after which it accepts no further memos
+    closed: AtomicBool,
+    pub config: SlabConfig,
+    pub metrics: SlabMetrics,
     pub dropping: bool
 }
 
+/// Persisted as ( subject_ids_reserved, memos_received, memos_redundantly_received ), and only now and then: whenever
+/// the subject id counter passes its reservation, on shutdown, and on drop
 struct SlabCounters{
-    last_memo_id: u32,
-    last_subject_id: u32,
+    last_subject_id: u64,
+    /// The subject id counter may advance this far before the counters must be persisted again. A slab reopened
+    /// after failing to persist its latest counts resumes from here, so no subject id is ever issued twice
+    subject_ids_reserved: u64,
     memos_received: u64,
     memos_redundantly_received: u64,
 }
 
+/// Those awaiting the arrival of a memo, whether a blocked thread or a future
+enum MemoWaiter {
+    Blocking(mpsc::Sender<Memo>),
+    Async(oneshot::Sender<Memo>)
+}
+
 #[derive(Clone)]
 pub struct WeakSlab{
-    pub id: u32,
+    pub id: SlabId,
     inner: Weak<SlabInner>
 }
 
 impl Slab {
-    fn check_peering_target( &self, memo: &Memo ) -> u8 {
-        if memo.does_peering() {
-            5
-        }else{
-            // This is necessary to prevent memo routing loops for now, as
-            // memoref.is_peered_with_slabref() obviously doesn't work for non-peered memos
-            // something here should change when we switch to gossip/plumtree, but
-            // I'm not sufficiently clear on that at the time of this writing
-            0
-        }
-    }
-/*    pub fn memo_durability_score( &self, _memo: &Memo ) -> u8 {
-        // TODO: devise durability_score algo
-        //       Should this number be inflated for memos we don't care about?
-        //       Or should that be a separate signal?
-
-        // Proposed factors:
-        // Estimated number of copies in the network (my count = count of first order peers + their counts weighted by: uptime?)
-        // Present diasporosity ( my diasporosity score = mean peer diasporosity scores weighted by what? )
-        0
-    }
-*/
     pub fn check_memo_waiters ( &self, memo: &Memo) {
-        match self.memo_wait_channels.lock().unwrap().entry(memo.id) {
+        match self.memo_wait_channels.lock().unwrap().entry(memo.id()) {
             Entry::Occupied(o) => {
-                for channel in o.get() {
+                for waiter in o.remove() {
                     // we don't care if it worked or not.
-                    // if the channel is closed, we're scrubbing it anyway
-                    channel.send(memo.clone()).ok();
+                    // if the waiter has given up, we're scrubbing it anyway
+                    match waiter {
+                        MemoWaiter::Blocking(channel) => { channel.send(memo.clone()).ok(); },
+                        MemoWaiter::Async(channel)    => { channel.send(memo.clone()).ok(); }
+                    }
                 }
-                o.remove();
             },
             Entry::Vacant(_) => {}
         };
@@ -127,16 +168,15 @@ impl Slab {
             //    C. Should we be sing that to determine the peered memo instead of the payload?
             //println!("MEOW {}, {:?}", my_ref );
 
-            let peering_memoref = self.new_memo(
-                None,
-                memoref.to_head(),
-                MemoBody::Peering(
-                    memoref.id,
+            self.send_peering(
+                origin_slabref,
+                Some(memoref),
+                vec![(
+                    memoref.id(),
                     memoref.subject_id,
                     memoref.get_peerlist_for_peer(&self.my_ref, Some(origin_slabref.slab_id))
-                )
+                )]
             );
-            origin_slabref.send( &self.my_ref, &peering_memoref );
         }
 
     }
@@ -147,11 +187,15 @@ impl Drop for SlabInner {
         self.dropping = true;
 
         //println!("# SlabInner({}).drop", self.id);
-        self.memoref_dispatch_tx_channel.take();
-        if let Some(t) = self.memoref_dispatch_thread.write().unwrap().take() {
-            t.join().expect("join memoref_dispatch_thread");
+        self.dispatch_pool.shutdown();
+        self.persist_counters();
+        if let Some(timer) = self.plumtree.get_mut().unwrap().take_timer() {
+            timer.stop();
         }
         self.net.deregister_local_slab(self.id);
+        if let Err(e) = self.storage.flush() {
+            println!("WARNING - Slab({}) failed to flush storage: {:?}", self.id, e );
+        }
         // TODO: Drop all observers? Or perhaps observers should drop the slab (weak ref directionality)
     }
 }
@@ -0,0 +1,194 @@
+/*
+    Peering updates are coalesced per destination slab into a single Peering memo.
+
+    The first update for a given destination is sent immediately (leading edge). Any further updates
+    for that destination within the peering window are accumulated, and flushed together by a background
+    thread once the window has elapsed. Updates for the same memo are merged along the way. As ever, the
+    memos being peered are the parents of the Peering memo which conveys them.
+
+    Local destinations are exempt, as there is no per-packet cost to speak of, and the simulator
+    depends on peering being conveyed promptly.
+*/
+
+use super::*;
+use std::mem;
+use std::time::{Duration,Instant};
+
+pub struct PeeringQueue {
+    pub window: Duration,
+    destinations: HashMap<SlabId, PeeringDestination>,
+    flusher_running: bool,
+}
+
+struct PeeringDestination {
+    slabref: SlabRef,
+    pending: Vec<PeeringEntry>,
+    parents: Vec<MemoRef>,
+    last_sent: Option<Instant>,
+}
+
+impl PeeringQueue {
+    pub fn new (window: Duration) -> Self {
+        PeeringQueue {
+            window,
+            destinations: HashMap::new(),
+            flusher_running: false,
+        }
+    }
+    /// Returns true if the entries should be sent immediately, otherwise they are accumulated
+    fn enqueue (&mut self, dest: &SlabRef, parent: Option<&MemoRef>, entries: &[PeeringEntry], now: Instant) -> bool {
+        let window = self.window;
+        let destination = self.destinations.entry(dest.slab_id).or_insert_with(|| {
+            PeeringDestination {
+                slabref: dest.clone(),
+                pending: Vec::new(),
+                parents: Vec::new(),
+                last_sent: None,
+            }
+        });
+
+        let window_elapsed = match destination.last_sent {
+            Some(last_sent) => now.duration_since(last_sent) >= window,
+            None            => true
+        };
+
+        if destination.pending.is_empty() && window_elapsed {
+            destination.last_sent = Some(now);
+            return true;
+        }
+
+        for entry in entries.iter() {
+            merge_entry(&mut destination.pending, entry);
+        }
+        if let Some(parent) = parent {
+            if !destination.parents.iter().any(|p| p == parent ) {
+                destination.parents.push(parent.clone());
+            }
+        }
+        false
+    }
+    /// Take all pending entries whose window has elapsed ( or all of them, if forced )
+    fn take_due (&mut self, now: Instant, force: bool) -> Vec<(SlabRef, Vec<MemoRef>, Vec<PeeringEntry>)> {
+        let window = self.window;
+        let mut due = Vec::new();
+
+        for destination in self.destinations.values_mut() {
+            if destination.pending.is_empty() {
+                continue;
+            }
+            let window_elapsed = match destination.last_sent {
+                Some(last_sent) => now.duration_since(last_sent) >= window,
+                None            => true
+            };
+            if force || window_elapsed {
+                destination.last_sent = Some(now);
+                due.push((
+                    destination.slabref.clone(),
+                    mem::take(&mut destination.parents),
+                    mem::take(&mut destination.pending)
+                ));
+            }
+        }
+
+        due
+    }
+    fn has_pending (&self) -> bool {
+        self.destinations.values().any(|d| !d.pending.is_empty() )
+    }
+}
+
+fn merge_entry (pending: &mut Vec<PeeringEntry>, entry: &PeeringEntry) {
+    if let Some(existing) = pending.iter_mut().find(|e| e.0 == entry.0 ) {
+        if existing.1.is_none() {
+            existing.1 = entry.1;
+        }
+        for peer in entry.2.iter() {
+            existing.2.apply_peer(peer.clone());
+        }
+        return;
+    }
+
+    pending.push(entry.clone());
+}
+
+impl Slab {
+    /// Convey peering updates to the given slab, coalescing them with other updates for the same destination.
+    /// The parent, if any, is the memo which occasioned the update
+    pub fn send_peering (&self, dest: &SlabRef, parent: Option<&MemoRef>, entries: Vec<PeeringEntry>) {
+        if entries.is_empty() {
+            return;
+        }
+
+        let send_now = if dest.get_return_address().is_local() {
+            true
+        } else {
+            let mut queue = self.peering_queue.lock().unwrap();
+            if queue.window == Duration::from_millis(0) {
+                true
+            } else {
+                let send_now = queue.enqueue(dest, parent, &entries, Instant::now());
+                if !send_now && !queue.flusher_running {
+                    queue.flusher_running = true;
+                    self.spawn_peering_flusher(queue.window);
+                }
+                send_now
+            }
+        };
+
+        if send_now {
+            self.send_peering_memo(dest, parent.into_iter().cloned().collect(), entries);
+        }
+    }
+    pub fn set_peering_window (&self, window: Duration) {
+        self.peering_queue.lock().unwrap().window = window;
+    }
+    /// Send all pending peering updates, regardless of the window
+    pub fn flush_peering (&self) {
+        let due = self.peering_queue.lock().unwrap().take_due(Instant::now(), true);
+        for (dest, parents, entries) in due {
+            self.send_peering_memo(&dest, parents, entries);
+        }
+    }
+    fn send_peering_memo (&self, dest: &SlabRef, parents: Vec<MemoRef>, entries: Vec<PeeringEntry>) {
+        let peering_memoref = self.new_memo_basic(
+            None,
+            MemoRefHead::new_from_vec(parents),
+            MemoBody::Peering(entries)
+        );
+        dest.send( &self.my_ref, &peering_memoref );
+        self.metrics.peering_memos_sent.inc();
+    }
+    fn spawn_peering_flusher (&self, window: Duration) {
+        let weak_self = self.weak();
+
+        // Runs only while there are updates pending, and is started again by send_peering as needed
+        thread::spawn(move || {
+            loop {
+                thread::sleep(window);
+
+                let slab = match weak_self.upgrade() {
+                    Some(slab) => slab,
+                    None       => break
+                };
+
+                let (due, finished) = {
+                    let mut queue = slab.peering_queue.lock().unwrap();
+                    let due = queue.take_due(Instant::now(), false);
+
+                    let finished = !queue.has_pending();
+                    if finished {
+                        queue.flusher_running = false;
+                    }
+                    (due, finished)
+                };
+
+                for (dest, parents, entries) in due {
+                    slab.send_peering_memo(&dest, parents, entries);
+                }
+                if finished {
+                    break;
+                }
+            }
+        });
+    }
+}
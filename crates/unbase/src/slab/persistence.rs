@@ -0,0 +1,204 @@
+use super::*;
+use super::storage::StorageError;
+use super::storage::serde::{StoredMemoSeed,StoredPeerListSeed};
+use crate::util::serde::*;
+
+use serde_json;
+use std::collections::HashSet;
+
+impl SlabInner {
+    /// On SlabInner rather than Slab, such that the counters may be persisted as the slab is dropped
+    pub (super) fn persist_counters (&self) {
+        if self.restoring.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let result = {
+            let counters = self.counters.read().unwrap();
+            serde_json::to_vec(&(counters.subject_ids_reserved, counters.memos_received, counters.memos_redundantly_received))
+                .map_err(|e| StorageError::Encoding(e.to_string()))
+        }.and_then(|bytes| self.storage.put(StorageKey::Counters, bytes));
+
+        if let Err(e) = result {
+            println!("WARNING - Slab({}) storage error: {:?}", self.id, e );
+        }
+    }
+}
+
+impl Slab {
+    /// Load and decode a single small record from storage, prior to the slab being constructed
+    pub (super) fn load_record<T> ( storage: &(dyn StorageBackend + Send + Sync), key: &StorageKey ) -> Result<Option<T>,StorageError>
+        where T: ::serde::Deserialize
+    {
+        match storage.get(key)? {
+            Some(bytes) => {
+                let value = serde_json::from_slice(&bytes).map_err(|e| StorageError::Corrupt(format!("{:?}: {}", key, e)))?;
+                Ok(Some(value))
+            },
+            None => Ok(None)
+        }
+    }
+    /// Reload all memos and peerlists from storage into this slab.
+    ///
+    /// Writes back to storage are suppressed for the duration, as everything being loaded is already there
+    pub (super) fn restore_from_storage (&self) -> Result<(),StorageError> {
+        self.restoring.store(true, Ordering::SeqCst);
+        let result = self.restore_records();
+        self.restoring.store(false, Ordering::SeqCst);
+        result
+    }
+    fn restore_records (&self) -> Result<(),StorageError> {
+        let keys = self.storage.keys()?;
+
+        // Peerlists first, so that the memos ( and their parents ) find their peers already in place
+        for key in keys.iter() {
+            if let &StorageKey::PeerList(memo_id) = key {
+                if let Some(bytes) = self.storage.get(key)? {
+                    let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+                    let (subject_id, peerlist) = StoredPeerListSeed{ dest_slab: self }.deserialize(&mut deserializer)
+                        .map_err(|e| StorageError::Corrupt(format!("{:?}: {}", key, e)))?;
+
+                    self.assert_memoref(memo_id, subject_id, peerlist, None);
+                }
+            }
+        }
+
+        // The query contexts which knew the latest head of each subject are gone, so we work out those heads from
+        // the memos themselves: those which are not the parent of another
+        let mut subject_memorefs : Vec<(SubjectId, MemoRef)> = Vec::new();
+        let mut parent_ids : HashSet<MemoId> = HashSet::new();
+
+        for key in keys.iter() {
+            if let &StorageKey::Memo(_) = key {
+                if let Some(bytes) = self.storage.get(key)? {
+                    let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+                    let memo = StoredMemoSeed{ dest_slab: self }.deserialize(&mut deserializer)
+                        .map_err(|e| StorageError::Corrupt(format!("{:?}: {}", key, e)))?;
+
+                    parent_ids.extend(memo.parents.memo_ids());
+                    let subject_id = memo.subject_id;
+                    let (memoref, _) = self.assert_memoref(memo.id(), memo.subject_id, MemoPeerList::new(vec![]), Some(memo));
+
+                    if let Some(subject_id) = subject_id {
+                        subject_memorefs.push((subject_id, memoref));
+                    }
+                }
+            }
+        }
+
+        let mut restored_heads : HashMap<SubjectId, Vec<MemoRef>> = HashMap::new();
+        for (subject_id, memoref) in subject_memorefs {
+            if !parent_ids.contains(&memoref.id()) {
+                restored_heads.entry(subject_id).or_default().push(memoref);
+            }
+        }
+        *self.restored_heads.write().unwrap() = restored_heads.into_iter()
+            .map(|(subject_id, memorefs)| (subject_id, MemoRefHead::new_from_vec(memorefs)) ).collect();
+
+        for key in keys.iter() {
+            if let &StorageKey::Collected(memo_id) = key {
+                if let Some(parent_ids) = Self::load_record::<Vec<MemoId>>(&*self.storage, key)? {
+                    self.collected.write().unwrap().insert(memo_id, parent_ids);
+                }
+            }
+        }
+
+        if let Some(memo_ids) = Self::load_record::<Vec<MemoId>>(&*self.storage, &StorageKey::RootIndexSeed)? {
+            let mut memorefs = Vec::with_capacity(memo_ids.len());
+            for memo_id in memo_ids {
+                match self.memorefs_by_id.read().unwrap().get(&memo_id) {
+                    Some(memoref) => memorefs.push(memoref.clone()),
+                    None          => return Err(StorageError::Corrupt(format!("root index seed references missing memo {:?}", memo_id)))
+                }
+            }
+
+            // These were a head when they were stored, so there's no need to re-apply them
+            let seed = MemoRefHead::new_from_vec(memorefs);
+
+            if seed.len() > 0 {
+                self.net.apply_root_index_seed( &self.presence_for_origin(&self.my_ref), &seed, &self.my_ref );
+            }
+        }
+
+        Ok(())
+    }
+    /// The head of each subject found in storage when this slab was reopened. Each new context starts from these,
+    /// as the context which knew them did not survive
+    pub fn restored_subject_heads (&self) -> Vec<(SubjectId, MemoRefHead)> {
+        self.restored_heads.read().unwrap().iter().map(|(subject_id, head)| (*subject_id, head.clone()) ).collect()
+    }
+    fn handle_storage_result (&self, result: Result<(),StorageError>) {
+        if let Err(e) = result {
+            println!("WARNING - Slab({}) storage error: {:?}", self.id, e );
+        }
+    }
+    fn encode_record<T> (&self, value: &T) -> Result<Vec<u8>,StorageError>
+        where T: ::serde::Serialize
+    {
+        serde_json::to_vec(value).map_err(|e| StorageError::Encoding(e.to_string()))
+    }
+    pub (super) fn persist_slab_id (&self) {
+        let result = self.encode_record(&self.id).and_then(|bytes| self.storage.put(StorageKey::SlabId, bytes));
+        self.handle_storage_result(result);
+    }
+    pub (super) fn persist_root_index_seed (&self) {
+        if self.restoring.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if let Some(seed) = self.get_root_index_seed() {
+            let result = self.encode_record(&seed.memo_ids())
+                .and_then(|bytes| self.storage.put(StorageKey::RootIndexSeed, bytes));
+            self.handle_storage_result(result);
+        }
+    }
+    /// Write the current state of a memoref through to storage: The memo itself if resident, and its peerlist
+    ///
+    /// Records are keyed by MemoId, so storage which retains records forgoes lazy hashing: each memo it keeps is
+    /// hashed as it is created or received, at the cost of one SHA-256 over the memo's contents. Control memos
+    /// ( requests, peering, presence, goodbyes and the like ) are transient, so they are neither kept nor hashed
+    pub (super) fn persist_memoref (&self, memoref: &MemoRef) {
+        if self.restoring.load(Ordering::SeqCst) || !self.storage.retains_records() {
+            return;
+        }
+        if let Some(memo) = memoref.get_memo_if_resident() {
+            if !memo.does_peering() {
+                return;
+            }
+        }
+
+        let return_address = TransportAddress::Local;
+        let helper = SerializeHelper{ dest_slab_id: &self.id, return_address: &return_address };
+
+        let result = match memoref.get_memo_if_resident() {
+            Some(memo) => self.encode_record(&SerializeWrapper(&memo, &helper))
+                              .and_then(|bytes| self.storage.put(StorageKey::Memo(memoref.id()), bytes)),
+            None       => self.storage.remove(&StorageKey::Memo(memoref.id()))
+        };
+        self.handle_storage_result(result);
+
+        self.persist_peerlist(memoref);
+    }
+    /// Replace the records of a garbage collected memo with the ids of its parents
+    pub (super) fn persist_collected_memo (&self, memo_id: MemoId, parent_ids: &Vec<MemoId>) {
+        let result = self.storage.remove(&StorageKey::Memo(memo_id))
+            .and_then(|_| self.storage.remove(&StorageKey::PeerList(memo_id)) )
+            .and_then(|_| self.encode_record(parent_ids) )
+            .and_then(|bytes| self.storage.put(StorageKey::Collected(memo_id), bytes));
+        self.handle_storage_result(result);
+    }
+    pub (super) fn persist_peerlist (&self, memoref: &MemoRef) {
+        if self.restoring.load(Ordering::SeqCst) || !self.storage.retains_records() {
+            return;
+        }
+
+        let return_address = TransportAddress::Local;
+        let helper = SerializeHelper{ dest_slab_id: &self.id, return_address: &return_address };
+
+        let result = {
+            let peerlist = memoref.peerlist.read().unwrap();
+            self.encode_record(&(memoref.subject_id, SerializeWrapper(&*peerlist, &helper)))
+        }.and_then(|bytes| self.storage.put(StorageKey::PeerList(memoref.id()), bytes));
+        self.handle_storage_result(result);
+    }
+}
@@ -0,0 +1,236 @@
+/*
+    Plumtree: Epidemic broadcast trees ( Leitão, Pereira & Rodrigues, 2007 )
+
+    Under the Plumtree emission strategy, each slab regards each of its peers as either eager or lazy.
+    Memos are pushed in full to eager peers, and merely announced to lazy peers by way of a peering update.
+
+    Receiving a memo which we already had means that it reached us by more than one path, so we prune the
+    redundant path by asking its sender to treat us as a lazy peer. The eager links thereby converge upon
+    a spanning tree, and the remaining links carry only announcements.
+
+    Should the tree be broken ( eg: by a departing slab ) memos will be announced to us without arriving.
+    Once the graft timeout elapses for such a memo, we graft: asking one of the slabs which announced it to
+    send it, and to treat us as an eager peer once more. This repairs the tree.
+
+    Peers are eager until they prove to be redundant.
+
+    The graft timer runs only while memos are missing, and is stopped when the slab is shut down or dropped.
+*/
+
+use super::*;
+use std::collections::HashSet;
+use std::time::{Duration,Instant};
+
+pub struct PlumtreeState {
+    /// Peers to which we only announce memos. All others are eager
+    lazy: HashSet<SlabId>,
+    /// Memos which were announced to us, but which have yet to arrive
+    missing: HashMap<MemoId, MissingMemo>,
+    /// None while the graft timer isn't running
+    timer: Option<GraftTimer>,
+}
+
+pub struct GraftTimer {
+    /// Dropping the sender stops the timer
+    stop:   mpsc::Sender<()>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl GraftTimer {
+    /// Stop the timer, and wait for it to finish
+    pub fn stop (self) {
+        drop(self.stop);
+        // The last reference to the slab may have been released by the timer itself
+        if self.handle.thread().id() != thread::current().id() {
+            self.handle.join().expect("join graft timer thread");
+        }
+    }
+}
+
+struct MissingMemo {
+    /// When we last announced, or grafted
+    since: Instant,
+    /// Slabs which announced the memo to us, in the order we heard from them
+    announcers: Vec<SlabRef>,
+}
+
+impl PlumtreeState {
+    pub fn new () -> Self {
+        PlumtreeState {
+            lazy: HashSet::new(),
+            missing: HashMap::new(),
+            timer: None,
+        }
+    }
+    pub fn take_timer (&mut self) -> Option<GraftTimer> {
+        self.timer.take()
+    }
+    /// Take the memos which have been missing for longer than the timeout, grouped by the slab from which to graft them.
+    /// Each is given until the next timeout to arrive from that slab, before we try the next announcer
+    fn take_due (&mut self, now: Instant, timeout: Duration) -> Vec<(SlabRef, Vec<MemoId>)> {
+        let mut due : Vec<(SlabRef, Vec<MemoId>)> = Vec::new();
+
+        for (memo_id, missing) in self.missing.iter_mut() {
+            if now.duration_since(missing.since) < timeout || missing.announcers.is_empty() {
+                continue;
+            }
+
+            let announcer = missing.announcers.remove(0);
+            missing.since = now;
+
+            match due.iter_mut().find(|d| d.0.slab_id == announcer.slab_id ) {
+                Some(d) => d.1.push(*memo_id),
+                None    => due.push((announcer, vec![*memo_id]))
+            }
+        }
+
+        // Nobody left to ask
+        self.missing.retain(|_, missing| !missing.announcers.is_empty() || now.duration_since(missing.since) < timeout );
+
+        due
+    }
+}
+
+impl Slab {
+    /// Push a memo to our eager peers, and announce it to our lazy peers. The slab from which we received it ( if any )
+    /// is skipped, as are those which we know to have it already
+    pub (super) fn plumtree_push (&self, memoref: &MemoRef, origin_slabref: Option<&SlabRef>) {
+        match memoref.get_memo_if_resident() {
+            Some(ref memo) if memo.does_peering() => {},
+            _                                     => return
+        }
+
+        let lazy = self.plumtree.lock().unwrap().lazy.clone();
+        let peers : Vec<SlabRef> = self.peer_refs.read().unwrap().iter()
+            .filter(|p| origin_slabref.is_none_or(|o| o.slab_id != p.slab_id ) )
+            .filter(|p| !memoref.is_peered_with_slabref(p) )
+            .cloned()
+            .collect();
+
+        for peer in peers.iter() {
+            if lazy.contains(&peer.slab_id) {
+                self.send_peering(peer, Some(memoref), vec![(
+                    memoref.id(),
+                    memoref.subject_id,
+                    memoref.get_peerlist_for_peer(&self.my_ref, Some(peer.slab_id))
+                )]);
+            } else {
+                peer.send( &self.my_ref, memoref );
+            }
+        }
+    }
+    /// A memo arrived from another slab. If it's new to us, its sender is an eager peer and we pass it along.
+    /// Otherwise the sender is redundant, and we prune it
+    pub (super) fn plumtree_receive (&self, memoref: &MemoRef, origin_slabref: &SlabRef, was_resident: bool) {
+        match memoref.get_memo_if_resident() {
+            Some(ref memo) if memo.does_peering() => {},
+            _                                     => return
+        }
+
+        let prune = {
+            let mut state = self.plumtree.lock().unwrap();
+            state.missing.remove(&memoref.id());
+
+            if was_resident {
+                state.lazy.insert(origin_slabref.slab_id)
+            } else {
+                state.lazy.remove(&origin_slabref.slab_id);
+                false
+            }
+        };
+
+        if was_resident {
+            if prune {
+                let prune_memoref = self.new_memo_basic_noparent(None, MemoBody::Prune(self.id));
+                origin_slabref.send( &self.my_ref, &prune_memoref );
+            }
+        } else {
+            self.plumtree_push(memoref, Some(origin_slabref));
+        }
+    }
+    /// A memo of which we had never heard was announced to us. Graft it if it doesn't arrive in time
+    pub (super) fn plumtree_announced (&self, memo_id: MemoId, announcer: &SlabRef) {
+        let mut state = self.plumtree.lock().unwrap();
+
+        let missing = state.missing.entry(memo_id).or_insert_with(|| {
+            MissingMemo {
+                since: Instant::now(),
+                announcers: Vec::new(),
+            }
+        });
+        if !missing.announcers.iter().any(|a| a.slab_id == announcer.slab_id ) {
+            missing.announcers.push(announcer.clone());
+        }
+
+        if state.timer.is_none() {
+            state.timer = Some(self.spawn_graft_timer(self.config.graft_timeout()));
+        }
+    }
+    /// The given slab was announced these memos, but never received them
+    pub (super) fn handle_graft (&self, memo_ids: &[MemoId], requesting_slabref: &SlabRef) {
+        self.plumtree.lock().unwrap().lazy.remove(&requesting_slabref.slab_id);
+
+        for memo_id in memo_ids {
+            let memoref = self.get_memoref(memo_id);
+            if let Some(memoref) = memoref {
+                if memoref.is_resident() {
+                    requesting_slabref.send( &self.my_ref, &memoref );
+                }
+            }
+        }
+    }
+    /// The given slab received memos from us redundantly
+    pub (super) fn handle_prune (&self, pruning_slabref: &SlabRef) {
+        self.plumtree.lock().unwrap().lazy.insert(pruning_slabref.slab_id);
+    }
+    /// Forget a departed peer
+    pub (super) fn plumtree_forget_peer (&self, slab_id: SlabId) {
+        let mut state = self.plumtree.lock().unwrap();
+        state.lazy.remove(&slab_id);
+        for missing in state.missing.values_mut() {
+            missing.announcers.retain(|a| a.slab_id != slab_id );
+        }
+    }
+    /// Graft any memos which have been missing for longer than the graft timeout
+    pub fn graft_missing_memos (&self) {
+        let due = self.plumtree.lock().unwrap().take_due(Instant::now(), self.config.graft_timeout());
+        for (announcer, memo_ids) in due {
+            self.plumtree.lock().unwrap().lazy.remove(&announcer.slab_id);
+
+            let graft_memoref = self.new_memo_basic_noparent(None, MemoBody::Graft(memo_ids, self.id));
+            announcer.send( &self.my_ref, &graft_memoref );
+        }
+    }
+    fn spawn_graft_timer (&self, timeout: Duration) -> GraftTimer {
+        let weak_self = self.weak();
+        let interval = (timeout / 2).max(Duration::from_millis(1));
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+
+        let handle = thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                let slab = match weak_self.upgrade() {
+                    Some(slab) => slab,
+                    None       => break
+                };
+                slab.graft_missing_memos();
+
+                // Nothing left to wait for. plumtree_announced starts us again as needed
+                let mut state = slab.plumtree.lock().unwrap();
+                if state.missing.is_empty() {
+                    state.timer = None;
+                    break;
+                }
+            }
+        });
+
+        GraftTimer{ stop: stop_tx, handle }
+    }
+    /// Stop the graft timer, if it's running, and wait for it to finish
+    pub (super) fn stop_graft_timer (&self) {
+        // Released before joining, as the timer may be waiting for it
+        let timer = self.plumtree.lock().unwrap().take_timer();
+        if let Some(timer) = timer {
+            timer.stop();
+        }
+    }
+}
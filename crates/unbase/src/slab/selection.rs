@@ -0,0 +1,190 @@
+/*
+    Peer selection: Which peers we send a memo to for the sake of its durability, and which peers
+    we ask for a memo which isn't resident, are chosen by the slab's PeerSelector.
+
+    Selectors are given the anticipated lifetime of each candidate ( from its SlabPresence ) and the
+    round trip time we've observed when retrieving memos from it, if any. Round trip times are measured
+    from the sending of a MemoRequest to the arrival of the requested memo, and smoothed over time.
+*/
+
+use super::*;
+use std::cmp::Reverse;
+use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::time::{Duration,Instant};
+
+/// Why peers are being selected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionPurpose {
+    /// To receive a replica of a memo
+    Replication,
+    /// To be asked for a memo which we lack
+    Retrieval,
+}
+
+/// A peer which may be selected, along with what we know of it
+#[derive(Clone, Debug)]
+pub struct PeerCandidate {
+    pub slabref:  SlabRef,
+    pub lifetime: SlabAnticipatedLifetime,
+    /// Smoothed round trip time of memo retrievals from this peer. None if we've yet to retrieve anything from it
+    pub rtt:      Option<Duration>,
+}
+
+pub trait PeerSelector {
+    /// Order the candidates by preference for the given purpose. The caller takes as many as it needs from the front
+    fn select (&self, candidates: Vec<PeerCandidate>, purpose: SelectionPurpose) -> Vec<PeerCandidate>;
+}
+
+/// The built-in peer selectors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PeerSelection {
+    Random,
+    RoundRobin,
+    LowestLatency,
+    HighestLifetime,
+}
+
+impl PeerSelection {
+    pub fn build (&self) -> Box<dyn PeerSelector + Send + Sync> {
+        match *self {
+            PeerSelection::Random          => Box::new(RandomSelector),
+            PeerSelection::RoundRobin      => Box::new(RoundRobinSelector::new()),
+            PeerSelection::LowestLatency   => Box::new(LowestLatencySelector),
+            PeerSelection::HighestLifetime => Box::new(HighestLifetimeSelector),
+        }
+    }
+}
+
+impl FromStr for PeerSelection {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "Random"          => Ok(PeerSelection::Random),
+            "RoundRobin"      => Ok(PeerSelection::RoundRobin),
+            "LowestLatency"   => Ok(PeerSelection::LowestLatency),
+            "HighestLifetime" => Ok(PeerSelection::HighestLifetime),
+            _                 => Err(())
+        }
+    }
+}
+
+/// Candidates in random order
+pub struct RandomSelector;
+
+impl PeerSelector for RandomSelector {
+    fn select (&self, mut candidates: Vec<PeerCandidate>, _purpose: SelectionPurpose) -> Vec<PeerCandidate> {
+        crate::util::random::shuffle(&mut candidates);
+        candidates
+    }
+}
+
+/// Candidates in their given order, rotated by one with each selection, such that load is spread evenly
+pub struct RoundRobinSelector {
+    next: AtomicUsize,
+}
+
+impl Default for RoundRobinSelector {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl RoundRobinSelector {
+    pub fn new () -> Self {
+        RoundRobinSelector{ next: AtomicUsize::new(0) }
+    }
+}
+
+impl PeerSelector for RoundRobinSelector {
+    fn select (&self, mut candidates: Vec<PeerCandidate>, _purpose: SelectionPurpose) -> Vec<PeerCandidate> {
+        if !candidates.is_empty() {
+            let offset = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+            candidates.rotate_left(offset);
+        }
+        candidates
+    }
+}
+
+/// Candidates with the lowest observed round trip time first. Those we've yet to measure come last
+pub struct LowestLatencySelector;
+
+impl PeerSelector for LowestLatencySelector {
+    fn select (&self, mut candidates: Vec<PeerCandidate>, _purpose: SelectionPurpose) -> Vec<PeerCandidate> {
+        candidates.sort_by_key(|c| (c.rtt.is_none(), c.rtt) );
+        candidates
+    }
+}
+
+/// Candidates with the longest anticipated lifetime first, such that replicas land on long-lived slabs
+/// rather than ephemeral ones. Ties are broken by round trip time
+pub struct HighestLifetimeSelector;
+
+impl PeerSelector for HighestLifetimeSelector {
+    fn select (&self, mut candidates: Vec<PeerCandidate>, _purpose: SelectionPurpose) -> Vec<PeerCandidate> {
+        candidates.sort_by_key(|c| (Reverse(c.lifetime.durability_weight()), c.rtt.is_none(), c.rtt) );
+        candidates
+    }
+}
+
+/// MemoRequests awaiting a reply, for the measurement of round trip times
+pub struct OutstandingRequests {
+    requests: HashMap<MemoId, (Instant, Vec<SlabId>)>,
+}
+
+impl OutstandingRequests {
+    pub fn new () -> Self {
+        OutstandingRequests{ requests: HashMap::new() }
+    }
+}
+
+impl Slab {
+    /// Replace the peer selector with which this slab was configured
+    pub fn set_peer_selector (&self, selector: Box<dyn PeerSelector + Send + Sync>) {
+        *self.peer_selector.write().unwrap() = selector;
+    }
+    /// The smoothed round trip time of memo retrievals from the given peer, if we've measured any
+    pub fn peer_rtt (&self, slab_id: SlabId) -> Option<Duration> {
+        self.peer_rtts.read().unwrap().get(&slab_id).cloned()
+    }
+    /// Order the given peers by preference for the given purpose, according to our peer selector
+    pub fn select_peers (&self, slabrefs: Vec<SlabRef>, purpose: SelectionPurpose) -> Vec<SlabRef> {
+        let candidates = {
+            let rtts = self.peer_rtts.read().unwrap();
+            slabrefs.into_iter().map(|slabref| {
+                PeerCandidate {
+                    lifetime: slabref.get_anticipated_lifetime(),
+                    rtt:      rtts.get(&slabref.slab_id).cloned(),
+                    slabref,
+                }
+            }).collect()
+        };
+
+        self.peer_selector.read().unwrap().select(candidates, purpose).into_iter().map(|c| c.slabref ).collect()
+    }
+    pub (super) fn note_memo_requested (&self, memo_id: MemoId, slab_ids: Vec<SlabId>) {
+        let now = Instant::now();
+        // Requests which go unanswered for this long aren't going to be answered
+        let expiry = self.config.retrieval_timeout() * self.config.retrieval_attempts.max(1);
+
+        let mut outstanding = self.outstanding_requests.lock().unwrap();
+        outstanding.requests.retain(|_, &mut (sent, _)| now.duration_since(sent) < expiry );
+        outstanding.requests.insert(memo_id, (now, slab_ids));
+    }
+    /// A memo arrived. If we asked the sender for it, measure the round trip
+    pub (super) fn note_memo_arrived (&self, memo_id: MemoId, origin_slabref: &SlabRef) {
+        let sent = match self.outstanding_requests.lock().unwrap().requests.remove(&memo_id) {
+            Some((sent, ref slab_ids)) if slab_ids.contains(&origin_slabref.slab_id) => sent,
+            _ => return
+        };
+
+        let sample = sent.elapsed();
+        let mut rtts = self.peer_rtts.write().unwrap();
+        let rtt = match rtts.get(&origin_slabref.slab_id) {
+            // Exponentially weighted moving average, as for TCP's SRTT
+            Some(previous) => (*previous * 7 + sample) / 8,
+            None           => sample
+        };
+        rtts.insert(origin_slabref.slab_id, rtt);
+    }
+}
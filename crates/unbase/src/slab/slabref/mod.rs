@@ -65,6 +65,28 @@ impl SlabRef{
         list.push(presence.clone());
         return true // We did a thing
     }
+    /// If the given presence places this slab at a remote address other than the one(s) we know it by,
+    /// then some other slab is claiming its id
+    pub fn conflicting_presence ( &self, presence: &SlabPresence ) -> Option<SlabIdConflict> {
+        if self.slab_id == self.owning_slab_id || presence.address.is_local() {
+            return None;
+        }
+
+        let known_addresses : Vec<TransportAddress> = self.presence.read().unwrap().iter()
+            .filter(|p| !p.address.is_local() )
+            .map(|p| p.address.clone() )
+            .collect();
+
+        if known_addresses.is_empty() || known_addresses.contains(&presence.address) {
+            return None;
+        }
+
+        Some(SlabIdConflict{
+            slab_id: self.slab_id,
+            known_addresses,
+            claimed_address: presence.address.clone(),
+        })
+    }
     pub fn get_presence_for_remote(&self, return_address: &TransportAddress) -> Vec<SlabPresence> {
 
         // If the slabref we are serializing is local, then construct a presence that refers to us
@@ -85,6 +107,13 @@ impl SlabRef{
             self.presence.read().unwrap().clone()
         }
     }
+    /// The longest anticipated lifetime of any of the presences we know for this slab
+    pub fn get_anticipated_lifetime(&self) -> SlabAnticipatedLifetime {
+        self.presence.read().unwrap().iter()
+            .map(|p| p.lifetime.clone())
+            .max_by_key(|l| l.durability_weight())
+            .unwrap_or(SlabAnticipatedLifetime::Unknown)
+    }
     pub fn compare(&self, other: &SlabRef) -> bool {
         // When comparing equality, we can skip the transmitter
         self.slab_id == other.slab_id && *self.presence.read().unwrap() == *other.presence.read().unwrap()
@@ -96,7 +125,10 @@ impl SlabRef{
 
         // IF this slabref points to the destination slab, then use to_sab.my_ref
         // because we know it exists already, and we're not allowed to assert a self-ref
-        if self.slab_id == to_slab.id {
+        if self.owning_slab_id == to_slab.id {
+            // Already theirs. Asserting it again would try to update its presence with its own
+            self.clone()
+        }else if self.slab_id == to_slab.id {
             to_slab.my_ref.clone()
         }else{
             //let address = &*self.return_address.read().unwrap();
@@ -0,0 +1,39 @@
+use super::*;
+
+/// Storage backend that intentionally forgets everything it is given.
+/// This is the default for slabs created with `Slab::new`
+#[derive(Clone)]
+pub struct Blackhole;
+
+impl Default for Blackhole {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Blackhole {
+    pub fn new () -> Self {
+        Blackhole
+    }
+}
+
+impl StorageBackend for Blackhole {
+    fn get ( &self, _key: &StorageKey ) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(None)
+    }
+    fn put ( &self, _key: StorageKey, _value: Vec<u8> ) -> Result<(), StorageError> {
+        Ok(())
+    }
+    fn remove ( &self, _key: &StorageKey ) -> Result<(), StorageError> {
+        Ok(())
+    }
+    fn keys ( &self ) -> Result<Vec<StorageKey>, StorageError> {
+        Ok(Vec::new())
+    }
+    fn flush ( &self ) -> Result<(), StorageError> {
+        Ok(())
+    }
+    fn retains_records ( &self ) -> bool {
+        false
+    }
+}
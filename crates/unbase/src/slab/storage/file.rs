@@ -0,0 +1,250 @@
+use super::*;
+use std::fs::{self,OpenOptions};
+use std::io::{Read,Write,Seek,SeekFrom};
+use std::path::{Path,PathBuf};
+use std::sync::{Arc,Mutex};
+use std::collections::HashMap;
+use serde_json;
+
+// Log entry layout:
+// [ op: u8 ][ key_len: u32 LE ][ value_len: u32 LE ][ key (json) ][ value ]
+const OP_PUT        : u8  = 1;
+const OP_REMOVE     : u8  = 2;
+const HEADER_LEN    : u64 = 9;
+
+// Don't bother compacting logs smaller than this
+const COMPACT_MIN_GARBAGE : u64 = 1024 * 1024;
+
+/// File backed storage: An append-only log of puts and removes, plus an in-memory index
+/// of the latest value offset for each key. The index is rebuilt by scanning the log on open.
+///
+/// Superseded entries are reclaimed by `compact`, which is also triggered automatically
+/// once more than half of the log is garbage.
+#[derive(Clone)]
+pub struct File {
+    shared: Arc<Mutex<FileInternal>>
+}
+
+struct FileInternal {
+    path:    PathBuf,
+    log:     fs::File,
+    index:   HashMap<StorageKey,Slot>,
+    end:     u64,
+    garbage: u64,
+}
+
+#[derive(Clone,Copy)]
+struct Slot {
+    value_offset: u64,
+    value_len:    u32,
+    entry_len:    u64
+}
+
+impl File {
+    /// Open (or create) the log file at the given path
+    pub fn open<P: AsRef<Path>> (path: P) -> Result<File,StorageError> {
+        let path = path.as_ref().to_path_buf();
+        let mut log = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+
+        let (index, end, garbage) = scan(&mut log)?;
+
+        // Discard any partially written entry at the tail ( crash during append )
+        log.set_len(end)?;
+
+        Ok(File {
+            shared: Arc::new(Mutex::new(FileInternal {
+                path,
+                log,
+                index,
+                end,
+                garbage
+            }))
+        })
+    }
+    /// Rewrite the log such that it contains only the live records
+    pub fn compact (&self) -> Result<(),StorageError> {
+        self.shared.lock().unwrap().compact()
+    }
+    /// Size of the log in bytes, including garbage
+    pub fn log_len (&self) -> u64 {
+        self.shared.lock().unwrap().end
+    }
+}
+
+impl StorageBackend for File {
+    fn get ( &self, key: &StorageKey ) -> Result<Option<Vec<u8>>, StorageError> {
+        let mut shared = self.shared.lock().unwrap();
+        let slot = match shared.index.get(key) {
+            Some(slot) => *slot,
+            None       => return Ok(None)
+        };
+
+        Ok(Some(shared.read_value(&slot)?))
+    }
+    fn put ( &self, key: StorageKey, value: Vec<u8> ) -> Result<(), StorageError> {
+        let mut shared = self.shared.lock().unwrap();
+        let slot = shared.append(OP_PUT, &key, &value)?;
+
+        if let Some(old) = shared.index.insert(key, slot) {
+            shared.garbage += old.entry_len;
+        }
+
+        shared.conditionally_compact()
+    }
+    fn remove ( &self, key: &StorageKey ) -> Result<(), StorageError> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(old) = shared.index.remove(key) {
+            let slot = shared.append(OP_REMOVE, key, &[])?;
+            shared.garbage += old.entry_len + slot.entry_len;
+        }
+
+        shared.conditionally_compact()
+    }
+    fn keys ( &self ) -> Result<Vec<StorageKey>, StorageError> {
+        Ok(self.shared.lock().unwrap().index.keys().cloned().collect())
+    }
+    fn flush ( &self ) -> Result<(), StorageError> {
+        self.shared.lock().unwrap().log.sync_data()?;
+        Ok(())
+    }
+}
+
+impl FileInternal {
+    fn append (&mut self, op: u8, key: &StorageKey, value: &[u8]) -> Result<Slot,StorageError> {
+        let entry = encode_entry(op, key, value)?;
+
+        self.log.seek(SeekFrom::Start(self.end))?;
+        self.log.write_all(&entry)?;
+
+        let slot = Slot {
+            value_offset: self.end + (entry.len() - value.len()) as u64,
+            value_len:    value.len() as u32,
+            entry_len:    entry.len() as u64
+        };
+        self.end += slot.entry_len;
+
+        Ok(slot)
+    }
+    fn read_value (&mut self, slot: &Slot) -> Result<Vec<u8>,StorageError> {
+        let mut value = vec![0u8; slot.value_len as usize];
+        self.log.seek(SeekFrom::Start(slot.value_offset))?;
+        self.log.read_exact(&mut value)?;
+        Ok(value)
+    }
+    fn conditionally_compact (&mut self) -> Result<(),StorageError> {
+        if self.garbage > COMPACT_MIN_GARBAGE && self.garbage * 2 > self.end {
+            self.compact()
+        }else{
+            Ok(())
+        }
+    }
+    fn compact (&mut self) -> Result<(),StorageError> {
+        let tmp_path = self.path.with_extension("compact");
+        let mut tmp = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&tmp_path)?;
+
+        let mut index = HashMap::with_capacity(self.index.len());
+        let mut end = 0u64;
+
+        let live : Vec<(StorageKey,Slot)> = self.index.iter().map(|(k,s)| (k.clone(), *s)).collect();
+        for (key, slot) in live {
+            let value = self.read_value(&slot)?;
+            let entry = encode_entry(OP_PUT, &key, &value)?;
+            tmp.write_all(&entry)?;
+
+            index.insert(key, Slot {
+                value_offset: end + (entry.len() - value.len()) as u64,
+                value_len:    value.len() as u32,
+                entry_len:    entry.len() as u64
+            });
+            end += entry.len() as u64;
+        }
+
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.log     = tmp;
+        self.index   = index;
+        self.end     = end;
+        self.garbage = 0;
+
+        Ok(())
+    }
+}
+
+fn encode_entry (op: u8, key: &StorageKey, value: &[u8]) -> Result<Vec<u8>,StorageError> {
+    let key_bytes = serde_json::to_vec(key).map_err(|e| StorageError::Encoding(e.to_string()))?;
+
+    let mut entry = Vec::with_capacity(HEADER_LEN as usize + key_bytes.len() + value.len());
+    entry.push(op);
+    entry.extend_from_slice(&(key_bytes.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&key_bytes);
+    entry.extend_from_slice(value);
+
+    Ok(entry)
+}
+
+fn read_u32 (bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[0..4]);
+    u32::from_le_bytes(buf)
+}
+
+/// Rebuild the index by replaying the log. Returns the index, the offset of the end of the
+/// last complete entry, and the number of bytes occupied by superseded entries
+fn scan (log: &mut fs::File) -> Result<(HashMap<StorageKey,Slot>, u64, u64),StorageError> {
+    let total = log.metadata()?.len();
+    log.seek(SeekFrom::Start(0))?;
+
+    let mut index : HashMap<StorageKey,Slot> = HashMap::new();
+    let mut garbage = 0u64;
+    let mut offset = 0u64;
+    let mut header = [0u8; HEADER_LEN as usize];
+
+    while offset + HEADER_LEN <= total {
+        log.read_exact(&mut header)?;
+
+        let op        = header[0];
+        let key_len   = read_u32(&header[1..5]) as u64;
+        let value_len = read_u32(&header[5..9]);
+        let entry_len = HEADER_LEN + key_len + value_len as u64;
+
+        if offset + entry_len > total {
+            // Torn write at the tail of the log
+            break;
+        }
+
+        let mut key_bytes = vec![0u8; key_len as usize];
+        log.read_exact(&mut key_bytes)?;
+        let key : StorageKey = serde_json::from_slice(&key_bytes)
+            .map_err(|e| StorageError::Corrupt(format!("undecodable key at offset {}: {}", offset, e)))?;
+
+        log.seek(SeekFrom::Current(value_len as i64))?;
+
+        match op {
+            OP_PUT => {
+                let slot = Slot {
+                    value_offset: offset + HEADER_LEN + key_len,
+                    value_len,
+                    entry_len
+                };
+                if let Some(old) = index.insert(key, slot) {
+                    garbage += old.entry_len;
+                }
+            }
+            OP_REMOVE => {
+                if let Some(old) = index.remove(&key) {
+                    garbage += old.entry_len;
+                }
+                garbage += entry_len;
+            }
+            _ => {
+                return Err(StorageError::Corrupt(format!("unknown op {} at offset {}", op, offset)));
+            }
+        }
+
+        offset += entry_len;
+    }
+
+    Ok((index, offset, garbage))
+}
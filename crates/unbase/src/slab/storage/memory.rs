@@ -0,0 +1,52 @@
+use super::*;
+use std::sync::{Arc,RwLock};
+use std::collections::HashMap;
+
+/// Volatile storage backend.
+///
+/// Clones share the same underlying records, so a slab may be dropped and reopened
+/// from a clone of the same `Memory` within a single process. Mostly useful for testing.
+#[derive(Clone)]
+pub struct Memory {
+    records: Arc<RwLock<HashMap<StorageKey, Vec<u8>>>>
+}
+
+impl Default for Memory {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+impl Memory {
+    pub fn new () -> Self {
+        Memory {
+            records: Arc::new(RwLock::new(HashMap::new()))
+        }
+    }
+    pub fn len (&self) -> usize {
+        self.records.read().unwrap().len()
+    }
+    pub fn is_empty (&self) -> bool {
+        self.records.read().unwrap().is_empty()
+    }
+}
+
+impl StorageBackend for Memory {
+    fn get ( &self, key: &StorageKey ) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.records.read().unwrap().get(key).cloned())
+    }
+    fn put ( &self, key: StorageKey, value: Vec<u8> ) -> Result<(), StorageError> {
+        self.records.write().unwrap().insert(key, value);
+        Ok(())
+    }
+    fn remove ( &self, key: &StorageKey ) -> Result<(), StorageError> {
+        self.records.write().unwrap().remove(key);
+        Ok(())
+    }
+    fn keys ( &self ) -> Result<Vec<StorageKey>, StorageError> {
+        Ok(self.records.read().unwrap().keys().cloned().collect())
+    }
+    fn flush ( &self ) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+//! Pluggable storage backends for the contents of a `Slab`.
+//!
+//! The Slab continues to keep its working set in `memorefs_by_id`, but every memo, peerlist,
+//! counter and root index seed change is written through to a `StorageBackend`, such that the
+//! slab may later be reopened with `Slab::new_with_storage`.
+
+mod blackhole;
+mod memory;
+mod file;
+pub mod serde;
+
+pub use self::blackhole::Blackhole;
+pub use self::memory::Memory;
+pub use self::file::File;
+pub use crate::error::StorageError;
+
+use crate::slab::{MemoId};
+
+/// Identifies a single record within a storage backend
+/// Serialized by hand, in the externally tagged form: eg: "Counters" or {"Memo":[...]}. See serde.rs
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum StorageKey {
+    SlabId,
+    Counters,
+    RootIndexSeed,
+    Memo(MemoId),
+    PeerList(MemoId),
+    /// The parent ids of a memo which was garbage collected
+    Collected(MemoId),
+}
+
+/// A trait for storage backends to implement
+///
+/// Backends are simple key-value stores. Encoding and decoding of the records is done by the Slab.
+pub trait StorageBackend {
+    /// Retrieve the record for a given key, if present
+    fn get    ( &self, key: &StorageKey ) -> Result<Option<Vec<u8>>, StorageError>;
+    /// Insert or overwrite the record for a given key
+    fn put    ( &self, key: StorageKey, value: Vec<u8> ) -> Result<(), StorageError>;
+    /// Remove the record for a given key. Removing a nonexistent key is not an error
+    fn remove ( &self, key: &StorageKey ) -> Result<(), StorageError>;
+    /// List all keys presently stored
+    fn keys   ( &self ) -> Result<Vec<StorageKey>, StorageError>;
+    /// Ensure all previous writes have been handed off to durable storage
+    fn flush  ( &self ) -> Result<(), StorageError>;
+    /// Whether records put are kept at all. If not, the slab needn't bother encoding them, nor hashing memos to key them
+    fn retains_records ( &self ) -> bool {
+        true
+    }
+}
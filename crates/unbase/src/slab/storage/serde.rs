@@ -0,0 +1,203 @@
+use crate::slab::*;
+use crate::slab::memo_serde::MemoBodySeed;
+use crate::slab::memoref_serde::MemoPeerSeed;
+use crate::memorefhead::serde::MemoRefHeadSeed;
+use crate::subject::SubjectId;
+use crate::util::serde::*;
+use super::StorageKey;
+
+use ::serde::{Serialize,Deserialize};
+use ::serde::de::{EnumVisitor,VariantVisitor};
+use std::fmt;
+
+/// Decodes a Memo previously encoded for storage by the same slab.
+///
+/// Unlike MemoSeed, the memo is not reconstituted, because none of the ingress side effects
+/// ( emission, peering, dispatch ) are desirable when restoring a slab from storage
+pub struct StoredMemoSeed<'a> { pub dest_slab: &'a Slab }
+
+/// Decodes the subject id and MemoPeerList stored for a given MemoRef
+pub struct StoredPeerListSeed<'a> { pub dest_slab: &'a Slab }
+
+impl<'a> DeserializeSeed for StoredMemoSeed<'a> {
+    type Value = Memo;
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'a> Visitor for StoredMemoSeed<'a> {
+    type Value = Memo;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("stored Memo")
+    }
+
+    fn visit_seq<V> (self, mut visitor: V) -> Result<Self::Value, V::Error>
+        where V: SeqVisitor
+    {
+        // We were the origin of our own records
+        let origin_slabref = &self.dest_slab.my_ref;
+
+        let id: MemoId = match visitor.visit()? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(0, &self));
+            }
+        };
+        let subject_id: Option<SubjectId> = match visitor.visit()? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(1, &self));
+            }
+        };
+        let body: MemoBody = match visitor.visit_seed(MemoBodySeed{ dest_slab: self.dest_slab, origin_slabref })? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(2, &self));
+            }
+        };
+        let parents: MemoRefHead = match visitor.visit_seed(MemoRefHeadSeed{ dest_slab: self.dest_slab, origin_slabref })? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(3, &self));
+            }
+        };
+
+        Ok(Memo::new(MemoInner {
+            id:             LazyMemoId::known(id),
+            owning_slab_id: self.dest_slab.id,
+            subject_id,
+            parents,
+            body
+        }))
+    }
+}
+
+impl<'a> DeserializeSeed for StoredPeerListSeed<'a> {
+    type Value = (Option<SubjectId>, MemoPeerList);
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'a> Visitor for StoredPeerListSeed<'a> {
+    type Value = (Option<SubjectId>, MemoPeerList);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("stored MemoPeerList")
+    }
+
+    fn visit_seq<V> (self, mut visitor: V) -> Result<Self::Value, V::Error>
+        where V: SeqVisitor
+    {
+        let subject_id: Option<SubjectId> = match visitor.visit()? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(0, &self));
+            }
+        };
+        let peers: Vec<MemoPeer> = match visitor.visit_seed(VecSeed(MemoPeerSeed{ dest_slab: self.dest_slab }))? {
+            Some(value) => value,
+            None => {
+                return Err(DeError::invalid_length(1, &self));
+            }
+        };
+
+        Ok((subject_id, MemoPeerList::new(peers)))
+    }
+}
+
+enum StorageKeyVariant {
+    SlabId,
+    Counters,
+    RootIndexSeed,
+    Memo,
+    PeerList,
+    Collected
+}
+
+const STORAGEKEY_VARIANTS: &[&str] = &[
+    "SlabId",
+    "Counters",
+    "RootIndexSeed",
+    "Memo",
+    "PeerList",
+    "Collected"
+];
+
+impl Serialize for StorageKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        match *self {
+            StorageKey::SlabId                 => serializer.serialize_unit_variant("StorageKey", 0, "SlabId"),
+            StorageKey::Counters               => serializer.serialize_unit_variant("StorageKey", 1, "Counters"),
+            StorageKey::RootIndexSeed          => serializer.serialize_unit_variant("StorageKey", 2, "RootIndexSeed"),
+            StorageKey::Memo(ref memo_id)      => serializer.serialize_newtype_variant("StorageKey", 3, "Memo", memo_id),
+            StorageKey::PeerList(ref memo_id)  => serializer.serialize_newtype_variant("StorageKey", 4, "PeerList", memo_id),
+            StorageKey::Collected(ref memo_id) => serializer.serialize_newtype_variant("StorageKey", 5, "Collected", memo_id),
+        }
+    }
+}
+
+impl Deserialize for StorageKey {
+    fn deserialize<D>(deserializer: D) -> Result<StorageKey, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize_enum("StorageKey", STORAGEKEY_VARIANTS, StorageKeyVisitor)
+    }
+}
+struct StorageKeyVisitor;
+impl Visitor for StorageKeyVisitor {
+    type Value = StorageKey;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+       formatter.write_str("StorageKey")
+    }
+    fn visit_enum<V>(self, visitor: V) -> Result<StorageKey, V::Error>
+        where V: EnumVisitor
+    {
+        match visitor.visit_variant()? {
+            (StorageKeyVariant::SlabId,        variant) => variant.visit_unit().map(|_| StorageKey::SlabId),
+            (StorageKeyVariant::Counters,      variant) => variant.visit_unit().map(|_| StorageKey::Counters),
+            (StorageKeyVariant::RootIndexSeed, variant) => variant.visit_unit().map(|_| StorageKey::RootIndexSeed),
+            (StorageKeyVariant::Memo,          variant) => variant.visit_newtype().map(StorageKey::Memo),
+            (StorageKeyVariant::PeerList,      variant) => variant.visit_newtype().map(StorageKey::PeerList),
+            (StorageKeyVariant::Collected,     variant) => variant.visit_newtype().map(StorageKey::Collected),
+        }
+    }
+}
+
+impl Deserialize for StorageKeyVariant {
+    fn deserialize<D>(deserializer: D) -> Result<StorageKeyVariant, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize(StorageKeyVariantVisitor)
+    }
+}
+struct StorageKeyVariantVisitor;
+impl Visitor for StorageKeyVariantVisitor {
+    type Value = StorageKeyVariant;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+       formatter.write_str("StorageKey Variant")
+    }
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where E: DeError
+    {
+        match value {
+            "SlabId"        => Ok(StorageKeyVariant::SlabId),
+            "Counters"      => Ok(StorageKeyVariant::Counters),
+            "RootIndexSeed" => Ok(StorageKeyVariant::RootIndexSeed),
+            "Memo"          => Ok(StorageKeyVariant::Memo),
+            "PeerList"      => Ok(StorageKeyVariant::PeerList),
+            "Collected"     => Ok(StorageKeyVariant::Collected),
+            _ => Err(DeError::unknown_variant(value, STORAGEKEY_VARIANTS)),
+        }
+    }
+}
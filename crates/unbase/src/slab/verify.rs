@@ -0,0 +1,202 @@
+/*
+    Integrity checking: Slab::verify walks memorefs_by_id and the known heads, reporting anything which
+    shouldn't be possible as a structured finding. Nothing is retrieved from other slabs in the process,
+    and nothing is repaired. Anything refused in the course of operation ( eg: a peerlist entry naming
+    this slab ) is recorded as a finding at the time, and reported alongside.
+
+    Intended for use in tests and CI, and for checking a stored slab after a crash ( see the unbase-fsck binary )
+*/
+
+use super::*;
+use std::collections::HashSet;
+
+/// Something found to be amiss by Slab::verify
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntegrityFinding {
+    /// The memo's contents do not hash to its id
+    MemoIdMismatch{ memo_id: MemoId, calculated_id: MemoId },
+    /// The memoref or memo disagree about the subject to which the memo belongs
+    SubjectMismatch{ memo_id: MemoId, memoref_subject_id: Option<SubjectId>, memo_subject_id: Option<SubjectId> },
+    /// A memoref, memo or slabref held by this slab claims to be owned by another
+    ForeignOwner{ memo_id: Option<MemoId>, owning_slab_id: SlabId },
+    /// The peerlist of a memo mentions this slab
+    SelfPeer{ memo_id: MemoId },
+    /// A parent of a resident memo is neither resident, collected, nor peered with any slab which might have it
+    UnresolvableParent{ memo_id: MemoId, parent_id: MemoId },
+    /// A head contains an entry which descends another
+    MutuallyDescendingHead{ subject_id: Option<SubjectId>, memo_id: MemoId, descended_memo_id: MemoId },
+}
+
+/// The outcome of Slab::verify
+#[derive(Debug)]
+pub struct IntegrityReport {
+    pub slab_id: SlabId,
+    /// The number of memorefs checked
+    pub memorefs: usize,
+    /// The number of heads checked
+    pub heads: usize,
+    pub findings: Vec<IntegrityFinding>,
+}
+
+impl IntegrityReport {
+    pub fn is_clean (&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+fn short_id (memo_id: &MemoId) -> String {
+    memo_id[0..8].iter().map(|b| format!("{:02x}", b) ).collect()
+}
+
+impl fmt::Display for IntegrityFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntegrityFinding::MemoIdMismatch{ ref memo_id, ref calculated_id } =>
+                write!(f, "memo {} hashes to {}", short_id(memo_id), short_id(calculated_id)),
+            IntegrityFinding::SubjectMismatch{ ref memo_id, memoref_subject_id, memo_subject_id } =>
+                write!(f, "memo {} belongs to subject {:?}, but its memoref says {:?}", short_id(memo_id), memo_subject_id, memoref_subject_id),
+            IntegrityFinding::ForeignOwner{ memo_id: Some(ref memo_id), owning_slab_id } =>
+                write!(f, "memo {} references something owned by slab {}", short_id(memo_id), owning_slab_id),
+            IntegrityFinding::ForeignOwner{ memo_id: None, owning_slab_id } =>
+                write!(f, "peer slabref is owned by slab {}", owning_slab_id),
+            IntegrityFinding::SelfPeer{ ref memo_id } =>
+                write!(f, "memo {} lists this slab as a peer", short_id(memo_id)),
+            IntegrityFinding::UnresolvableParent{ ref memo_id, ref parent_id } =>
+                write!(f, "memo {} has parent {} which cannot be resolved", short_id(memo_id), short_id(parent_id)),
+            IntegrityFinding::MutuallyDescendingHead{ subject_id, ref memo_id, ref descended_memo_id } =>
+                write!(f, "head of subject {:?} contains memo {} which descends {}", subject_id, short_id(memo_id), short_id(descended_memo_id)),
+        }
+    }
+}
+
+impl Slab {
+    /// Check the coherence of this slab's state. Heads which are being modified concurrently may be skipped
+    pub fn verify (&self) -> IntegrityReport {
+        let mut findings = self.integrity_findings.lock().unwrap().clone();
+
+        let memorefs : Vec<MemoRef> = self.all_memorefs();
+        for memoref in memorefs.iter() {
+            self.verify_memoref(memoref, &mut findings);
+        }
+
+        for peer_ref in self.peer_refs.read().unwrap().iter() {
+            if peer_ref.owning_slab_id != self.id {
+                findings.push(IntegrityFinding::ForeignOwner{ memo_id: None, owning_slab_id: peer_ref.owning_slab_id });
+            }
+        }
+
+        let heads = match self.pinned_memos() {
+            Some(pinned) => pinned.heads,
+            None         => Vec::new()
+        };
+        for head in heads.iter() {
+            self.verify_head(head, &mut findings);
+        }
+
+        IntegrityReport {
+            slab_id:  self.id,
+            memorefs: memorefs.len(),
+            heads:    heads.len(),
+            findings,
+        }
+    }
+    /// Record a finding which arose in the course of operation, to be reported by verify
+    pub (super) fn note_integrity_finding (&self, finding: IntegrityFinding) {
+        let mut findings = self.integrity_findings.lock().unwrap();
+        if !findings.contains(&finding) {
+            findings.push(finding);
+        }
+    }
+    fn verify_memoref (&self, memoref: &MemoRef, findings: &mut Vec<IntegrityFinding>) {
+        let memo_id = memoref.id();
+
+        if memoref.owning_slab_id != self.id {
+            findings.push(IntegrityFinding::ForeignOwner{ memo_id: Some(memo_id), owning_slab_id: memoref.owning_slab_id });
+        }
+
+        for peer in memoref.peerlist.read().unwrap().iter() {
+            if peer.slabref.slab_id == self.id {
+                findings.push(IntegrityFinding::SelfPeer{ memo_id });
+            }
+            if peer.slabref.owning_slab_id != self.id {
+                findings.push(IntegrityFinding::ForeignOwner{ memo_id: Some(memo_id), owning_slab_id: peer.slabref.owning_slab_id });
+            }
+        }
+
+        let memo = match memoref.get_memo_if_resident() {
+            Some(memo) => memo,
+            None       => return
+        };
+
+        if memo.owning_slab_id != self.id {
+            findings.push(IntegrityFinding::ForeignOwner{ memo_id: Some(memo_id), owning_slab_id: memo.owning_slab_id });
+        }
+        if memo.subject_id != memoref.subject_id {
+            findings.push(IntegrityFinding::SubjectMismatch{ memo_id, memoref_subject_id: memoref.subject_id, memo_subject_id: memo.subject_id });
+        }
+
+        let calculated_id = Memo::calculate_id(memo.subject_id, &memo.parents, &memo.body);
+        if calculated_id != memo_id {
+            findings.push(IntegrityFinding::MemoIdMismatch{ memo_id, calculated_id });
+        }
+
+        for parent in memo.parents.iter() {
+            if parent.owning_slab_id != self.id {
+                findings.push(IntegrityFinding::ForeignOwner{ memo_id: Some(memo_id), owning_slab_id: parent.owning_slab_id });
+            }
+            if !self.memoref_is_resolvable(parent) {
+                findings.push(IntegrityFinding::UnresolvableParent{ memo_id, parent_id: parent.id() });
+            }
+        }
+    }
+    fn memoref_is_resolvable (&self, memoref: &MemoRef) -> bool {
+        if memoref.is_resident() || self.memo_is_collected(&memoref.id()) {
+            return true;
+        }
+
+        // The parent memoref may not be the one we hold, if it was reconstituted before we had one
+        let is_peered = |m: &MemoRef| m.peerlist.read().unwrap().iter().any(|p| p.status != MemoPeeringStatus::NonParticipating );
+        let held = self.get_memoref(&memoref.id());
+
+        is_peered(memoref) || held.is_some_and(|m| m.is_resident() || is_peered(&m) )
+    }
+    fn verify_head (&self, head: &MemoRefHead, findings: &mut Vec<IntegrityFinding>) {
+        let subject_id = head.first_subject_id();
+
+        for memoref in head.iter() {
+            let ancestors = self.known_ancestor_ids(memoref);
+            for other in head.iter() {
+                if other.id() != memoref.id() && ancestors.contains(&other.id()) {
+                    findings.push(IntegrityFinding::MutuallyDescendingHead{ subject_id, memo_id: memoref.id(), descended_memo_id: other.id() });
+                }
+            }
+        }
+    }
+    /// The ids of all ancestors of the given memo which can be determined without retrieving anything
+    fn known_ancestor_ids (&self, memoref: &MemoRef) -> HashSet<MemoId> {
+        let mut ancestors = HashSet::new();
+        let mut stack : Vec<MemoId> = Vec::new();
+
+        let parent_ids = |memo_id: &MemoId| -> Vec<MemoId> {
+            if let Some(memoref) = self.get_memoref(memo_id) {
+                if let Some(memo) = memoref.get_memo_if_resident() {
+                    return memo.parents.memo_ids();
+                }
+            }
+            self.collected.read().unwrap().get(memo_id).cloned().unwrap_or(Vec::new())
+        };
+
+        match memoref.get_memo_if_resident() {
+            Some(memo) => stack.extend(memo.parents.memo_ids()),
+            None       => stack.extend(parent_ids(&memoref.id())),
+        }
+
+        while let Some(memo_id) = stack.pop() {
+            if ancestors.insert(memo_id) {
+                stack.extend(parent_ids(&memo_id));
+            }
+        }
+
+        ancestors
+    }
+}
@@ -1,12 +1,13 @@
 use core::ops::Deref;
 use std::fmt;
 use std::collections::HashMap;
-use std::sync::{Arc,RwLock,Weak};
+use std::sync::{Arc,Mutex,RwLock,Weak};
 
 use crate::slab::*;
 use crate::memorefhead::*;
 use crate::context::{Context,ContextRef};
 use crate::error::*;
+use crate::value::Value;
 
 pub type SubjectId     = u64;
 pub type SubjectField  = String;
@@ -25,14 +26,19 @@ pub struct WeakSubject(Weak<SubjectInner>);
 pub struct SubjectInner {
     pub id:     SubjectId,
     head:       RwLock<MemoRefHead>,
+    /// The distance of the head from its keyframes, counted as we edit, such that deciding whether to materialize
+    /// needn't walk the head. None if it's unknown, as when the head was applied from elsewhere
+    keyframe_distance: Mutex<Option<KeyframeDistance>>,
     contextref: ContextRef,
 }
 
 impl Subject {
-    pub fn new ( context: &Context, vals: HashMap<String, String>, is_index: bool ) -> Result<Subject,String> {
+    pub fn new<V: Into<Value>> ( context: &Context, vals: HashMap<String, V>, is_index: bool ) -> Result<Subject,String> {
         Self::new_with_contextref( ContextRef::Strong(context.clone()), vals, is_index )
     }
-    pub fn new_with_contextref ( contextref: ContextRef, vals: HashMap<String, String>, is_index: bool ) -> Result<Subject,String> {
+    pub fn new_with_contextref<V: Into<Value>> ( contextref: ContextRef, vals: HashMap<String, V>, is_index: bool ) -> Result<Subject,String> {
+        let vals : HashMap<String, Value> = vals.into_iter().map(|(k,v)| (k, v.into()) ).collect();
+
         // don't store this
         let context = contextref.get_context();
 
@@ -49,6 +55,7 @@ impl Subject {
         let subject = Subject(Arc::new(SubjectInner{
             id: subject_id,
             head: RwLock::new(head),
+            keyframe_distance: Mutex::new(Some(KeyframeDistance::default())),
             contextref: contextref
         }));
 
@@ -70,6 +77,7 @@ impl Subject {
         let subject = Subject(Arc::new(SubjectInner{
             id: subject_id,
             head: RwLock::new(head),
+            keyframe_distance: Mutex::new(None),
             contextref: contextref
         }));
 
@@ -78,66 +86,301 @@ impl Subject {
         subject
     }
     pub fn new_blank ( context: &Context ) -> Result<Subject,String> {
-        Self::new( context, HashMap::new(), false )
+        Self::new( context, HashMap::<String,Value>::new(), false )
     }
-    pub fn new_kv ( context: &Context, key: &str, value: &str ) -> Result<Subject,String> {
+    pub fn new_kv<V: Into<Value>> ( context: &Context, key: &str, value: V ) -> Result<Subject,String> {
         let mut vals = HashMap::new();
-        vals.insert(key.to_string(), value.to_string());
+        vals.insert(key.to_string(), value.into());
 
         Self::new( context, vals, false )
     }
+    /// The value of the given field, rendered as a string. See get_typed_value
     pub fn get_value ( &self, key: &str ) -> Option<String> {
         //println!("# Subject({}).get_value({})",self.id,key);
 
+        self.get_typed_value(key).map(|v| v.to_string() )
+    }
+    pub fn get_typed_value ( &self, key: &str ) -> Option<Value> {
         self.head.read().unwrap().project_value(&self.contextref.get_context(), key)
     }
+    /// Non-blocking equivalent of get_value. Memos which are not resident are requested from our peers
+    pub async fn get_value_async ( &self, key: &str ) -> Result<Option<String>, RetrieveError> {
+        Ok(self.get_typed_value_async(key).await?.map(|v| v.to_string() ))
+    }
+    /// Non-blocking equivalent of get_typed_value
+    pub async fn get_typed_value_async ( &self, key: &str ) -> Result<Option<Value>, RetrieveError> {
+        // Don't hold the lock across the await
+        let head = self.head.read().unwrap().clone();
+        head.project_value_async(&self.contextref.get_context(), key).await
+    }
+    pub fn get_bool ( &self, key: &str ) -> Option<bool> {
+        self.get_typed_value(key).and_then(|v| v.as_bool() )
+    }
+    pub fn get_i64 ( &self, key: &str ) -> Option<i64> {
+        self.get_typed_value(key).and_then(|v| v.as_i64() )
+    }
+    pub fn get_f64 ( &self, key: &str ) -> Option<f64> {
+        self.get_typed_value(key).and_then(|v| v.as_f64() )
+    }
+    pub fn get_bytes ( &self, key: &str ) -> Option<Vec<u8>> {
+        self.get_typed_value(key).and_then(|v| v.as_bytes().map(|b| b.to_vec()) )
+    }
+    /// The subject referenced by the given field, if it holds a subject reference
+    pub fn get_subject_ref ( &self, key: &str ) -> Result<Subject, RetrieveError> {
+        match self.get_typed_value(key).and_then(|v| v.as_subject_id() ) {
+            Some(subject_id) => self.contextref.get_context().get_subject_by_id(subject_id),
+            None             => Err(RetrieveError::NotFound)
+        }
+    }
     pub fn get_relation ( &self, key: RelationSlotId ) -> Result<Subject, RetrieveError> {
         //println!("# Subject({}).get_relation({})",self.id,key);
 
         let context = self.contextref.get_context();
-        match self.head.read().unwrap().project_relation(&context, key) {
+
+        // Release the lock before applying the head, as the subject may be related to itself
+        let relation = self.head.read().unwrap().project_relation(&context, key);
+        match relation {
             Ok((subject_id, head)) => context.get_subject_with_head(subject_id,head),
             Err(e)   => Err(e)
 
         }
     }
     pub fn set_value (&self, key: &str, value: &str) -> bool {
+        self.set_typed_value(key, value)
+    }
+    pub fn set_typed_value<V: Into<Value>> (&self, key: &str, value: V) -> bool {
+        let mut vals = HashMap::new();
+        vals.insert(key.to_string(), value.into());
+
+        self.apply_body(MemoBody::Edit(vals))
+    }
+    /// All current values of the given field, each with the id of the memo which wrote it. There will be more
+    /// than one only if the field was written concurrently, in which case see resolve_value
+    pub fn get_value_versions (&self, key: &str) -> Vec<ValueVersion> {
+        self.head.read().unwrap().project_value_versions(&self.contextref.get_context(), key)
+    }
+    /// Write a value for the given field which supersedes each of the given versions, as returned by get_value_versions,
+    /// including any which have not yet been applied to this subject
+    pub fn resolve_value<V: Into<Value>> (&self, key: &str, versions: &[ValueVersion], value: V) {
+        {
+            let context = self.contextref.get_context();
+            let mut head = self.head.write().unwrap();
+
+            for version in versions.iter() {
+                head.apply_memoref(&version.memoref, &context.slab);
+            }
+        }
+
         let mut vals = HashMap::new();
-        vals.insert(key.to_string(), value.to_string());
+        vals.insert(key.to_string(), value.into());
+        self.apply_body(MemoBody::Edit(vals));
+    }
+    /// Increment ( or decrement ) the given counter field. Concurrent increments are summed rather than overwritten
+    pub fn increment (&self, key: &str, delta: i64) -> bool {
+        let mut deltas = HashMap::new();
+        deltas.insert(key.to_string(), delta);
 
+        let slab_id = self.contextref.get_context().slab.id;
+        self.apply_body(MemoBody::Counter(slab_id, deltas))
+    }
+    pub fn get_counter (&self, key: &str) -> i64 {
+        self.head.read().unwrap().project_counter(&self.contextref.get_context(), key)
+    }
+    /// Add an element to the given set field. An addition survives any concurrent removal of the same element
+    pub fn add_to_set<V: Into<Value>> (&self, key: &str, element: V) -> bool {
+        let mut adds = HashMap::new();
+        adds.insert(key.to_string(), vec![element.into()]);
+
+        self.apply_body(MemoBody::SetAdd(adds))
+    }
+    /// Remove an element from the given set field. Returns false if the element was not present
+    pub fn remove_from_set<V: Into<Value>> (&self, key: &str, element: V) -> bool {
+        let element = element.into();
+
+        // Only the additions we have observed are removed. Don't hold the lock while projecting
+        let head = self.head.read().unwrap().clone();
+        let observed : Vec<(MemoId, Value)> = head.project_set_tags(&self.contextref.get_context(), key)
+            .into_iter().filter(|(_, e)| *e == element ).collect();
+
+        if observed.is_empty() {
+            return false;
+        }
+
+        let mut removes = HashMap::new();
+        removes.insert(key.to_string(), observed);
+
+        self.apply_body(MemoBody::SetRemove(removes))
+    }
+    pub fn get_set (&self, key: &str) -> Vec<Value> {
+        self.head.read().unwrap().project_set(&self.contextref.get_context(), key)
+    }
+    /// Write the given register field. Values written concurrently are all retained, until overwritten
+    pub fn set_register<V: Into<Value>> (&self, key: &str, value: V) -> bool {
+        let mut vals = HashMap::new();
+        vals.insert(key.to_string(), value.into());
+
+        self.apply_body(MemoBody::Register(vals))
+    }
+    /// The current values of the given register field. More than one means that they were written concurrently
+    pub fn get_register (&self, key: &str) -> Vec<Value> {
+        self.head.read().unwrap().project_register(&self.contextref.get_context(), key)
+    }
+    /// Record the current values of the given fields and relation slots in a single memo, such that reading them
+    /// need not traverse any further. Other fields and slots are unaffected
+    pub fn partially_materialize (&self, keys: &[&str], slots: &[RelationSlotId]) -> bool {
         let context = self.contextref.get_context();
-        let slab = &context.slab;
-        let mut head = self.head.write().unwrap();
 
+        // Don't hold the lock while projecting. The snapshot descends only what it was projected from
+        let snapshot_head = self.get_head();
+
+        let mut values = HashMap::new();
+        for key in keys.iter() {
+            if let Some(value) = snapshot_head.project_materialized_value(&context, key) {
+                values.insert(key.to_string(), value);
+            }
+        }
+
+        let mut relations = HashMap::new();
+        for slot in slots.iter() {
+            match snapshot_head.project_relation(&context, *slot) {
+                Ok(relation)                 => { relations.insert(*slot, Some(relation)); },
+                Err(RetrieveError::NotFound) => { relations.insert(*slot, None); },
+                Err(_)                       => {}
+            }
+        }
+
+        self.apply_snapshot(&context, snapshot_head, MemoBody::PartiallyMaterialized{ v: values, r: RelationSlotSubjectHead(relations) })
+    }
+    /// Record the entire present state of this subject in a single FullyMaterialized memo, such that reading it need
+    /// not traverse any further. Returns false if the subject was already fully materialized
+    pub fn fully_materialize (&self) -> bool {
+        let context = self.contextref.get_context();
+
+        // Don't hold the lock while projecting. The snapshot descends only what it was projected from
+        let snapshot_head = self.get_head();
+        if snapshot_head.is_fully_materialized(&context.slab) {
+            return false;
+        }
+
+        let body = snapshot_head.project_materialized_body(&context);
+        self.apply_snapshot(&context, snapshot_head, body)
+    }
+    /// Fully materialize this subject if the memos since its last FullyMaterialized memo reach the thresholds
+    /// configured for the slab. Called after each edit
+    pub fn conditionally_materialize (&self) -> bool {
+        let context = self.contextref.get_context();
+        let (max_edits, max_bytes) = (context.slab.config.materialize_after_edits, context.slab.config.materialize_after_bytes);
+
+        let known = *self.keyframe_distance.lock().unwrap();
+        let distance = match known {
+            Some(distance) => distance,
+            None => {
+                // Measured under the lock, lest the head change before we record it
+                let head = self.head.read().unwrap();
+                let distance = head.keyframe_distance(&context.slab, max_edits, max_bytes);
+                *self.keyframe_distance.lock().unwrap() = Some(distance);
+                distance
+            }
+        };
+
+        if distance.reaches(max_edits, max_bytes) {
+            self.fully_materialize()
+        } else {
+            false
+        }
+    }
+    fn apply_snapshot (&self, context: &Context, snapshot_head: MemoRefHead, body: MemoBody) -> bool {
+        let slab = &context.slab;
         let memoref = slab.new_memo_basic(
             Some(self.id),
-            head.clone(),
-            MemoBody::Edit(vals)
+            snapshot_head,
+            body
         );
 
-        head.apply_memoref(&memoref, &slab);
+        let mut head = self.head.write().unwrap();
+        head.apply_memoref(&memoref, slab);
         context.apply_subject_head( self.id,  &head, false );
 
+        // Edits made while we were projecting the snapshot don't descend it
+        *self.keyframe_distance.lock().unwrap() = if head.len() == 1 {
+            memoref.get_memo_if_resident().map(|memo| KeyframeDistance::default().after(&memo) )
+        } else {
+            None
+        };
+
+        true
+    }
+    /// Count a memo which was just applied atop the entire head, while the head is locked
+    fn note_edit (&self, memoref: &MemoRef) {
+        let mut distance = self.keyframe_distance.lock().unwrap();
+        *distance = match (*distance, memoref.get_memo_if_resident()) {
+            (Some(d), Some(memo)) => Some(d.after(&memo)),
+            _                     => None
+        };
+    }
+    fn apply_body (&self, body: MemoBody) -> bool {
+        {
+            let context = self.contextref.get_context();
+            let slab = &context.slab;
+            let mut head = self.head.write().unwrap();
+
+            let memoref = slab.new_memo_basic(
+                Some(self.id),
+                head.clone(),
+                body
+            );
+
+            head.apply_memoref(&memoref, slab);
+            self.note_edit(&memoref);
+            context.apply_subject_head( self.id,  &head, false );
+        }
+
+        self.conditionally_materialize();
         true
     }
     pub fn set_relation (&self, key: RelationSlotId, relation: &Self) {
         //println!("# Subject({}).set_relation({}, {})", &self.id, key, relation.id);
-        let mut memoref_map : HashMap<RelationSlotId, (SubjectId,MemoRefHead)> = HashMap::new();
-        memoref_map.insert(key, (relation.id, relation.get_head().clone()) );
+        let mut memoref_map : HashMap<RelationSlotId, Option<(SubjectId,MemoRefHead)>> = HashMap::new();
+        memoref_map.insert(key, Some((relation.id, relation.get_head().clone())) );
+
+        {
+            let context = self.contextref.get_context();
+            let slab = &context.slab;
+            let mut head = self.head.write().unwrap();
+
+            let memoref = slab.new_memo(
+                Some(self.id),
+                head.clone(),
+                MemoBody::Relation(RelationSlotSubjectHead(memoref_map))
+            );
 
-        let context = self.contextref.get_context();
-        let slab = &context.slab;
-        let mut head = self.head.write().unwrap();
+            head.apply_memoref(&memoref, slab);
+            self.note_edit(&memoref);
+            context.apply_subject_head( self.id, &head, false );
+        }
 
-        let memoref = slab.new_memo(
-            Some(self.id),
-            head.clone(),
-            MemoBody::Relation(RelationSlotSubjectHead(memoref_map))
-        );
+        self.conditionally_materialize();
+    }
+    /// Remove the relation in the given slot, if any. This is recorded as a tombstone, such that it overrides
+    /// any relation set in the slot previously
+    pub fn clear_relation (&self, key: RelationSlotId) {
+        {
+            let context = self.contextref.get_context();
+            let slab = &context.slab;
+            let mut head = self.head.write().unwrap();
+
+            let memoref = slab.new_memo(
+                Some(self.id),
+                head.clone(),
+                MemoBody::Relation(RelationSlotSubjectHead::cleared(key))
+            );
 
-        head.apply_memoref(&memoref, &slab);
-        context.apply_subject_head( self.id, &head, false );
+            head.apply_memoref(&memoref, slab);
+            self.note_edit(&memoref);
+            context.apply_subject_head( self.id, &head, false );
+        }
 
+        self.conditionally_materialize();
     }
     // TODO: get rid of apply_head and get_head in favor of Arc sharing heads with the context
     pub fn apply_head (&self, new: &MemoRefHead){
@@ -147,16 +390,30 @@ impl Subject {
         let slab = context.slab.clone(); // TODO: find a way to get rid of this clone
 
         //println!("# Record({}) calling apply_memoref", self.id);
-        self.head.write().unwrap().apply(&new, &slab);
+        let mut head = self.head.write().unwrap();
+        let prior = head.clone();
+        head.apply(new, &slab);
+
+        // A head we already have changes nothing, and needn't cost us the count
+        if *head != prior {
+            *self.keyframe_distance.lock().unwrap() = None;
+        }
     }
     pub fn get_head (&self) -> MemoRefHead {
         self.head.read().unwrap().clone()
     }
+    /// Returns the head of this subject, unless it's presently locked for update
+    pub fn try_get_head (&self) -> Option<MemoRefHead> {
+        match self.head.try_read() {
+            Ok(head) => Some(head.clone()),
+            Err(_)   => None
+        }
+    }
     pub fn get_all_memo_ids ( &self ) -> Vec<MemoId> {
         //println!("# Subject({}).get_all_memo_ids()",self.id);
         let context = self.contextref.get_context();
         let slab = context.slab.clone(); // TODO: find a way to get rid of this clone
-        self.head.read().unwrap().causal_memo_iter( &slab ).map(|m| m.id).collect()
+        self.head.read().unwrap().causal_memo_iter( &slab ).map(|m| m.id()).collect()
     }
     pub fn weak (&self) -> WeakSubject {
         WeakSubject(Arc::downgrade(&self.0))
@@ -165,10 +422,6 @@ impl Subject {
         let context = self.contextref.get_context();
         self.head.read().unwrap().is_fully_materialized(&context.slab)
     }
-    pub fn fully_materialize (&self, _slab: &Slab) -> bool {
-        unimplemented!();
-        //self.shared.lock().unwrap().head.fully_materialize(slab)
-    }
 }
 
 impl Drop for SubjectInner {
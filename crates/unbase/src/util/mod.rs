@@ -1,5 +1,6 @@
 pub mod serde;
 pub mod system_creator;
 pub mod logging;
+pub mod random;
 
 pub use self::logging::init_basic_logger;
\ No newline at end of file
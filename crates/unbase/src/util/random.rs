@@ -0,0 +1,18 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+/// A random, nonzero u64, drawn from the thread's cryptographically secure generator
+pub fn nonzero_u64 () -> u64 {
+    let mut rng = rand::thread_rng();
+    loop {
+        let n : u64 = rng.gen();
+        if n != 0 {
+            return n;
+        }
+    }
+}
+
+/// Shuffle the given items into a random order
+pub fn shuffle<T> (items: &mut [T]) {
+    items.shuffle(&mut rand::thread_rng());
+}
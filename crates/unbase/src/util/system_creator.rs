@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use crate::memorefhead::MemoRefHead;
 use crate::slab::*;
+use crate::value::Value;
 
 pub struct SystemCreator;
 
@@ -9,7 +10,7 @@ impl SystemCreator {
     pub fn generate_root_index_seed( slab: &Slab ) -> MemoRefHead {
 
         let mut values = HashMap::new();
-        values.insert("tier".to_string(),0.to_string());
+        values.insert("tier".to_string(),Value::I64(0));
 
         let memoref = slab.new_memo_basic_noparent(
             Some(slab.generate_subject_id()),
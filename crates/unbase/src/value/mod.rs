@@ -0,0 +1,194 @@
+/* Value
+ *
+ * The value of a single field of a Subject, as carried by Edit and materialized memos.
+ *
+ * Values are serialized with their type, such that they round-trip through storage and the wire
+ * format intact. Subject references carry only the SubjectId. Unlike relations, they do not
+ * convey the head of the referenced subject, and so are not subject to the consistency model.
+*/
+
+mod serde;
+
+use crate::subject::{Subject,SubjectId};
+use crate::slab::MemoId;
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    I64(i64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Map(HashMap<String, Value>),
+    /// A reference to another subject
+    Subject(SubjectId),
+    /// The elements of an observed-remove set as recorded by a materialized memo, each tagged with the id of the
+    /// memo which added it, such that removals which are concurrent with the materialization still apply
+    Set(Vec<(MemoId, Value)>),
+}
+
+impl Value {
+    pub fn is_null (&self) -> bool {
+        *self == Value::Null
+    }
+    pub fn as_bool (&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _              => None
+        }
+    }
+    pub fn as_i64 (&self) -> Option<i64> {
+        match *self {
+            Value::I64(i) => Some(i),
+            _             => None
+        }
+    }
+    /// Integers are widened
+    pub fn as_f64 (&self) -> Option<f64> {
+        match *self {
+            Value::F64(f) => Some(f),
+            Value::I64(i) => Some(i as f64),
+            _             => None
+        }
+    }
+    pub fn as_str (&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s.as_str()),
+            _                    => None
+        }
+    }
+    pub fn as_bytes (&self) -> Option<&[u8]> {
+        match *self {
+            Value::Bytes(ref b) => Some(b.as_slice()),
+            _                   => None
+        }
+    }
+    pub fn as_list (&self) -> Option<&Vec<Value>> {
+        match *self {
+            Value::List(ref l) => Some(l),
+            _                  => None
+        }
+    }
+    pub fn as_map (&self) -> Option<&HashMap<String, Value>> {
+        match *self {
+            Value::Map(ref m) => Some(m),
+            _                 => None
+        }
+    }
+    pub fn as_subject_id (&self) -> Option<SubjectId> {
+        match *self {
+            Value::Subject(subject_id) => Some(subject_id),
+            _                          => None
+        }
+    }
+    /// A rough estimate of the memory occupied by this value, for the purposes of memory budgeting
+    pub fn approximate_size (&self) -> usize {
+        use std::mem::size_of;
+
+        size_of::<Value>() + match *self {
+            Value::String(ref s) => s.len(),
+            Value::Bytes(ref b)  => b.len(),
+            Value::List(ref l)   => l.iter().map(|v| v.approximate_size() ).sum(),
+            Value::Map(ref m)    => m.iter().map(|(k,v)| k.len() + size_of::<String>() + v.approximate_size() ).sum(),
+            Value::Set(ref s)    => s.iter().map(|(_,v)| size_of::<MemoId>() + v.approximate_size() ).sum(),
+            _                    => 0
+        }
+    }
+}
+
+/// Strings are rendered bare, such that they read as they did before values were typed. Subject references are rendered as #id
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Null             => write!(f, "null"),
+            Value::Bool(b)          => write!(f, "{}", b),
+            Value::I64(i)           => write!(f, "{}", i),
+            Value::F64(v)           => write!(f, "{}", v),
+            Value::String(ref s)    => write!(f, "{}", s),
+            Value::Bytes(ref b)     => write!(f, "{:?}", b),
+            Value::List(ref l)      => {
+                write!(f, "[")?;
+                for (i, v) in l.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(ref m)       => {
+                let mut keys : Vec<&String> = m.keys().collect();
+                keys.sort();
+
+                write!(f, "{{")?;
+                for (i, k) in keys.into_iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{:?}:{}", k, m[k])?;
+                }
+                write!(f, "}}")
+            }
+            Value::Subject(subject_id) => write!(f, "#{}", subject_id),
+            // Rendered as a list of the distinct elements
+            Value::Set(ref s)       => {
+                let mut elements : Vec<&Value> = Vec::new();
+                for (_, v) in s.iter() {
+                    if !elements.contains(&v) {
+                        elements.push(v);
+                    }
+                }
+
+                write!(f, "[")?;
+                for (i, v) in elements.into_iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self { Value::Bool(b) }
+}
+impl From<i64> for Value {
+    fn from(i: i64) -> Self { Value::I64(i) }
+}
+impl From<i32> for Value {
+    fn from(i: i32) -> Self { Value::I64(i as i64) }
+}
+impl From<f64> for Value {
+    fn from(f: f64) -> Self { Value::F64(f) }
+}
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Self { Value::String(s.to_string()) }
+}
+impl From<String> for Value {
+    fn from(s: String) -> Self { Value::String(s) }
+}
+impl<'a> From<&'a String> for Value {
+    fn from(s: &'a String) -> Self { Value::String(s.clone()) }
+}
+impl From<Vec<u8>> for Value {
+    fn from(b: Vec<u8>) -> Self { Value::Bytes(b) }
+}
+impl From<Vec<Value>> for Value {
+    fn from(l: Vec<Value>) -> Self { Value::List(l) }
+}
+impl From<HashMap<String, Value>> for Value {
+    fn from(m: HashMap<String, Value>) -> Self { Value::Map(m) }
+}
+impl<'a> From<&'a Subject> for Value {
+    fn from(subject: &'a Subject) -> Self { Value::Subject(subject.id) }
+}
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(o: Option<T>) -> Self {
+        match o {
+            Some(v) => v.into(),
+            None    => Value::Null
+        }
+    }
+}
@@ -0,0 +1,119 @@
+/* Value is serialized by hand, in the form which serde_derive would give it: externally tagged by variant name,
+ * eg: {"I64":3} or "Null". The derive of our serde version nests its impls within a const, which rustc now warns of
+*/
+
+use super::*;
+use crate::util::serde::*;
+
+use ::serde::{Serialize,Deserialize};
+use ::serde::de::{EnumVisitor,VariantVisitor};
+
+enum ValueVariant {
+    Null,
+    Bool,
+    I64,
+    F64,
+    String,
+    Bytes,
+    List,
+    Map,
+    Subject,
+    Set
+}
+
+const VALUE_VARIANTS: &'static [&str] = &[
+    "Null",
+    "Bool",
+    "I64",
+    "F64",
+    "String",
+    "Bytes",
+    "List",
+    "Map",
+    "Subject",
+    "Set"
+];
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        match *self {
+            Value::Null                => serializer.serialize_unit_variant("Value", 0, "Null"),
+            Value::Bool(ref b)         => serializer.serialize_newtype_variant("Value", 1, "Bool", b),
+            Value::I64(ref i)          => serializer.serialize_newtype_variant("Value", 2, "I64", i),
+            Value::F64(ref f)          => serializer.serialize_newtype_variant("Value", 3, "F64", f),
+            Value::String(ref s)       => serializer.serialize_newtype_variant("Value", 4, "String", s),
+            Value::Bytes(ref b)        => serializer.serialize_newtype_variant("Value", 5, "Bytes", b),
+            Value::List(ref l)         => serializer.serialize_newtype_variant("Value", 6, "List", l),
+            Value::Map(ref m)          => serializer.serialize_newtype_variant("Value", 7, "Map", m),
+            Value::Subject(ref id)     => serializer.serialize_newtype_variant("Value", 8, "Subject", id),
+            Value::Set(ref s)          => serializer.serialize_newtype_variant("Value", 9, "Set", s),
+        }
+    }
+}
+
+impl Deserialize for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Value, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize_enum("Value", VALUE_VARIANTS, ValueVisitor)
+    }
+}
+struct ValueVisitor;
+impl Visitor for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+       formatter.write_str("Value")
+    }
+    fn visit_enum<V>(self, visitor: V) -> Result<Value, V::Error>
+        where V: EnumVisitor
+    {
+        match visitor.visit_variant()? {
+            (ValueVariant::Null,    variant) => variant.visit_unit().map(|_| Value::Null),
+            (ValueVariant::Bool,    variant) => variant.visit_newtype().map(Value::Bool),
+            (ValueVariant::I64,     variant) => variant.visit_newtype().map(Value::I64),
+            (ValueVariant::F64,     variant) => variant.visit_newtype().map(Value::F64),
+            (ValueVariant::String,  variant) => variant.visit_newtype().map(Value::String),
+            (ValueVariant::Bytes,   variant) => variant.visit_newtype().map(Value::Bytes),
+            (ValueVariant::List,    variant) => variant.visit_newtype().map(Value::List),
+            (ValueVariant::Map,     variant) => variant.visit_newtype().map(Value::Map),
+            (ValueVariant::Subject, variant) => variant.visit_newtype().map(Value::Subject),
+            (ValueVariant::Set,     variant) => variant.visit_newtype().map(Value::Set),
+        }
+    }
+}
+
+impl Deserialize for ValueVariant {
+    fn deserialize<D>(deserializer: D) -> Result<ValueVariant, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize(ValueVariantVisitor)
+    }
+}
+struct ValueVariantVisitor;
+impl Visitor for ValueVariantVisitor {
+    type Value = ValueVariant;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+       formatter.write_str("Value Variant")
+    }
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where E: DeError
+    {
+        match value {
+            "Null"    => Ok(ValueVariant::Null),
+            "Bool"    => Ok(ValueVariant::Bool),
+            "I64"     => Ok(ValueVariant::I64),
+            "F64"     => Ok(ValueVariant::F64),
+            "String"  => Ok(ValueVariant::String),
+            "Bytes"   => Ok(ValueVariant::Bytes),
+            "List"    => Ok(ValueVariant::List),
+            "Map"     => Ok(ValueVariant::Map),
+            "Subject" => Ok(ValueVariant::Subject),
+            "Set"     => Ok(ValueVariant::Set),
+            _ => Err(DeError::unknown_variant(value, VALUE_VARIANTS)),
+        }
+    }
+}
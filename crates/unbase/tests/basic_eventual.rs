@@ -14,9 +14,7 @@ fn basic_eventual() {
     let slab_b = unbase::Slab::new(&net);
     let slab_c = unbase::Slab::new(&net);
 
-    assert!(slab_a.id == 0, "Slab A ID shoud be 0");
-    assert!(slab_b.id == 1, "Slab B ID shoud be 1");
-    assert!(slab_c.id == 2, "Slab C ID shoud be 2");
+    assert!(slab_a.id != slab_b.id && slab_b.id != slab_c.id && slab_a.id != slab_c.id, "Slab IDs should be distinct");
 
     assert!(slab_a.peer_slab_count() == 2, "Slab A Should know two peers" );
     assert!(slab_b.peer_slab_count() == 2, "Slab B Should know two peers" );
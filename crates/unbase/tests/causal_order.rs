@@ -0,0 +1,67 @@
+extern crate unbase;
+use unbase::Value;
+use unbase::memorefhead::MemoRefHead;
+use unbase::slab::{MemoBody,MemoId};
+use unbase::subject::Subject;
+use std::collections::HashMap;
+
+fn edit (key: &str, value: &str) -> MemoBody {
+    let mut vals = HashMap::new();
+    vals.insert(key.to_string(), Value::from(value));
+    MemoBody::Edit(vals)
+}
+
+#[test]
+fn causal_order_is_deterministic() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    let id = Some(record.id);
+
+    //          /- E -> C -\
+    //  head ->              -> B -> A
+    //          \- F -> D -/
+    let b = slab.new_memo_basic(id, record.get_head(), edit("animal_sound", "Woof"));
+    let c = slab.new_memo_basic(id, b.to_head(), edit("animal_sound", "Meow"));
+    let d = slab.new_memo_basic(id, b.to_head(), edit("animal_sound", "Baa"));
+    let e = slab.new_memo_basic(id, c.to_head(), edit("legs", "4"));
+    let f = slab.new_memo_basic(id, d.to_head(), edit("legs", "2"));
+
+    let forward  = MemoRefHead::new_from_vec(vec![e.clone(), f.clone()]);
+    let backward = MemoRefHead::new_from_vec(vec![f.clone(), e.clone()]);
+
+    let forward_ids  : Vec<MemoId> = forward.causal_memo_iter(&slab).map(|m| m.id()).collect();
+    let backward_ids : Vec<MemoId> = backward.causal_memo_iter(&slab).map(|m| m.id()).collect();
+    assert_eq!(forward_ids.len(), 6, "each memo should be visited exactly once");
+    assert_eq!(forward_ids, backward_ids, "the order of the head should not matter");
+
+    // Awaiting the memos makes no difference to their order
+    let mut iter = forward.causal_memo_iter(&slab);
+    let mut async_ids : Vec<MemoId> = Vec::new();
+    while let Some(memo) = futures::executor::block_on(iter.next_async()).expect("retrieval") {
+        async_ids.push(memo.id());
+    }
+    assert_eq!(async_ids, forward_ids);
+
+    // No memo is visited before any of its descendants
+    let position = |memo_id: &MemoId| forward_ids.iter().position(|i| i == memo_id).unwrap();
+    for memo in forward.causal_memo_iter(&slab) {
+        for parent_id in memo.parents.memo_ids() {
+            assert!(position(&parent_id) > position(&memo.id()));
+        }
+    }
+
+    // Concurrent edits are resolved identically, and never in favor of an edit they both supersede
+    let sound = forward.project_value(&context, "animal_sound");
+    assert_eq!(sound, backward.project_value(&context, "animal_sound"));
+    assert!(sound == Some(Value::from("Meow")) || sound == Some(Value::from("Baa")));
+    assert_eq!(forward.project_value(&context, "legs"), backward.project_value(&context, "legs"));
+
+    // A head containing a memo and its ancestor is traversed as though it contained only the former
+    let redundant = MemoRefHead::new_from_vec(vec![b.clone(), e.clone()]);
+    let redundant_ids : Vec<MemoId> = redundant.causal_memo_iter(&slab).map(|m| m.id()).collect();
+    assert_eq!(redundant_ids.len(), 4);
+    assert_eq!(redundant_ids[0], e.id());
+}
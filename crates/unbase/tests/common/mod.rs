@@ -0,0 +1,31 @@
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use std::{thread, time};
+
+/// How long to wait for anything before giving up
+const DEADLINE: time::Duration = time::Duration::from_secs(5);
+
+/// Wait for a condition which other threads are expected to bring about, failing with the given message if they don't
+pub fn eventually<F: FnMut() -> bool> (what: &str, mut condition: F) {
+    let deadline = time::Instant::now() + DEADLINE;
+    while !condition() {
+        assert!(time::Instant::now() < deadline, "{}", what);
+        thread::sleep(time::Duration::from_millis(10));
+    }
+}
+
+/// Wait for a sampled value to stop changing, and return it. A sample of None is not yet fit to settle upon
+pub fn settled<T: PartialEq, F: FnMut() -> Option<T>> (what: &str, mut sample: F) -> T {
+    let deadline = time::Instant::now() + DEADLINE;
+    loop {
+        let before = sample();
+        thread::sleep(time::Duration::from_millis(50));
+        if let Some(after) = sample() {
+            if before.as_ref() == Some(&after) {
+                return after;
+            }
+        }
+        assert!(time::Instant::now() < deadline, "{}", what);
+    }
+}
@@ -0,0 +1,84 @@
+extern crate unbase;
+use unbase::subject::Subject;
+
+#[test]
+fn compression_repoints_relations() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let owner = Subject::new_kv(&context, "name", "Alice").unwrap();
+    let dog   = Subject::new_kv(&context, "name", "Fido").unwrap();
+    dog.set_relation(0, &owner);
+
+    // The relation references a head of the owner which this edit supersedes. Only the context knows otherwise
+    owner.set_value("name", "Alicia");
+    let owner_id = owner.id;
+    let dog_id   = dog.id;
+    drop(owner);
+    drop(dog);
+
+    assert!(context.get_subject_head(owner_id).is_some());
+    context.compress();
+
+    // Both are conveyed by the subjects which relate to them, ultimately the root index
+    assert!(context.get_subject_head(owner_id).is_none(), "owner should have been removed from the context");
+    assert!(context.get_subject_head(dog_id).is_none(),   "dog should have been removed from the context");
+
+    let dog = context.get_subject_by_id(dog_id).unwrap();
+    assert_eq!(dog.get_value("name").unwrap(), "Fido");
+    assert_eq!(dog.get_relation(0).unwrap().get_value("name").unwrap(), "Alicia");
+
+    // Nothing further to compress
+    let resident = slab.count_of_memorefs_resident();
+    context.compress();
+    assert_eq!(slab.count_of_memorefs_resident(), resident);
+}
+
+#[test]
+fn compression_breaks_cycles() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let dog = Subject::new_kv(&context, "name", "Fido").unwrap();
+    let cat = Subject::new_kv(&context, "name", "Tom").unwrap();
+    dog.set_relation(0, &cat);
+    cat.set_relation(0, &dog);
+
+    dog.set_value("name", "Rex");
+    cat.set_value("name", "Felix");
+
+    let dog_id = dog.id;
+    let cat_id = cat.id;
+    drop(dog);
+    drop(cat);
+
+    context.compress();
+
+    // Whichever was visited first was repointed by the other, which must then remain in the context
+    let remaining = vec![dog_id, cat_id].into_iter().filter(|id| context.get_subject_head(*id).is_some() ).count();
+    assert_eq!(remaining, 1);
+
+    let dog = context.get_subject_by_id(dog_id).unwrap();
+    let cat = context.get_subject_by_id(cat_id).unwrap();
+    assert_eq!(dog.get_value("name").unwrap(), "Rex");
+    assert_eq!(cat.get_value("name").unwrap(), "Felix");
+    assert_eq!(dog.get_relation(0).unwrap().get_value("name").unwrap(), "Felix");
+    assert_eq!(cat.get_relation(0).unwrap().get_value("name").unwrap(), "Rex");
+}
+
+#[test]
+fn compression_retains_self_referencing_subjects() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let dog = Subject::new_kv(&context, "name", "Fido").unwrap();
+    dog.set_relation(0, &dog);
+    dog.set_value("name", "Rex");
+
+    context.compress();
+    assert!(context.get_subject_head(dog.id).is_some(), "a subject can't convey its own head");
+    assert_eq!(dog.get_relation(0).unwrap().get_value("name").unwrap(), "Rex");
+}
@@ -0,0 +1,43 @@
+extern crate unbase;
+use unbase::config::{NetworkConfig,SlabConfig};
+use unbase::subject::Subject;
+use unbase::slab::{EmissionStrategy,PeerSelection};
+
+#[test]
+fn config_json_overlay() {
+    let mut config = NetworkConfig::default();
+    config.apply_json(br#"{ "local_transport": false, "slab": { "request_fanout": 3, "memory_budget": 4096 } }"#).expect("apply_json");
+
+    assert!(!config.local_transport);
+    assert_eq!(config.slab.request_fanout,      3);
+    assert_eq!(config.slab.memory_budget,       Some(4096));
+
+    // Settings which were not mentioned retain their defaults
+    assert_eq!(config.slab.retrieval_attempts,  SlabConfig::default().retrieval_attempts);
+
+    config.apply_json(br#"{ "slab": { "emission_strategy": "Plumtree", "peer_selection": "RoundRobin", "memory_budget": null } }"#).expect("apply_json");
+    assert_eq!(config.slab.emission_strategy,   EmissionStrategy::Plumtree);
+    assert_eq!(config.slab.peer_selection,      PeerSelection::RoundRobin);
+    assert_eq!(config.slab.memory_budget,       Some(4096));
+
+    assert!(config.apply_json(br#"{ "slab": { "index_depth": "deep" } }"#).is_err());
+    assert!(config.apply_json(br#"{ "slab": [] }"#).is_err());
+}
+
+#[test]
+fn config_applied_to_slabs() {
+    let mut config = NetworkConfig::default();
+    config.slab.initial_subject_counter = 42;
+
+    let net = unbase::Network::create_new_system_with_config(config);
+    let slab_a = unbase::Slab::new(&net);
+    assert_eq!(slab_a.config.initial_subject_counter, 42);
+
+    let slab_config = SlabConfig { index_depth: 3, ..SlabConfig::default() };
+    let slab_b = unbase::Slab::new_with_config(&net, slab_config);
+    assert_eq!(slab_b.config.index_depth, 3);
+
+    let context_b = slab_b.create_context();
+    let record = Subject::new_kv(&context_b, "animal_type","Cat").unwrap();
+    assert_eq!(record.get_value("animal_type").unwrap(), "Cat");
+}
@@ -0,0 +1,192 @@
+extern crate unbase;
+use unbase::Value;
+use unbase::subject::Subject;
+use unbase::slab::{MemoBody,storage};
+use std::collections::HashMap;
+
+fn field<V> (key: &str, value: V) -> HashMap<String, V> {
+    let mut map = HashMap::new();
+    map.insert(key.to_string(), value);
+    map
+}
+
+#[test]
+fn concurrent_increments() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let widget = Subject::new_kv(&context, "stock", 10i64).unwrap();
+    let base = widget.get_head();
+
+    // Two writers each sell one widget, neither having seen the other's sale
+    widget.increment("stock", -1);
+    let sale = widget.get_head().memo_ids();
+    let concurrent = slab.new_memo_basic(Some(widget.id), base, MemoBody::Counter(slab.id.wrapping_add(1), field("stock", -1i64)));
+    widget.apply_head(&concurrent.to_head());
+
+    assert_eq!(widget.get_head().len(), 2);
+    assert_eq!(widget.get_counter("stock"), 8);
+
+    // Identical increments by different slabs are distinct memos
+    assert!(sale[0] != concurrent.id());
+
+    widget.increment("stock", 5);
+    assert_eq!(widget.get_head().len(), 1);
+    assert_eq!(widget.get_counter("stock"), 13);
+
+    // A plain value resets the counter
+    widget.set_typed_value("stock", 100i64);
+    widget.increment("stock", 1);
+    assert_eq!(widget.get_counter("stock"), 101);
+    assert_eq!(widget.get_counter("missing"), 0);
+}
+
+#[test]
+fn increments_concurrent_with_snapshot() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let widget = Subject::new_kv(&context, "stock", 10i64).unwrap();
+    widget.increment("stock", 3);
+    let base = widget.get_head();
+
+    // One writer snapshots the counter, while another increments it
+    assert!(widget.partially_materialize(&["stock"], &[]));
+    let concurrent = slab.new_memo_basic(Some(widget.id), base, MemoBody::Counter(slab.id.wrapping_add(1), field("stock", 1i64)));
+    widget.apply_head(&concurrent.to_head());
+
+    // The snapshot includes the increment of 3, which is reached again by way of the concurrent increment
+    assert_eq!(widget.get_head().len(), 2);
+    assert_eq!(widget.get_counter("stock"), 14);
+
+    // As does a snapshot of both
+    assert!(widget.fully_materialize());
+    assert_eq!(widget.get_head().len(), 1);
+    assert_eq!(widget.get_counter("stock"), 14);
+
+    widget.increment("stock", 1);
+    assert_eq!(widget.get_counter("stock"), 15);
+}
+
+#[test]
+fn concurrent_set_operations() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let dog = Subject::new_kv(&context, "name", "Fido").unwrap();
+    dog.add_to_set("tricks", "sit");
+    dog.add_to_set("tricks", "stay");
+    assert_eq!(dog.get_set("tricks"), vec![Value::from("stay"), Value::from("sit")]);
+    let base = dog.get_head();
+
+    // One writer removes "sit" while another concurrently adds it again. The addition wins
+    assert!(dog.remove_from_set("tricks", "sit"));
+    assert_eq!(dog.get_set("tricks"), vec![Value::from("stay")]);
+
+    let concurrent = slab.new_memo_basic(Some(dog.id), base, MemoBody::SetAdd(field("tricks", vec![Value::from("sit")])));
+    dog.apply_head(&concurrent.to_head());
+
+    let tricks = dog.get_set("tricks");
+    assert_eq!(tricks.len(), 2);
+    assert!(tricks.contains(&Value::from("sit")));
+    assert!(tricks.contains(&Value::from("stay")));
+
+    // Having now observed the concurrent addition, a removal removes it
+    assert!(dog.remove_from_set("tricks", "sit"));
+    assert_eq!(dog.get_set("tricks"), vec![Value::from("stay")]);
+    assert!(!dog.remove_from_set("tricks", "sit"), "Nothing to remove");
+
+    // A plain list serves as the initial contents of the set
+    dog.set_typed_value("toys", vec![Value::from("ball"), Value::from("bone")]);
+    dog.add_to_set("toys", "rope");
+    assert!(dog.remove_from_set("toys", "ball"));
+    let toys = dog.get_set("toys");
+    assert_eq!(toys.len(), 2);
+    assert!(toys.contains(&Value::from("bone")));
+    assert!(toys.contains(&Value::from("rope")));
+}
+
+#[test]
+fn set_removal_concurrent_with_snapshot() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let dog = Subject::new_kv(&context, "name", "Fido").unwrap();
+    dog.add_to_set("tricks", "sit");
+    let sit_tag = dog.get_head().memo_ids()[0];
+    dog.add_to_set("tricks", "stay");
+    let base = dog.get_head();
+
+    // One writer snapshots the set, while another removes the addition of "sit" it had seen
+    assert!(dog.fully_materialize());
+    let removal = slab.new_memo_basic(Some(dog.id), base, MemoBody::SetRemove(field("tricks", vec![(sit_tag, Value::from("sit"))])));
+    dog.apply_head(&removal.to_head());
+
+    assert_eq!(dog.get_head().len(), 2);
+    assert_eq!(dog.get_set("tricks"), vec![Value::from("stay")]);
+
+    // And the tags survive a further snapshot
+    assert!(dog.fully_materialize());
+    assert_eq!(dog.get_set("tricks"), vec![Value::from("stay")]);
+    assert!(dog.remove_from_set("tricks", "stay"));
+    assert!(dog.get_set("tricks").is_empty());
+}
+
+#[test]
+fn concurrent_register_writes() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let car = Subject::new_kv(&context, "make", "Volvo").unwrap();
+    car.set_register("color", "red");
+    assert_eq!(car.get_register("color"), vec![Value::from("red")]);
+    let base = car.get_head();
+
+    car.set_register("color", "blue");
+    let concurrent = slab.new_memo_basic(Some(car.id), base, MemoBody::Register(field("color", Value::from("green"))));
+    car.apply_head(&concurrent.to_head());
+
+    // Both concurrent writes are retained, and "red" is overwritten by each of them
+    let colors = car.get_register("color");
+    assert_eq!(colors.len(), 2);
+    assert!(colors.contains(&Value::from("blue")));
+    assert!(colors.contains(&Value::from("green")));
+    assert!(car.get_typed_value("color").is_some());
+
+    car.set_register("color", "black");
+    assert_eq!(car.get_register("color"), vec![Value::from("black")]);
+    assert!(car.get_register("missing").is_empty());
+}
+
+#[test]
+fn crdt_memos_roundtrip() {
+    let store = storage::Memory::new();
+
+    let subject_id = {
+        let net = unbase::Network::create_new_system();
+        let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("new slab");
+        let context = slab.create_context();
+
+        let widget = Subject::new_kv(&context, "stock", 10i64).unwrap();
+        widget.increment("stock", 3);
+        widget.add_to_set("bins", "A4");
+        widget.add_to_set("bins", "B2");
+        widget.remove_from_set("bins", "A4");
+        widget.set_register("supplier", "Acme");
+        widget.id
+    };
+
+    let net = unbase::Network::new();
+    let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("reopened slab");
+    let context = slab.create_context();
+    let widget = context.get_subject_by_id(subject_id).expect("subject should survive reopening");
+
+    assert_eq!(widget.get_counter("stock"), 13);
+    assert_eq!(widget.get_set("bins"), vec![Value::from("B2")]);
+    assert_eq!(widget.get_register("supplier"), vec![Value::from("Acme")]);
+}
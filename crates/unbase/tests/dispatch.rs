@@ -0,0 +1,92 @@
+extern crate unbase;
+use unbase::config::NetworkConfig;
+use unbase::subject::Subject;
+use unbase::slab::MemoRef;
+
+mod common;
+use common::{eventually, settled};
+
+#[test]
+fn dispatch_pool_drains() {
+    let mut config = NetworkConfig::default();
+    config.slab.dispatch_workers = 3;
+
+    let net = unbase::Network::create_new_system_with_config(config);
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+
+    let context_a = slab_a.create_context();
+    let context_b = slab_b.create_context();
+
+    let records : Vec<Subject> = (0..10).map(|i| Subject::new_kv(&context_a, "number", i.to_string()).unwrap() ).collect();
+
+    eventually("The dispatch queue should have drained", || {
+        slab_b.dispatch_queue_depth() == 0 && records.iter().all(|record| context_b.get_subject_by_id(record.id).is_ok() )
+    });
+
+    for (i, record) in records.iter().enumerate() {
+        let record_b = context_b.get_subject_by_id(record.id).expect("subject should have propagated");
+        assert_eq!(record_b.get_value("number").unwrap(), i.to_string());
+    }
+}
+
+#[test]
+fn batched_ingress_yields_single_head() {
+    let net = unbase::Network::create_new_system();
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+
+    let context_a = slab_a.create_context();
+    let context_b = slab_b.create_context();
+
+    let record = Subject::new_kv(&context_a, "number", "0").unwrap();
+    eventually("subject should have propagated", || context_b.get_subject_by_id(record.id).is_ok() );
+
+    let record_b = context_b.get_subject_by_id(record.id).unwrap();
+
+    // Successive edits arrive at slab_b together, and descend one another
+    for i in 1..20 {
+        record.set_value("number", &i.to_string());
+    }
+
+    eventually("The last edit should have been applied", || record_b.get_value("number").as_deref() == Some("19") );
+    assert_eq!(context_b.get_subject_head_memo_ids(record.id).len(), 1, "Batched memos should be reduced to a single head");
+}
+
+#[test]
+fn batch_dispatches_one_head_per_subject() {
+    let net = unbase::Network::create_new_system();
+    let simulator = unbase::network::transport::Simulator::new();
+    net.add_transport( Box::new(simulator.clone()) );
+
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+
+    let context_a = slab_a.create_context();
+    let context_b = slab_b.create_context();
+
+    let record = Subject::new_kv(&context_a, "number", "0").unwrap();
+    simulator.advance_clock(1);
+    slab_b.subscribe_subject(record.id, &context_b);
+
+    // Held by the simulator, so that we may convey them to slab_b ourselves, all at once
+    let memorefs : Vec<MemoRef> = (1..20).map(|i| {
+        record.set_value("number", &i.to_string());
+        record.get_head().iter().next().unwrap().clone()
+    }).collect();
+    let last_id = memorefs.last().unwrap().id();
+
+    // The index memos delivered above are still being dispatched, and the counter covers every subject
+    let dispatched = settled_dispatch_count(&slab_b);
+    MemoRef::clone_all_for_slab(&memorefs, &slab_a.my_ref.clone_for_slab(&slab_b), &slab_b);
+
+    eventually("the batch was not dispatched", || context_b.get_subject_head_memo_ids(record.id) == vec![last_id] );
+    assert_eq!(slab_b.metrics.subject_heads_dispatched.get(), dispatched + 1, "The batch should be applied as a single head");
+}
+
+/// Wait for the dispatch queue to drain, and for the dispatch count to stop changing
+fn settled_dispatch_count(slab: &unbase::Slab) -> u64 {
+    settled("the dispatch queue did not settle", || {
+        if slab.dispatch_queue_depth() == 0 { Some(slab.metrics.subject_heads_dispatched.get()) } else { None }
+    })
+}
@@ -0,0 +1,24 @@
+extern crate unbase;
+use unbase::slab::MemoBody;
+use std::collections::HashMap;
+
+#[test]
+fn durability_target() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+
+    let subject_id = slab.generate_subject_id();
+    let mut values = HashMap::new();
+    values.insert("animal_sound".to_string(), unbase::Value::from("Moo"));
+    let memoref = slab.new_memo_basic_noparent(Some(subject_id), MemoBody::Edit(values));
+
+    // Nobody else has it
+    assert_eq!(slab.memo_durability_score(&memoref), 0);
+    assert!(!slab.memo_is_durable(&memoref), "Unreplicated memo should not meet the default target");
+
+    slab.set_subject_durability_target(subject_id, 0);
+    assert!(slab.memo_is_durable(&memoref), "Subject target should override the default");
+
+    slab.set_memo_durability_target(memoref.id(), 4);
+    assert!(!slab.memo_is_durable(&memoref), "Memo target should override the subject target");
+}
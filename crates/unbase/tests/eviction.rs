@@ -0,0 +1,54 @@
+extern crate unbase;
+use unbase::subject::Subject;
+use unbase::slab::MemoBody;
+use std::collections::HashMap;
+
+mod common;
+use common::eventually;
+
+#[test]
+fn eviction_requires_peers() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    record.set_value("animal_sound", "Woof");
+
+    assert!(slab.resident_memory_usage() > 0, "Resident memos should be accounted for");
+    let resident = slab.count_of_memorefs_resident();
+
+    // Way over budget, but nobody else has a copy of anything
+    slab.set_memory_budget(Some(0));
+    assert_eq!(slab.evict_memos(), 0, "Unpeered memos must not be evicted");
+    assert_eq!(slab.count_of_memorefs_resident(), resident);
+
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Woof");
+}
+
+#[test]
+fn evicted_memos_are_retrievable() {
+    let net = unbase::Network::create_new_system();
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+    slab_a.set_default_durability_target(1);
+
+    let subject_id = slab_a.generate_subject_id();
+    let mut values = HashMap::new();
+    values.insert("animal_sound".to_string(), unbase::Value::from("Moo"));
+    let memoref = slab_a.new_memo_basic_noparent(Some(subject_id), MemoBody::Edit(values));
+
+    // No context is holding this memo, so only its peering keeps it around
+    eventually("memo should have been replicated to slab B", || slab_b.count_of_memorefs_resident() > 0 && slab_a.memo_is_durable(&memoref) );
+
+    // Remotizing issues peering memos of its own, so memory usage needn't drop by much, if at all
+    slab_a.set_memory_budget(Some(0));
+    assert!(slab_a.evict_memos() > 0, "Safely peered memos should be evicted");
+    assert!(!memoref.is_resident(), "The replicated edit should be among them");
+
+    let memo = memoref.get_memo(&slab_a).expect("evicted memo should be retrievable from its peer");
+    match memo.body {
+        MemoBody::Edit(ref values) => assert_eq!(values.get("animal_sound"), Some(&unbase::Value::from("Moo"))),
+        _ => panic!("unexpected memo body")
+    }
+}
@@ -0,0 +1,93 @@
+extern crate unbase;
+use unbase::subject::Subject;
+use unbase::slab::{MemoBody,RelationSlotSubjectHead};
+use std::collections::HashMap;
+
+mod common;
+use common::eventually;
+
+#[test]
+fn materialized_memos_supersede_their_ancestors() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    slab.set_default_durability_target(0);
+    let context = slab.create_context();
+
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    record.set_value("animal_sound", "Woof");
+    let woof_head = record.get_head();
+    record.set_value("animal_sound", "Meow");
+    let meow_head = record.get_head();
+
+    assert_eq!(slab.collect_garbage(), 0, "Nothing is superseded until a materialized memo exists");
+
+    let mut values = HashMap::new();
+    values.insert("animal_sound".to_string(), unbase::Value::from("Meow"));
+    let materialized = slab.new_memo_basic(
+        Some(record.id),
+        record.get_head(),
+        MemoBody::FullyMaterialized{ v: values, r: RelationSlotSubjectHead::empty() }
+    );
+    context.apply_subject_head(record.id, &materialized.to_head(), true);
+
+    // Woof and Meow. Moo remains live, as the index still refers to the head the subject was created with
+    let resident = slab.count_of_memorefs_resident();
+    assert_eq!(slab.collect_garbage(), 2);
+    assert_eq!(slab.count_of_memorefs_resident(), resident - 2);
+    assert!(slab.memo_is_collected(&woof_head.memo_ids()[0]));
+    assert!(slab.memo_is_collected(&meow_head.memo_ids()[0]));
+    assert!(!slab.memo_is_collected(&materialized.id()));
+
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Meow");
+
+    // Stale heads are still recognized as having been superseded
+    let mut head = materialized.to_head();
+    head.apply(&woof_head, &slab);
+    assert_eq!(head.memo_ids(), vec![materialized.id()]);
+
+    assert_eq!(slab.collect_garbage(), 0);
+}
+
+#[test]
+fn memos_below_their_durability_target_are_kept() {
+    let net = unbase::Network::create_new_system();
+    let slab_a = unbase::Slab::new(&net);
+    let _slab_b = unbase::Slab::new(&net);
+    slab_a.set_default_durability_target(1);
+    let context = slab_a.create_context();
+
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    record.set_value("animal_sound", "Woof");
+    let woof_head = record.get_head();
+    record.set_value("animal_sound", "Meow");
+    let meow_head = record.get_head();
+
+    // Slab B holding a replica is not enough for this one
+    let woof_id = woof_head.memo_ids()[0];
+    slab_a.set_memo_durability_target(woof_id, 1000);
+
+    let mut values = HashMap::new();
+    values.insert("animal_sound".to_string(), unbase::Value::from("Meow"));
+    let materialized = slab_a.new_memo_basic(
+        Some(record.id),
+        record.get_head(),
+        MemoBody::FullyMaterialized{ v: values, r: RelationSlotSubjectHead::empty() }
+    );
+    context.apply_subject_head(record.id, &materialized.to_head(), true);
+
+    // Wait for Slab B to confirm that it holds them
+    eventually("Slab B should have replicated the memos", || {
+        woof_head.iter().chain(meow_head.iter()).all(|memoref| slab_a.memo_durability_score(memoref) > 0 )
+    });
+
+    // Only Meow
+    assert_eq!(slab_a.collect_garbage(), 1);
+    assert!(!slab_a.memo_is_collected(&woof_id));
+    assert!(slab_a.memo_is_collected(&meow_head.memo_ids()[0]));
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Meow");
+
+    // A later pass collects it once it meets its target
+    slab_a.set_memo_durability_target(woof_id, 1);
+    assert_eq!(slab_a.collect_garbage(), 1);
+    assert!(slab_a.memo_is_collected(&woof_id));
+}
@@ -0,0 +1,174 @@
+extern crate unbase;
+use unbase::Value;
+use unbase::subject::Subject;
+use unbase::slab::{MemoBody,RelationSlotSubjectHead,storage};
+use std::collections::HashMap;
+
+#[test]
+fn traversal_stops_at_materialized_memos() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    record.set_value("animal_sound", "Woof");
+
+    let mut values = HashMap::new();
+    values.insert("animal_sound".to_string(), Value::from("Woof"));
+    let materialized = slab.new_memo_basic(
+        Some(record.id),
+        record.get_head(),
+        MemoBody::FullyMaterialized{ v: values, r: RelationSlotSubjectHead::empty() }
+    );
+    record.apply_head(&materialized.to_head());
+    record.set_value("animal_type", "Dog");
+
+    let memos : Vec<_> = record.get_head().causal_memo_iter(&slab).collect();
+    assert_eq!(memos.len(), 2, "Nothing before the materialized memo should be visited");
+    assert_eq!(memos[1].id(), materialized.id());
+
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Woof");
+    assert_eq!(record.get_value("animal_type").unwrap(),  "Dog");
+}
+
+#[test]
+fn partial_materialization() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let owner  = Subject::new_kv(&context, "name", "Alice").unwrap();
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    record.set_value("animal_type", "Bovine");
+    for sound in ["Woof", "Meow", "Baa"].iter() {
+        record.set_value("animal_sound", sound);
+    }
+    record.increment("legs", 4);
+    record.set_relation(0, &owner);
+
+    assert!(record.partially_materialize(&["animal_sound", "legs", "missing"], &[0, 1]));
+
+    let snapshot = record.get_head().to_vec()[0].get_memo(&slab).unwrap();
+    match snapshot.body {
+        MemoBody::PartiallyMaterialized{ ref v, ref r } => {
+            assert_eq!(v.len(), 2);
+            assert_eq!(v.get("animal_sound"), Some(&Value::from("Baa")));
+            assert_eq!(v.get("legs"),         Some(&Value::I64(4)));
+            assert!(r.get(&0).unwrap().is_some());
+            assert!(r.get(&1).unwrap().is_none(), "empty slots are recorded as such");
+        }
+        ref body => panic!("unexpected memo body {:?}", body)
+    }
+
+    // Fields which were snapshotted, and those which weren't, read as before
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Baa");
+    assert_eq!(record.get_value("animal_type").unwrap(),  "Bovine");
+    assert_eq!(record.get_relation(0).unwrap().get_value("name").unwrap(), "Alice");
+    assert!(record.get_relation(1).is_err());
+
+    // A snapshotted counter continues from its total
+    record.increment("legs", -1);
+    assert_eq!(record.get_counter("legs"), 3);
+}
+
+#[test]
+fn partial_materialization_roundtrip() {
+    let store = storage::Memory::new();
+
+    let subject_id = {
+        let net = unbase::Network::create_new_system();
+        let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("new slab");
+        let context = slab.create_context();
+
+        let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+        record.set_value("animal_sound", "Woof");
+        record.set_value("animal_type", "Dog");
+        record.partially_materialize(&["animal_sound"], &[]);
+        record.id
+    };
+
+    let net = unbase::Network::new();
+    let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("reopened slab");
+    let context = slab.create_context();
+    let record = context.get_subject_by_id(subject_id).expect("subject should survive reopening");
+
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Woof");
+    assert_eq!(record.get_value("animal_type").unwrap(),  "Dog");
+}
+
+#[test]
+fn full_materialization() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let owner  = Subject::new_kv(&context, "name", "Alice").unwrap();
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    record.set_value("animal_sound", "Woof");
+    record.increment("legs", 4);
+    record.add_to_set("tricks", "sit");
+    record.set_relation(0, &owner);
+    record.set_relation(1, &owner);
+    record.clear_relation(1);
+
+    assert!(!record.is_fully_materialized());
+    assert!(record.fully_materialize());
+    assert!(record.is_fully_materialized());
+    assert!(!record.fully_materialize(), "Already fully materialized");
+
+    let memos : Vec<_> = record.get_head().causal_memo_iter(&slab).collect();
+    assert_eq!(memos.len(), 1, "Nothing before the materialized memo should be visited");
+    match memos[0].body {
+        MemoBody::FullyMaterialized{ ref v, ref r } => {
+            assert_eq!(v.len(), 3);
+            assert_eq!(r.len(), 1, "cleared slots are omitted");
+        }
+        ref body => panic!("unexpected memo body {:?}", body)
+    }
+
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Woof");
+    assert_eq!(record.get_counter("legs"), 4);
+    assert_eq!(record.get_set("tricks"), vec![Value::from("sit")]);
+    assert_eq!(record.get_relation(0).unwrap().get_value("name").unwrap(), "Alice");
+    assert!(record.get_relation(1).is_err());
+}
+
+#[test]
+fn automatic_materialization() {
+    let mut config = unbase::config::NetworkConfig::default();
+    config.slab.materialize_after_edits = Some(4);
+
+    let net = unbase::Network::create_new_system_with_config(config);
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let record = Subject::new_kv(&context, "count", 0i64).unwrap();
+    for i in 1..4 {
+        record.set_typed_value("count", i as i64);
+    }
+    assert!(!record.is_fully_materialized(), "Three edits don't reach the threshold");
+
+    record.set_typed_value("count", 4i64);
+    assert!(record.is_fully_materialized());
+    assert_eq!(record.get_i64("count"), Some(4));
+
+    // The count starts over from the materialized memo
+    for i in 5..1000 {
+        record.set_typed_value("count", i as i64);
+        assert!(record.get_head().causal_memo_iter(&slab).count() <= 5);
+    }
+    assert_eq!(record.get_i64("count"), Some(999));
+
+    // Edits applied from elsewhere weren't counted as they were made, but count all the same
+    record.fully_materialize();
+    let mut head = record.get_head();
+    for i in 1000..1004 {
+        let mut values = HashMap::new();
+        values.insert("count".to_string(), Value::from(i as i64));
+        head = slab.new_memo_basic(Some(record.id), head, MemoBody::Edit(values)).to_head();
+    }
+    record.apply_head(&head);
+    assert!(!record.is_fully_materialized());
+    assert!(record.conditionally_materialize());
+    assert_eq!(record.get_i64("count"), Some(1003));
+}
@@ -0,0 +1,71 @@
+extern crate unbase;
+use unbase::slab::{MemoBody,MemoPeerList};
+use unbase::memorefhead::MemoRefHead;
+use unbase::error::IntegrityError;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+mod common;
+use common::eventually;
+
+fn edit (key: &str, value: &str) -> MemoBody {
+    let mut values = HashMap::new();
+    values.insert(key.to_string(), unbase::Value::from(value));
+    MemoBody::Edit(values)
+}
+
+#[test]
+fn content_addressed_memo_ids() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let subject_id = slab.generate_subject_id();
+
+    let a = slab.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Moo"));
+    let b = slab.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Moo"));
+    let c = slab.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Woof"));
+
+    assert_eq!(a.id(), b.id(), "Identical memos should have identical ids");
+    assert!(a.id() != c.id(), "Different memos should have different ids");
+
+    // Same contents, but claiming somebody else's id
+    let result = slab.reconstitute_memo(c.id(), Some(subject_id), MemoRefHead::new(), edit("animal_sound", "Meow"), &slab.my_ref, &MemoPeerList::new(vec![]));
+    assert_eq!(result.err(), Some(IntegrityError::MemoIdMismatch(c.id())));
+}
+
+#[test]
+fn memo_ids_are_calculated_lazily() {
+    let net = unbase::Network::create_new_system();
+    let slab_a = unbase::Slab::new(&net);
+    let subject_id = slab_a.generate_subject_id();
+
+    let kept = slab_a.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Moo"));
+    assert!(!kept.has_id(), "A memo which has not left its slab should not need an id");
+
+    let slab_b = unbase::Slab::new(&net);
+    let sent = slab_a.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Woof"));
+
+    // Transmission may happen on another thread
+    eventually("A memo which was sent to another slab should have an id", || sent.has_id() );
+    assert!(!kept.has_id());
+    assert_eq!(kept.id(), slab_b.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Moo")).id(), "Ids are the same whenever they're calculated");
+}
+
+#[test]
+fn memos_are_found_by_id_once_hashed() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let subject_id = slab.generate_subject_id();
+
+    // One id calculated by way of the memoref, the other by way of the memo
+    let a = slab.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Moo"));
+    let b = slab.new_memo_basic_noparent(Some(subject_id), edit("animal_sound", "Woof"));
+    a.id();
+    b.get_memo_if_resident().unwrap().id();
+
+    // Those memos coming back to us are the ones we already have
+    for &(ref memoref, sound) in [(a, "Moo"), (b, "Woof")].iter() {
+        let (_, received, had_memoref) = slab.reconstitute_memo(memoref.id(), Some(subject_id), MemoRefHead::new(), edit("animal_sound", sound), &slab.my_ref, &MemoPeerList::new(vec![])).unwrap();
+        assert!(had_memoref, "The memo should have been found by its id");
+        assert!(Arc::ptr_eq(&received.0, &memoref.0));
+    }
+}
@@ -0,0 +1,95 @@
+extern crate unbase;
+use unbase::config::NetworkConfig;
+use unbase::subject::Subject;
+use std::io::{Read,Write};
+use std::net::TcpStream;
+
+mod common;
+use common::eventually;
+
+#[test]
+fn metrics_rendering() {
+    let net = unbase::Network::create_new_system();
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+
+    let context_a = slab_a.create_context();
+    let _context_b = slab_b.create_context();
+
+    Subject::new_kv(&context_a, "animal_type","Cat").unwrap();
+    eventually("memos should have been conveyed between slabs", || transport_metric(&net, "local", "bytes_received") > 0 );
+
+    assert!(slab_a.metrics.memos_created.get() > 0);
+
+    let rendered = net.render_metrics();
+    assert!(rendered.contains("# TYPE unbase_memos_created_total counter"));
+    assert!(rendered.contains(&format!("unbase_memos_created_total{{slab=\"{}\"}} {}", slab_a.id, slab_a.metrics.memos_created.get())));
+    assert!(rendered.contains(&format!("unbase_memo_retrieval_seconds_bucket{{slab=\"{}\",le=\"+Inf\"}}", slab_b.id)));
+    assert!(rendered.contains("unbase_transport_packets_received_total{transport=\"local\"}"));
+    assert!(!rendered.contains("unbase_transport_packets_received_total{transport=\"local\"} 0\n"), "memos should have been conveyed between slabs");
+    assert!(!rendered.contains("unbase_transport_bytes_received_total{transport=\"local\"} 0\n"), "bytes conveyed between slabs should be counted");
+
+    // Every memo sent is received, once the transport has caught up
+    assert!(transport_metric(&net, "local", "packets_sent") > 0);
+    assert!(transport_metric(&net, "local", "bytes_sent") > 0);
+    eventually("everything sent should have been received", || {
+        transport_metric(&net, "local", "packets_received") == transport_metric(&net, "local", "packets_sent") &&
+        transport_metric(&net, "local", "bytes_received") == transport_metric(&net, "local", "bytes_sent")
+    });
+}
+
+#[test]
+fn simulator_metrics() {
+    let net = unbase::Network::create_new_system();
+    let simulator = unbase::network::transport::Simulator::new();
+    net.add_transport( Box::new(simulator.clone()) );
+
+    let slab_a = unbase::Slab::new(&net);
+    let _slab_b = unbase::Slab::new(&net);
+    let context_a = slab_a.create_context();
+
+    Subject::new_kv(&context_a, "animal_type","Cat").unwrap();
+
+    // Counted as sent straight away, but received only once the simulator delivers them
+    assert!(transport_metric(&net, "simulator", "packets_sent") > 0);
+    assert!(transport_metric(&net, "simulator", "bytes_sent") > 0);
+    assert_eq!(transport_metric(&net, "simulator", "packets_received"), 0);
+    assert_eq!(transport_metric(&net, "simulator", "bytes_received"), 0);
+
+    let mut ticks = 0;
+    while transport_metric(&net, "simulator", "packets_received") < transport_metric(&net, "simulator", "packets_sent") {
+        assert!(ticks < 100, "the simulator should have delivered everything sent");
+        simulator.advance_clock(1);
+        ticks += 1;
+    }
+    assert!(transport_metric(&net, "simulator", "bytes_received") > 0);
+    assert_eq!(transport_metric(&net, "simulator", "bytes_received"), transport_metric(&net, "simulator", "bytes_sent"));
+}
+
+/// Read a transport counter from the rendered metrics
+fn transport_metric (net: &unbase::Network, transport: &str, name: &str) -> u64 {
+    let prefix = format!("unbase_transport_{}_total{{transport=\"{}\"}} ", name, transport);
+    net.render_metrics().lines()
+        .find(|line| line.starts_with(&prefix))
+        .and_then(|line| line[prefix.len()..].parse().ok())
+        .expect("transport metric should be rendered")
+}
+
+#[test]
+fn metrics_http_listener() {
+    let config = NetworkConfig { metrics_address: Some("127.0.0.1:0".to_string()), ..NetworkConfig::default() };
+
+    let net = unbase::Network::create_new_system_with_config(config);
+    let slab = unbase::Slab::new(&net);
+
+    let address = net.metrics_address().expect("metrics listener should have started");
+
+    let mut stream = TcpStream::connect(address).expect("connect");
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains(&format!("unbase_memos_created_total{{slab=\"{}\"}}", slab.id)));
+}
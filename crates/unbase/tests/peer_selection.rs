@@ -0,0 +1,60 @@
+extern crate unbase;
+use unbase::config::{NetworkConfig,SlabConfig};
+use unbase::slab::*;
+use std::time::Duration;
+
+fn candidate (slab: &unbase::Slab, lifetime: SlabAnticipatedLifetime, rtt_ms: Option<u64>) -> PeerCandidate {
+    PeerCandidate {
+        slabref:  slab.my_ref.clone(),
+        lifetime,
+        rtt:      rtt_ms.map(Duration::from_millis),
+    }
+}
+
+#[test]
+fn builtin_selectors() {
+    let net = unbase::Network::create_new_system();
+    let ephemeral = unbase::Slab::new(&net);
+    let server    = unbase::Slab::new(&net);
+    let nearby    = unbase::Slab::new(&net);
+
+    let candidates = vec![
+        candidate(&ephemeral, SlabAnticipatedLifetime::Ephmeral, Some(1)),
+        candidate(&server,    SlabAnticipatedLifetime::VeryLong, Some(50)),
+        candidate(&nearby,    SlabAnticipatedLifetime::Long,     Some(5)),
+    ];
+    let order = |selected: Vec<PeerCandidate>| -> Vec<SlabId> { selected.iter().map(|c| c.slabref.slab_id ).collect() };
+
+    // Browser slabs should be the last place we put a replica
+    let selected = HighestLifetimeSelector.select(candidates.clone(), SelectionPurpose::Replication);
+    assert_eq!(order(selected), vec![server.id, nearby.id, ephemeral.id]);
+
+    let selected = LowestLatencySelector.select(candidates.clone(), SelectionPurpose::Retrieval);
+    assert_eq!(order(selected), vec![ephemeral.id, nearby.id, server.id]);
+
+    let round_robin = RoundRobinSelector::new();
+    let first  = order(round_robin.select(candidates.clone(), SelectionPurpose::Retrieval));
+    let second = order(round_robin.select(candidates.clone(), SelectionPurpose::Retrieval));
+    assert_eq!(first,  vec![ephemeral.id, server.id, nearby.id]);
+    assert_eq!(second, vec![server.id, nearby.id, ephemeral.id]);
+
+    let mut shuffled = order(RandomSelector.select(candidates.clone(), SelectionPurpose::Replication));
+    shuffled.sort();
+    let mut expected = vec![ephemeral.id, server.id, nearby.id];
+    expected.sort();
+    assert_eq!(shuffled, expected);
+}
+
+#[test]
+fn peer_selection_config() {
+    let mut config = NetworkConfig::default();
+    assert_eq!(config.slab.peer_selection, PeerSelection::HighestLifetime);
+
+    config.apply_json(br#"{ "slab": { "peer_selection": "LowestLatency" } }"#).expect("apply_json");
+    assert_eq!(config.slab.peer_selection, PeerSelection::LowestLatency);
+
+    let net = unbase::Network::create_new_system_with_config(config);
+    let slab = unbase::Slab::new_with_config(&net, SlabConfig::default());
+    slab.set_peer_selector(Box::new(RoundRobinSelector::new()));
+    assert_eq!(slab.peer_rtt(slab.id), None);
+}
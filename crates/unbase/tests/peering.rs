@@ -0,0 +1,70 @@
+extern crate unbase;
+use unbase::slab::{MemoBody,MemoPeer,MemoPeerList,MemoPeeringStatus};
+use std::collections::HashMap;
+use std::time::Duration;
+
+mod common;
+use common::eventually;
+
+#[test]
+fn peering_updates_are_coalesced() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+
+    // A slab with no local presence is remote as far as we're concerned, so peering with it is coalesced
+    let remote = slab.assert_slabref(12345, &[]);
+    slab.set_peering_window(Duration::from_secs(60));
+
+    let peer = |memoref: &unbase::slab::MemoRef| {
+        slab.send_peering(&remote, Some(memoref), vec![(
+            memoref.id(),
+            memoref.subject_id,
+            MemoPeerList::new(vec![MemoPeer{ slabref: slab.my_ref.clone(), status: MemoPeeringStatus::Resident }])
+        )]);
+    };
+
+    // The first update is sent immediately
+    let first = slab.new_memo_basic_noparent(None, MemoBody::Edit(HashMap::new()));
+    peer(&first);
+    assert_eq!(slab.metrics.peering_memos_sent.get(), 1);
+
+    // Those which follow within the window are held
+    for i in 0..10 {
+        let mut values = HashMap::new();
+        values.insert("i".to_string(), unbase::Value::from(i.to_string()));
+        let memoref = slab.new_memo_basic_noparent(None, MemoBody::Edit(values));
+        peer(&memoref);
+    }
+    assert_eq!(slab.metrics.peering_memos_sent.get(), 1);
+
+    // And then sent together in a single peering memo
+    slab.flush_peering();
+    assert_eq!(slab.metrics.peering_memos_sent.get(), 2);
+}
+
+#[test]
+fn peering_flusher_sends_held_updates() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+
+    let remote = slab.assert_slabref(12345, &[]);
+    slab.set_peering_window(Duration::from_millis(10));
+
+    for round in 1..3 {
+        for i in 0..5 {
+            let mut values = HashMap::new();
+            values.insert("i".to_string(), unbase::Value::from(format!("{}.{}", round, i)));
+            let memoref = slab.new_memo_basic_noparent(None, MemoBody::Edit(values));
+            slab.send_peering(&remote, Some(&memoref), vec![(
+                memoref.id(),
+                memoref.subject_id,
+                MemoPeerList::new(vec![MemoPeer{ slabref: slab.my_ref.clone(), status: MemoPeeringStatus::Resident }])
+            )]);
+        }
+
+        // At most the first was sent immediately. The rest are sent together once the window has elapsed.
+        // The flusher stops once it has nothing left to send, so it has to be started again for the second round
+        let sent = slab.metrics.peering_memos_sent.get();
+        eventually(&format!("held peering updates of round {} were not sent", round), || slab.metrics.peering_memos_sent.get() > sent );
+    }
+}
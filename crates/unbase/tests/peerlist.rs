@@ -0,0 +1,27 @@
+extern crate unbase;
+use unbase::slab::MemoBody;
+use std::collections::HashMap;
+
+mod common;
+use common::{eventually, settled};
+
+#[test]
+fn peerlists_are_bounded() {
+    let mut config = unbase::config::NetworkConfig::default();
+    config.slab.peerlist_limit = 2;
+
+    let net = unbase::Network::create_new_system_with_config(config);
+    let slabs : Vec<unbase::Slab> = (0..5).map(|_| unbase::Slab::new(&net) ).collect();
+
+    let subject_id = slabs[0].generate_subject_id();
+    let mut values = HashMap::new();
+    values.insert("animal_sound".to_string(), unbase::Value::from("Moo"));
+    let memoref = slabs[0].new_memo_basic_noparent(Some(subject_id), MemoBody::Edit(values));
+
+    eventually("memo should have been replicated", || !memoref.peerlist.read().unwrap().is_empty() );
+
+    // The durability target is out of reach with so few peers, but that mustn't have the memo sent around forever
+    settled("replication should have settled", || Some(slabs.iter().map(|slab| slab.count_of_memos_received() ).sum::<u64>()) );
+
+    assert!(memoref.peerlist.read().unwrap().len() <= 2, "peerlist should be pruned to the configured limit");
+}
@@ -0,0 +1,41 @@
+extern crate unbase;
+use unbase::config::NetworkConfig;
+use unbase::slab::EmissionStrategy;
+use unbase::subject::Subject;
+
+mod common;
+use common::eventually;
+
+#[test]
+fn plumtree_dissemination() {
+    let mut config = NetworkConfig::default();
+    config.slab.emission_strategy = EmissionStrategy::Plumtree;
+
+    let net = unbase::Network::create_new_system_with_config(config);
+    let slabs : Vec<unbase::Slab> = (0..5).map(|_| unbase::Slab::new(&net) ).collect();
+    let contexts : Vec<unbase::context::Context> = slabs.iter().map(|s| s.create_context() ).collect();
+
+    let record = Subject::new_kv(&contexts[0], "animal_sound", "Moo").unwrap();
+
+    // Subscribe to the subject everywhere, such that the edit below is applied to each context as it arrives.
+    // Memos which aren't pushed to a slab eagerly take a graft timeout or two to arrive
+    let records : Vec<Subject> = contexts.iter().skip(1).map(|context| {
+        let mut found = None;
+        eventually("subject should have been disseminated", || {
+            found = context.get_subject_by_id(record.id).ok();
+            found.is_some()
+        });
+        found.unwrap()
+    }).collect();
+
+    // Redundant paths are pruned as memos are received, so later edits are re-sent less
+    let redundant_before : u64 = slabs.iter().map(|s| s.count_of_memos_reduntantly_received() ).sum();
+    record.set_value("animal_sound", "Woof");
+
+    for record in records.iter() {
+        eventually("edit should have been disseminated", || record.get_value("animal_sound").unwrap() == "Woof" );
+    }
+
+    let redundant_after : u64 = slabs.iter().map(|s| s.count_of_memos_reduntantly_received() ).sum();
+    assert!(redundant_after - redundant_before < (slabs.len() * slabs.len()) as u64);
+}
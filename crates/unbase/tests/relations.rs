@@ -0,0 +1,27 @@
+extern crate unbase;
+use unbase::subject::Subject;
+use unbase::error::RetrieveError;
+
+#[test]
+fn clear_relation() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let owner = Subject::new_kv(&context, "name", "Alice").unwrap();
+    let dog   = Subject::new_kv(&context, "name", "Fido").unwrap();
+    let cat   = Subject::new_kv(&context, "name", "Tom").unwrap();
+
+    dog.set_relation(0, &owner);
+    dog.set_relation(1, &cat);
+    assert_eq!(dog.get_relation(0).unwrap().get_value("name").unwrap(), "Alice");
+
+    dog.clear_relation(0);
+    assert!(dog.get_relation(0).unwrap_err() == RetrieveError::NotFound, "cleared relation should not be found");
+    assert_eq!(dog.get_relation(1).unwrap().get_value("name").unwrap(), "Tom", "other slots are unaffected");
+
+    // Clearing an empty slot is harmless, and a cleared slot may be set again
+    dog.clear_relation(2);
+    dog.set_relation(0, &cat);
+    assert_eq!(dog.get_relation(0).unwrap().get_value("name").unwrap(), "Tom");
+}
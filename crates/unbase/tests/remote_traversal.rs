@@ -2,6 +2,9 @@ extern crate unbase;
 use unbase::subject::Subject;
 use std::{thread, time};
 
+mod common;
+use common::eventually;
+
 #[test]
 fn remote_traversal_simulated() {
 
@@ -86,6 +89,40 @@ fn remote_traversal_nondeterministic() {
 
 }
 
+#[test]
+fn remote_traversal_async() {
+
+    let net = unbase::Network::create_new_system();
+
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+
+    let context_a = slab_a.create_context();
+    let _context_b = slab_b.create_context();
+
+    let rec_a1 = Subject::new_kv(&context_a, "animal_sound", "Moo").unwrap();
+
+    rec_a1.set_value("animal_sound","Woof");
+    rec_a1.set_value("animal_sound","Meow");
+
+    // Memos may only be remotized once slab A knows that slab B has them
+    eventually("failed to remotize memos", || slab_a.remotize_memo_ids( &rec_a1.get_all_memo_ids() ).is_ok() );
+
+    // No separate thread required, as nothing here blocks on memo retrieval
+    let value = futures::executor::block_on(rec_a1.get_value_async("animal_sound")).expect("retrieval");
+    assert_eq!(value.unwrap(), "Meow");
+
+    let rec_a2 = Subject::new_kv(&context_a, "animal_type", "Cow").unwrap();
+    rec_a1.set_relation(3, &rec_a2);
+
+    eventually("failed to remotize memos", || slab_a.remotize_memo_ids( &rec_a1.get_all_memo_ids() ).is_ok() );
+
+    let links = futures::executor::block_on(rec_a1.get_head().project_all_relation_links_async(&slab_a)).expect("retrieval");
+    assert_eq!(links[3].subject_id, Some(rec_a2.id));
+    assert!(links.iter().filter(|link| link.slot_id != 3).all(|link| link.subject_id.is_none()));
+
+}
+
 /// Playing silly games with timing here in order to make it to work Initially
 /// Should be make substantially more robust.
 ///
@@ -138,7 +175,6 @@ fn remote_traversal_nondeterministic_udp() {
 
     let t2 = thread::spawn(|| {
         let net2 = unbase::Network::new();
-        net2.hack_set_next_slab_id(200);
 
         let udp2 = unbase::network::transport::TransportUDP::new("127.0.0.1:12002".to_string());
         net2.add_transport( Box::new(udp2.clone()) );
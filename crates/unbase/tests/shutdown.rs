@@ -0,0 +1,49 @@
+extern crate unbase;
+use unbase::subject::Subject;
+use unbase::slab::MemoBody;
+use std::collections::HashMap;
+use std::thread;
+
+#[test]
+fn shutdown_says_goodbye() {
+    let net = unbase::Network::create_new_system();
+    let simulator = unbase::network::transport::Simulator::new();
+    net.add_transport( Box::new(simulator.clone()) );
+
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+    let context_b = slab_b.create_context();
+
+    assert_eq!(slab_a.peer_slab_count(), 1, "Slab A should know one peer");
+
+    let _record = Subject::new_kv(&context_b, "animal_sound", "Moo").unwrap();
+
+    // Shutdown waits for Slab A to acknowledge the handoff, which takes the simulator to deliver
+    let shutdown = {
+        let slab_b = slab_b.clone();
+        thread::spawn(move || slab_b.shutdown())
+    };
+    while !shutdown.is_finished() {
+        simulator.advance_clock(1);
+        thread::yield_now();
+    }
+    assert!(shutdown.join().unwrap(), "Slab A should have acknowledged the handoff");
+
+    assert!(slab_b.is_closed());
+    assert_eq!(net.get_all_local_slabs().len(), 1, "Slab B should have left the network");
+
+    // Deliver the goodbye
+    simulator.advance_clock(1);
+
+    assert_eq!(slab_a.peer_slab_count(), 0, "Slab A should have forgotten Slab B");
+    assert!(slab_a.count_of_memos_received() > 0, "Slab A should have received Slab B's memos");
+
+    // Anything else sent Slab B's way is turned away
+    let received = slab_b.count_of_memos_received();
+    let memoref = slab_a.new_memo_basic_noparent(None, MemoBody::Edit(HashMap::new()));
+    let from_a = slab_a.my_ref.clone_for_slab(&slab_b);
+    let cloned = memoref.clone_for_slab(&from_a, &slab_b, true);
+
+    assert!(!cloned.is_resident(), "Slab B should have rejected the memo");
+    assert_eq!(slab_b.count_of_memos_received(), received);
+}
@@ -0,0 +1,109 @@
+extern crate unbase;
+use unbase::subject::Subject;
+use unbase::slab::MemoBody;
+use unbase::slab::storage::{self, StorageBackend, StorageKey};
+use std::collections::HashSet;
+
+#[test]
+fn reopen_slab_from_storage() {
+    let store = storage::Memory::new();
+
+    let (slab_id, subject_id) = {
+        let net = unbase::Network::create_new_system();
+        let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("new slab");
+        let context = slab.create_context();
+
+        let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+        record.set_value("animal_sound","Woof");
+
+        (slab.id, record.id)
+    };
+
+    assert!(!store.is_empty(), "Storage should contain records");
+
+    let net = unbase::Network::new();
+    let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("reopened slab");
+    assert_eq!(slab.id, slab_id, "Reopened slab should retain its id");
+
+    let context = slab.create_context();
+    let record = context.get_subject_by_id(subject_id).expect("subject should survive reopening");
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Woof");
+
+    // New slab ids must not collide with the restored one
+    let slab_b = unbase::Slab::new(&net);
+    assert!(slab_b.id != slab_id);
+
+    // Nor may the same stored slab be opened twice at once
+    match unbase::Slab::new_with_storage(&net, Box::new(store.clone())) {
+        Err(unbase::error::StorageError::SlabIdInUse(id)) => assert_eq!(id, slab_id),
+        _ => panic!("Opening the same stored slab twice should fail")
+    }
+}
+
+#[test]
+fn reopen_slab_from_file() {
+    let path = std::env::temp_dir().join(format!("unbase-storage-test-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let subject_id = {
+        let net = unbase::Network::create_new_system();
+        let slab = unbase::Slab::new_with_storage(&net, Box::new(storage::File::open(&path).unwrap())).expect("new slab");
+        let context = slab.create_context();
+
+        Subject::new_kv(&context, "animal_sound", "Moo").unwrap().id
+    };
+
+    let net = unbase::Network::new();
+    let slab = unbase::Slab::new_with_storage(&net, Box::new(storage::File::open(&path).unwrap())).expect("reopened slab");
+    let context = slab.create_context();
+    let record = context.get_subject_by_id(subject_id).expect("subject should survive reopening");
+    assert_eq!(record.get_value("animal_sound").unwrap(), "Moo");
+
+    drop(context);
+    drop(slab);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn counters_are_persisted_lazily() {
+    let store = storage::Memory::new();
+
+    let issued : HashSet<u64> = {
+        let net = unbase::Network::create_new_system();
+        let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("new slab");
+
+        // The first subject id reserves those which follow, so issuing them writes nothing further
+        let mut issued : HashSet<u64> = HashSet::new();
+        issued.insert(slab.generate_subject_id());
+        let counters = store.get(&StorageKey::Counters).unwrap().expect("counters should have been persisted");
+        issued.extend((0..100).map(|_| slab.generate_subject_id() ));
+        assert_eq!(store.get(&StorageKey::Counters).unwrap(), Some(counters));
+
+        // A slab which never gets to persist its latest counters, as if the process had died
+        std::mem::forget(slab);
+        issued
+    };
+
+    let net = unbase::Network::new();
+    let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("reopened slab");
+    for _ in 0..100 {
+        assert!(!issued.contains(&slab.generate_subject_id()), "Subject ids must not be reissued");
+    }
+}
+
+#[test]
+fn control_memos_are_not_stored() {
+    let store = storage::Memory::new();
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("new slab");
+
+    let edit = slab.new_memo_basic_noparent(Some(slab.generate_subject_id()), MemoBody::Edit(Default::default()));
+    let goodbye = slab.new_memo_basic_noparent(None, MemoBody::Goodbye(slab.id));
+
+    // Storing a memo requires its id, but there's no call to calculate one for a memo which isn't stored
+    assert!(edit.has_id());
+    assert!(!goodbye.has_id(), "A control memo should not have been hashed for storage");
+
+    assert!(store.get(&StorageKey::Memo(edit.id())).unwrap().is_some());
+    assert!(store.get(&StorageKey::Memo(goodbye.id())).unwrap().is_none());
+}
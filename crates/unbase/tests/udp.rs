@@ -19,7 +19,6 @@ fn test_udp() {
 
     let t2 = thread::spawn(|| {
         let net2 = unbase::Network::new();
-        net2.hack_set_next_slab_id(200);
 
         let udp2 = unbase::network::transport::TransportUDP::new("127.0.0.1:1337".to_string());
         net2.add_transport( Box::new(udp2.clone()) );
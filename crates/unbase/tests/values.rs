@@ -0,0 +1,128 @@
+extern crate unbase;
+extern crate serde_json;
+use unbase::Value;
+use unbase::subject::Subject;
+use unbase::slab::{MemoBody,storage};
+use std::collections::HashMap;
+
+fn sample_values () -> HashMap<String, Value> {
+    let mut map = HashMap::new();
+    map.insert("legs".to_string(), Value::I64(4));
+
+    let mut vals = HashMap::new();
+    vals.insert("name".to_string(),     Value::from("Fido"));
+    vals.insert("age".to_string(),      Value::I64(7));
+    vals.insert("weight".to_string(),   Value::F64(21.5));
+    vals.insert("good".to_string(),     Value::Bool(true));
+    vals.insert("owner".to_string(),    Value::Null);
+    vals.insert("chip".to_string(),     Value::Bytes(vec![0, 1, 255]));
+    vals.insert("tricks".to_string(),   Value::List(vec![Value::from("sit"), Value::from("stay")]));
+    vals.insert("anatomy".to_string(),  Value::Map(map));
+    vals.insert("friend".to_string(),   Value::Subject(42));
+    vals
+}
+
+#[test]
+fn typed_values() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let cat = Subject::new_kv(&context, "animal_type", "Cat").unwrap();
+    let dog = Subject::new(&context, sample_values(), false).unwrap();
+
+    for (key, value) in sample_values() {
+        assert_eq!(dog.get_typed_value(&key), Some(value));
+    }
+    assert_eq!(dog.get_i64("age"),    Some(7));
+    assert_eq!(dog.get_f64("age"),    Some(7.0));
+    assert_eq!(dog.get_f64("weight"), Some(21.5));
+    assert_eq!(dog.get_bool("good"),  Some(true));
+    assert_eq!(dog.get_i64("name"),   None);
+
+    // Strings read as they always have
+    assert_eq!(dog.get_value("name").unwrap(), "Fido");
+    assert_eq!(dog.get_value("age").unwrap(),  "7");
+
+    dog.set_typed_value("age", 8i64);
+    dog.set_typed_value("friend", &cat);
+    assert_eq!(dog.get_i64("age"), Some(8));
+    assert_eq!(dog.get_subject_ref("friend").unwrap().get_value("animal_type").unwrap(), "Cat");
+
+    // Values of different types are different memos, even if they'd be rendered the same
+    dog.set_value("age", "8");
+    assert_eq!(dog.get_typed_value("age"), Some(Value::from("8")));
+}
+
+#[test]
+fn typed_values_roundtrip() {
+    for (_, value) in sample_values() {
+        let encoded = serde_json::to_string(&value).expect("serialize");
+        let decoded : Value = serde_json::from_str(&encoded).expect("deserialize");
+        assert_eq!(decoded, value);
+    }
+
+    // Tagged by variant name, as stored and sent before
+    assert_eq!(serde_json::to_string(&Value::Null).unwrap(), r#""Null""#);
+    assert_eq!(serde_json::to_string(&Value::List(vec![Value::I64(3), Value::from("Moo")])).unwrap(), r#"{"List":[{"I64":3},{"String":"Moo"}]}"#);
+
+    let store = storage::Memory::new();
+
+    let subject_id = {
+        let net = unbase::Network::create_new_system();
+        let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("new slab");
+        let context = slab.create_context();
+
+        Subject::new(&context, sample_values(), false).unwrap().id
+    };
+
+    let net = unbase::Network::new();
+    let slab = unbase::Slab::new_with_storage(&net, Box::new(store.clone())).expect("reopened slab");
+    let context = slab.create_context();
+    let dog = context.get_subject_by_id(subject_id).expect("subject should survive reopening");
+
+    for (key, value) in sample_values() {
+        assert_eq!(dog.get_typed_value(&key), Some(value));
+    }
+}
+
+#[test]
+fn concurrent_value_versions() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let dog = Subject::new_kv(&context, "name", "Fido").unwrap();
+    assert_eq!(dog.get_value_versions("name").len(), 1);
+    assert!(dog.get_value_versions("missing").is_empty());
+    let base = dog.get_head();
+
+    let mut vals = HashMap::new();
+    vals.insert("name".to_string(), Value::from("Max"));
+
+    // Two writers rename the dog concurrently
+    dog.set_value("name", "Rex");
+    let concurrent = slab.new_memo_basic(Some(dog.id), base.clone(), MemoBody::Edit(vals.clone()));
+    dog.apply_head(&concurrent.to_head());
+
+    let versions = dog.get_value_versions("name");
+    assert_eq!(versions.len(), 2);
+    assert!(versions.iter().any(|v| v.memo_id == concurrent.id() && v.value == Value::from("Max") ));
+    assert!(versions.iter().any(|v| v.value == Value::from("Rex") ));
+
+    dog.resolve_value("name", &versions, "Rex Max");
+    let versions = dog.get_value_versions("name");
+    assert_eq!(versions.len(), 1);
+    assert_eq!(dog.get_value("name").unwrap(), "Rex Max");
+
+    // A version which this subject has not yet seen is superseded by the resolution too
+    vals.insert("name".to_string(), Value::from("Spot"));
+    let unseen = slab.new_memo_basic(Some(dog.id), base, MemoBody::Edit(vals));
+    let unseen_versions = unseen.to_head().project_value_versions(&context, "name");
+    assert_eq!(unseen_versions[0].memo_id, unseen.id());
+
+    dog.resolve_value("name", &unseen_versions, "Buddy");
+    assert_eq!(dog.get_head().len(), 1);
+    assert_eq!(dog.get_value_versions("name").len(), 1);
+    assert_eq!(dog.get_value("name").unwrap(), "Buddy");
+}
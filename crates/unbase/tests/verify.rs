@@ -0,0 +1,66 @@
+extern crate unbase;
+use unbase::context::ContextRef;
+use unbase::memorefhead::MemoRefHead;
+use unbase::slab::{IntegrityFinding,MemoBody,MemoPeer,MemoPeerList,MemoPeeringStatus};
+use unbase::subject::Subject;
+use std::collections::HashMap;
+use std::{thread, time};
+
+#[test]
+fn verify_clean_slabs() {
+    let net = unbase::Network::create_new_system();
+    let slab_a = unbase::Slab::new(&net);
+    let slab_b = unbase::Slab::new(&net);
+
+    let context_a = slab_a.create_context();
+    let _context_b = slab_b.create_context();
+
+    let record = Subject::new_kv(&context_a, "animal_sound", "Moo").unwrap();
+    record.set_value("animal_sound", "Woof");
+    thread::sleep(time::Duration::from_millis(50));
+
+    for slab in [&slab_a, &slab_b].iter() {
+        let report = slab.verify();
+        assert!(report.memorefs > 0);
+        assert!(report.is_clean(), "unexpected findings: {:?}", report.findings);
+    }
+}
+
+#[test]
+fn verify_mutually_descending_head() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+    let context = slab.create_context();
+
+    let record = Subject::new_kv(&context, "animal_sound", "Moo").unwrap();
+    let initial = record.get_head().to_vec();
+    record.set_value("animal_sound", "Woof");
+    let edit = record.get_head().to_vec();
+
+    let bad_head = MemoRefHead::new_from_vec(vec![edit[0].clone(), initial[0].clone()]);
+    let _bad = Subject::reconstitute(ContextRef::Strong(context.clone()), bad_head);
+
+    let findings = slab.verify().findings;
+    assert!(findings.contains(&IntegrityFinding::MutuallyDescendingHead{
+        subject_id:        Some(record.id),
+        memo_id:           edit[0].id(),
+        descended_memo_id: initial[0].id(),
+    }), "findings: {:?}", findings);
+}
+
+#[test]
+fn verify_reports_refused_self_peers() {
+    let net = unbase::Network::create_new_system();
+    let slab = unbase::Slab::new(&net);
+
+    let memoref = slab.new_memo_basic_noparent(None, MemoBody::Edit(HashMap::new()));
+    let self_peer = MemoPeer{ slabref: slab.my_ref.clone(), status: MemoPeeringStatus::Resident };
+
+    assert_eq!(memoref.update_peer(&slab.my_ref, MemoPeeringStatus::Resident, 10), Err(IntegrityFinding::SelfPeer{ memo_id: memoref.id() }));
+    assert!(slab.verify().is_clean(), "Nothing was reported to the slab");
+
+    // Peerlists asserted by the slab are applied less the self-peer, which is reported
+    slab.assert_memoref(memoref.id(), None, MemoPeerList::new(vec![self_peer]), None);
+    assert!(memoref.peerlist.read().unwrap().iter().all(|p| p.slabref.slab_id != slab.id ));
+    assert_eq!(slab.verify().findings, vec![IntegrityFinding::SelfPeer{ memo_id: memoref.id() }]);
+}